@@ -0,0 +1,3 @@
+-- Pending authorizations expire after a configurable maximum age
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN created_at BIGINT NOT NULL DEFAULT 0;
+UPDATE oauth2_pending_authorizations SET created_at = UNIX_TIMESTAMP();
\ No newline at end of file
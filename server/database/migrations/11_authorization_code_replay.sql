@@ -0,0 +1,6 @@
+-- Authorization codes are marked as used on exchange instead of being deleted,
+-- so that a replayed code can be detected. Used codes are purged once they expire.
+ALTER TABLE oauth2_authorization_codes ADD COLUMN used BOOL NOT NULL DEFAULT FALSE;
+
+-- The authorization code the refresh token family was issued from, if any
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN authorization_code VARCHAR(32) DEFAULT NULL;
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Scopes a user has granted to a client, so that repeated authorizations can skip the consent step
+CREATE TABLE user_consents (
+    user_id VARCHAR(64) NOT NULL,
+    client_id VARCHAR(32) NOT NULL,
+    scopes TEXT DEFAULT NULL,
+    granted_at BIGINT NOT NULL,
+    PRIMARY KEY (user_id, client_id)
+);
+
+-- The OpenID Connect `prompt` parameter provided in the authorization request
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN prompt TEXT DEFAULT NULL;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- The time at which the user authenticated, for the OpenID Connect `auth_time` claim and `max_age` parameter
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN auth_time BIGINT DEFAULT NULL;
+ALTER TABLE oauth2_authorization_codes ADD COLUMN auth_time BIGINT DEFAULT NULL;
+ALTER TABLE oauth2_access_tokens ADD COLUMN auth_time BIGINT DEFAULT NULL;
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN auth_time BIGINT DEFAULT NULL;
\ No newline at end of file
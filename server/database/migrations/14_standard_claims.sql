@@ -0,0 +1,8 @@
+-- The time at which the user's profile last changed, for the OpenID Connect `updated_at` claim
+ALTER TABLE users ADD COLUMN updated_at BIGINT NOT NULL DEFAULT 0;
+UPDATE users SET updated_at = UNIX_TIMESTAMP();
+
+-- Whether ID tokens contain the non-standard `sub_email`, `sub_name` and `sub_is_admin` claims.
+-- Existing clients may rely on these claims, so they keep receiving them.
+ALTER TABLE oauth2_clients ADD COLUMN legacy_id_token_claims BOOL NOT NULL DEFAULT FALSE;
+UPDATE oauth2_clients SET legacy_id_token_claims = TRUE;
\ No newline at end of file
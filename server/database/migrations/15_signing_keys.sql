@@ -0,0 +1,10 @@
+-- Keys used to sign OpenID Connect ID tokens, rotated over time
+CREATE TABLE oidc_signing_keys (
+    kid VARCHAR(64) NOT NULL PRIMARY KEY,
+    private_key TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    state VARCHAR(16) NOT NULL,
+    created_at BIGINT NOT NULL,
+    activated_at BIGINT DEFAULT NULL,
+    retired_at BIGINT DEFAULT NULL
+);
\ No newline at end of file
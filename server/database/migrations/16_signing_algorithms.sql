@@ -0,0 +1,2 @@
+ALTER TABLE oidc_signing_keys ADD COLUMN algorithm VARCHAR(16) NOT NULL DEFAULT 'RS256';
+ALTER TABLE oauth2_clients ADD COLUMN id_token_signed_response_alg VARCHAR(16) NOT NULL DEFAULT 'RS256';
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE oauth2_access_tokens ADD COLUMN jti VARCHAR(32) DEFAULT NULL;
+CREATE UNIQUE INDEX oauth2_access_tokens_jti ON oauth2_access_tokens (jti);
+ALTER TABLE oauth2_clients ADD COLUMN access_token_format VARCHAR(16) NOT NULL DEFAULT 'Opaque';
\ No newline at end of file
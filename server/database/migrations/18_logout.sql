@@ -0,0 +1,9 @@
+-- Redirect URIs clients may use after RP-initiated logout
+CREATE TABLE oauth2_client_post_logout_redirect_uris (
+    client_id VARCHAR(32) NOT NULL,
+    redirect_uri VARCHAR(512) NOT NULL,
+    PRIMARY KEY (client_id, redirect_uri)
+);
+
+-- The URI logout tokens are sent to, OpenID Connect Back-Channel Logout 1.0
+ALTER TABLE oauth2_clients ADD COLUMN backchannel_logout_uri TEXT DEFAULT NULL;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Login sessions, so a user logged in for one client need not log in again for another
+CREATE TABLE user_sessions (
+    session_id VARCHAR(32) NOT NULL PRIMARY KEY,
+    -- SHA-256 hash of the token in the session cookie, the token itself is not stored
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    user_id VARCHAR(64) NOT NULL,
+    -- When the user authenticated
+    auth_time BIGINT NOT NULL,
+    -- The authentication methods used, space separated, RFC 8176
+    amr TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    user_agent TEXT DEFAULT NULL
+);
+
+CREATE INDEX user_sessions_user_id ON user_sessions (user_id);
\ No newline at end of file
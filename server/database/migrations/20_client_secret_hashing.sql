@@ -0,0 +1,8 @@
+-- Client secrets are stored as their SHA-256 hash, hex encoded
+ALTER TABLE oauth2_clients RENAME COLUMN client_secret TO client_secret_hash;
+ALTER TABLE oauth2_clients MODIFY client_secret_hash VARCHAR(64) NOT NULL;
+UPDATE oauth2_clients SET client_secret_hash = SHA2(client_secret_hash, 256);
+
+-- After rotating the secret, the previous secret remains valid for a grace period
+ALTER TABLE oauth2_clients ADD COLUMN previous_client_secret_hash VARCHAR(64) DEFAULT NULL;
+ALTER TABLE oauth2_clients ADD COLUMN previous_client_secret_expires_at BIGINT DEFAULT NULL;
\ No newline at end of file
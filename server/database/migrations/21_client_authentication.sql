@@ -0,0 +1,14 @@
+-- The method the client authenticates with at the token endpoint, RFC 7591 Section 2
+ALTER TABLE oauth2_clients ADD COLUMN token_endpoint_auth_method VARCHAR(32) NOT NULL DEFAULT 'ClientSecretPost';
+UPDATE oauth2_clients SET token_endpoint_auth_method = 'None' WHERE is_public = TRUE;
+
+-- The JWK Set the client signs its assertions with, for the private_key_jwt method
+ALTER TABLE oauth2_clients ADD COLUMN jwks TEXT DEFAULT NULL;
+
+-- Identifiers of client assertions that were used, to prevent replay, RFC 7523 Section 3
+CREATE TABLE oauth2_client_assertions (
+    client_id VARCHAR(32) NOT NULL,
+    jti VARCHAR(255) NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY (client_id, jti)
+);
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Client metadata, RFC 7591 Section 2. Existing clients may use every grant and response type
+ALTER TABLE oauth2_clients ADD COLUMN grant_types TEXT DEFAULT NULL;
+ALTER TABLE oauth2_clients ADD COLUMN response_types TEXT DEFAULT NULL;
+ALTER TABLE oauth2_clients ADD COLUMN contacts TEXT DEFAULT NULL;
+UPDATE oauth2_clients SET
+    grant_types = '["authorization_code","implicit","refresh_token","client_credentials"]',
+    response_types = '["code","token","id_token token"]',
+    contacts = '[]';
+ALTER TABLE oauth2_clients MODIFY grant_types TEXT NOT NULL;
+ALTER TABLE oauth2_clients MODIFY response_types TEXT NOT NULL;
+ALTER TABLE oauth2_clients MODIFY contacts TEXT NOT NULL;
+ALTER TABLE oauth2_clients ADD COLUMN logo_uri TEXT DEFAULT NULL;
+
+-- The hash of the token a dynamically registered client manages its registration with, RFC 7592
+ALTER TABLE oauth2_clients ADD COLUMN registration_access_token_hash VARCHAR(64) DEFAULT NULL;
\ No newline at end of file
@@ -0,0 +1,18 @@
+-- TOTP two-factor authentication for users of the local provider, RFC 6238
+CREATE TABLE user_totp (
+    user_id VARCHAR(64) NOT NULL PRIMARY KEY,
+    -- Base32 encoded shared secret
+    secret VARCHAR(64) NOT NULL,
+    -- False until the user confirmed enrollment with a first code
+    enabled BOOL NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    -- The last time step a code was accepted for, codes cannot be used twice
+    last_used_step BIGINT DEFAULT NULL
+);
+
+CREATE TABLE user_totp_recovery_codes (
+    user_id VARCHAR(64) NOT NULL,
+    -- SHA-256 hash of the recovery code
+    code_hash VARCHAR(64) NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
\ No newline at end of file
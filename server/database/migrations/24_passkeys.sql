@@ -0,0 +1,21 @@
+-- WebAuthn credentials of users, used to log in with a passkey or as second factor
+CREATE TABLE user_passkeys (
+    -- Base64url encoded credential ID, of at most 255 bytes
+    credential_id VARCHAR(340) NOT NULL PRIMARY KEY,
+    user_id VARCHAR(64) NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    -- Base64url encoded COSE public key
+    public_key TEXT NOT NULL,
+    sign_count BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT DEFAULT NULL
+);
+
+-- Challenges of WebAuthn ceremonies in progress. Every challenge can be used once
+CREATE TABLE webauthn_challenges (
+    challenge VARCHAR(64) NOT NULL PRIMARY KEY,
+    ceremony VARCHAR(16) NOT NULL,
+    -- The user registering a passkey, not set for authentication
+    user_id VARCHAR(64) DEFAULT NULL,
+    expires_at BIGINT NOT NULL
+);
\ No newline at end of file
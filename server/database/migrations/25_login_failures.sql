@@ -0,0 +1,13 @@
+-- Failed login attempts, per account and per client IP address, to slow down brute-force attacks
+CREATE TABLE login_failures (
+    -- Whether the subject is an account or an IP address
+    kind VARCHAR(16) NOT NULL,
+    -- The normalized username or email address, or the IP address
+    subject VARCHAR(255) NOT NULL,
+    -- The number of failed attempts since the last success, within the failure window
+    failures INT NOT NULL,
+    last_failure_at BIGINT NOT NULL,
+    -- No attempts are accepted until this time
+    blocked_until BIGINT NOT NULL,
+    PRIMARY KEY (kind, subject)
+);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Tokens emailed to users who forgot their password, to set a new password
+CREATE TABLE password_reset_tokens (
+    -- SHA-256 hash of the token, the token itself is only sent to the user
+    token_hash VARCHAR(64) NOT NULL PRIMARY KEY,
+    user_id VARCHAR(64) NOT NULL,
+    expires_at BIGINT NOT NULL
+);
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- The login session an authorization was granted with,
+-- so logging out of the session revokes the tokens issued from it
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN session_id VARCHAR(32) DEFAULT NULL;
+ALTER TABLE oauth2_authorization_codes ADD COLUMN session_id VARCHAR(32) DEFAULT NULL;
+ALTER TABLE oauth2_access_tokens ADD COLUMN session_id VARCHAR(32) DEFAULT NULL;
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN session_id VARCHAR(32) DEFAULT NULL;
+
+CREATE INDEX oauth2_authorization_codes_session_id ON oauth2_authorization_codes (session_id);
+CREATE INDEX oauth2_access_tokens_session_id ON oauth2_access_tokens (session_id);
+CREATE INDEX oauth2_refresh_tokens_session_id ON oauth2_refresh_tokens (session_id);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Client names were already checked to be unique before creating or renaming a client,
+-- the constraint makes sure concurrent requests cannot create duplicates
+ALTER TABLE oauth2_clients ADD CONSTRAINT oauth2_clients_name UNIQUE (name);
\ No newline at end of file
@@ -0,0 +1,7 @@
+ALTER TABLE oauth2_clients ADD COLUMN is_public BOOL NOT NULL DEFAULT FALSE;
+
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN code_challenge TEXT DEFAULT NULL;
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN code_challenge_method TEXT DEFAULT NULL;
+
+ALTER TABLE oauth2_authorization_codes ADD COLUMN code_challenge TEXT DEFAULT NULL;
+ALTER TABLE oauth2_authorization_codes ADD COLUMN code_challenge_method TEXT DEFAULT NULL;
@@ -0,0 +1,8 @@
+-- Access tokens issued with the client credentials grant are not bound to a user
+ALTER TABLE oauth2_access_tokens MODIFY user_id VARCHAR(64) DEFAULT NULL;
+
+CREATE TABLE oauth2_client_permitted_scopes (
+    client_id VARCHAR(32) NOT NULL,
+    scope VARCHAR(64) NOT NULL,
+    PRIMARY KEY (client_id, scope)
+);
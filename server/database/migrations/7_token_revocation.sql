@@ -0,0 +1,3 @@
+-- The refresh token an access token was derived from, if any.
+-- Revoking a refresh token revokes all access tokens derived from it.
+ALTER TABLE oauth2_access_tokens ADD COLUMN refresh_token VARCHAR(32) DEFAULT NULL;
\ No newline at end of file
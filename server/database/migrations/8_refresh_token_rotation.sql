@@ -0,0 +1,12 @@
+-- Refresh tokens are rotated on every use. All refresh tokens descending from
+-- the same authorization form a family, identified by the first token of the family.
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN family_id VARCHAR(32) NOT NULL DEFAULT '';
+-- When this refresh token was issued, used for the idle lifetime
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN issued_at BIGINT NOT NULL DEFAULT 0;
+-- The absolute expiry of the family
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN expires_at BIGINT NOT NULL DEFAULT 0;
+-- Whether the refresh token has been exchanged already
+ALTER TABLE oauth2_refresh_tokens ADD COLUMN used BOOL NOT NULL DEFAULT FALSE;
+
+-- Existing refresh tokens each start their own family, expiring with the default lifetime of 30 days
+UPDATE oauth2_refresh_tokens SET family_id = token, issued_at = UNIX_TIMESTAMP(), expires_at = UNIX_TIMESTAMP() + 2592000;
\ No newline at end of file
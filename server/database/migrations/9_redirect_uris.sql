@@ -0,0 +1,23 @@
+-- Clients can register multiple redirect URIs
+CREATE TABLE oauth2_client_redirect_uris (
+    client_id VARCHAR(32) NOT NULL,
+    redirect_uri VARCHAR(512) NOT NULL,
+    PRIMARY KEY (client_id, redirect_uri)
+);
+
+INSERT INTO oauth2_client_redirect_uris (client_id, redirect_uri) SELECT client_id, redirect_uri FROM oauth2_clients;
+
+-- Native apps may use any port on a loopback redirect URI, RFC8252 Section 7.3
+ALTER TABLE oauth2_clients ADD COLUMN allow_loopback_any_port BOOL NOT NULL DEFAULT FALSE;
+
+-- The redirect URI used in the authorization request
+ALTER TABLE oauth2_pending_authorizations ADD COLUMN redirect_uri TEXT DEFAULT NULL;
+ALTER TABLE oauth2_authorization_codes ADD COLUMN redirect_uri TEXT DEFAULT NULL;
+
+UPDATE oauth2_pending_authorizations p JOIN oauth2_clients c ON p.client_id = c.client_id SET p.redirect_uri = c.redirect_uri;
+UPDATE oauth2_authorization_codes a JOIN oauth2_clients c ON a.client_id = c.client_id SET a.redirect_uri = c.redirect_uri;
+
+ALTER TABLE oauth2_pending_authorizations MODIFY redirect_uri TEXT NOT NULL;
+ALTER TABLE oauth2_authorization_codes MODIFY redirect_uri TEXT NOT NULL;
+
+ALTER TABLE oauth2_clients DROP COLUMN redirect_uri;
\ No newline at end of file
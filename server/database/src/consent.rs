@@ -0,0 +1,122 @@
+use crate::driver::Database;
+use sqlx::{FromRow, Result};
+use std::collections::HashSet;
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// The scopes a user has granted to a client.
+/// If a later authorization request by the client only requests granted scopes,
+/// the user does not have to grant consent again.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserConsent {
+    pub user_id: String,
+    pub client_id: String,
+    /// The granted scopes, space separated
+    pub scopes: Option<String>,
+    /// When consent was last granted
+    pub granted_at: i64,
+}
+
+impl UserConsent {
+    #[instrument]
+    pub async fn get(driver: &Database, user_id: &str, client_id: &str) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM user_consents WHERE user_id = ? AND client_id = ?")
+            .bind(user_id)
+            .bind(client_id)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// List all consents granted by the user.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_for_user(driver: &Database, user_id: &str) -> Result<Vec<Self>> {
+        sqlx::query_as("SELECT * FROM user_consents WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&**driver)
+            .await
+    }
+
+    /// Record that the user granted the scopes to the client.
+    /// Scopes granted earlier remain granted.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn grant(
+        driver: &Database,
+        user_id: &str,
+        client_id: &str,
+        scopes: &HashSet<String>,
+    ) -> Result<()> {
+        let mut granted = Self::get(driver, user_id, client_id)
+            .await?
+            .map(|c| c.scopes())
+            .unwrap_or_default();
+        granted.extend(scopes.iter().cloned());
+
+        let mut granted = granted.into_iter().collect::<Vec<_>>();
+        granted.sort();
+        let granted = (!granted.is_empty()).then(|| granted.join(" "));
+
+        sqlx::query("INSERT INTO user_consents (user_id, client_id, scopes, granted_at) VALUES (?, ?, ?, ?) ON DUPLICATE KEY UPDATE scopes = VALUES(scopes), granted_at = VALUES(granted_at)")
+            .bind(user_id)
+            .bind(client_id)
+            .bind(granted)
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The set of granted scopes.
+    pub fn scopes(&self) -> HashSet<String> {
+        self.scopes
+            .as_ref()
+            .map(|f| f.split(" ").map(|c| c.to_string()).collect::<HashSet<_>>())
+            .unwrap_or_default()
+    }
+
+    /// Whether all requested scopes have been granted.
+    pub fn covers(&self, requested: &HashSet<String>) -> bool {
+        requested.is_subset(&self.scopes())
+    }
+
+    /// Withdraw the consent.
+    /// All tokens issued to the client for the user are revoked.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn withdraw(self, driver: &Database) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM oauth2_access_tokens WHERE user_id = ? AND client_id = ?")
+            .bind(&self.user_id)
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM oauth2_refresh_tokens WHERE user_id = ? AND client_id = ?")
+            .bind(&self.user_id)
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_consents WHERE user_id = ? AND client_id = ?")
+            .bind(&self.user_id)
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
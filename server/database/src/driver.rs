@@ -26,6 +26,12 @@ impl Database {
     }
 }
 
+/// Whether the query failed because a unique column already contains the value
+pub fn is_unique_violation(e: &Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+}
+
 impl Deref for Database {
     type Target = MySqlPool;
 
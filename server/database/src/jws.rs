@@ -0,0 +1,90 @@
+//! Signing of JSON Web Tokens with a custom `typ` header.
+//!
+//! ID tokens are signed with `jwt-simple`, which always uses the `JWT` type.
+//! Other tokens, such as JWT access tokens, must carry their own type.
+
+use crate::signing_key::{JwtSigningAlgorithm, SigningKey};
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use p256::pkcs8::DecodePrivateKey;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs1v15;
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde::Serialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JwsError {
+    #[error("Invalid keypair: {0}")]
+    Keypair(String),
+    #[error("Failed to serialize token: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    alg: &'a str,
+    kid: &'a str,
+    typ: &'a str,
+}
+
+/// Sign the claims with the signing key, using the compact serialization.
+/// The `typ` header is set to `typ`, the `kid` header to the key ID of the signing key.
+///
+/// # Errors
+///
+/// - If the private key cannot be parsed
+/// - If the claims cannot be serialized
+///
+/// # Further reading
+/// - [RFC 7515, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7515#section-3.1)
+pub fn sign<C: Serialize>(
+    signing_key: &SigningKey,
+    typ: &str,
+    claims: &C,
+) -> Result<String, JwsError> {
+    let header = Header {
+        alg: signing_key.algorithm.name(),
+        kid: &signing_key.kid,
+        typ,
+    };
+
+    let signing_input = format!(
+        "{}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?),
+    );
+
+    let signature = match signing_key.algorithm {
+        JwtSigningAlgorithm::RS256 => {
+            // Keys imported from the configuration may be PKCS#1 encoded
+            let private_key = RsaPrivateKey::from_pkcs8_pem(&signing_key.private_key)
+                .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&signing_key.private_key))
+                .map_err(|e| JwsError::Keypair(e.to_string()))?;
+
+            pkcs1v15::SigningKey::<Sha256>::new(private_key)
+                .sign(signing_input.as_bytes())
+                .to_vec()
+        }
+        JwtSigningAlgorithm::ES256 => {
+            let private_key = p256::ecdsa::SigningKey::from_pkcs8_pem(&signing_key.private_key)
+                .map_err(|e| JwsError::Keypair(e.to_string()))?;
+
+            let signature: p256::ecdsa::Signature = private_key.sign(signing_input.as_bytes());
+            signature.to_vec()
+        }
+        JwtSigningAlgorithm::EdDSA => {
+            let private_key = ed25519_compact::KeyPair::from_pem(&signing_key.private_key)
+                .map_err(|e| JwsError::Keypair(e.to_string()))?;
+
+            private_key.sk.sign(signing_input.as_bytes(), None).to_vec()
+        }
+    };
+
+    Ok(format!(
+        "{signing_input}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
@@ -1,8 +1,17 @@
 use rand::Rng;
+use sha2::{Digest, Sha256};
 
+pub mod consent;
 pub mod constant_access_tokens;
 pub mod driver;
+pub mod jws;
+pub mod login_failure;
 pub mod oauth2_client;
+pub mod passkey;
+pub mod password_reset;
+pub mod session;
+pub mod signing_key;
+pub mod totp;
 pub mod user;
 
 fn generate_string(len: usize) -> String {
@@ -13,6 +22,12 @@ fn generate_string(len: usize) -> String {
         .collect()
 }
 
+/// Hash a token or secret before storing it, so a leaked database does not leak usable credentials.
+/// Only use this for random values, a fast hash is not suitable for passwords.
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 /// Macro to show the type of an enum as a String.
 /// The enum itself should only implement [sqlx::Encode] and [sqlx::Decode]
 // Issue: https://github.com/launchbadge/sqlx/issues/1241
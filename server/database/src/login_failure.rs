@@ -0,0 +1,161 @@
+use crate::driver::Database;
+use crate::impl_enum_type;
+use serde::{Deserialize, Serialize};
+use sqlx::{Decode, Encode, FromRow, Result};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// What failed login attempts are counted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub enum LoginFailureKind {
+    /// The username or email address attempts were made for
+    Account,
+    /// The IP address attempts were made from
+    Ip,
+}
+
+impl_enum_type!(LoginFailureKind);
+
+/// The failed login attempts for an account or IP address.
+#[derive(Debug, Clone, FromRow)]
+pub struct LoginFailures {
+    pub kind: LoginFailureKind,
+    /// The normalized username or email address, or the IP address
+    pub subject: String,
+    /// The number of failed attempts since the last success, within the failure window
+    pub failures: i32,
+    pub last_failure_at: i64,
+    /// No attempts are accepted until this time.
+    /// In the past if attempts are accepted.
+    pub blocked_until: i64,
+}
+
+impl LoginFailures {
+    /// Get the failed attempts for an account or IP address.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get(
+        driver: &Database,
+        kind: LoginFailureKind,
+        subject: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM login_failures WHERE kind = ? AND subject = ?")
+            .bind(kind)
+            .bind(subject)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// Record a failed attempt.
+    /// Failures older than `window` seconds are forgotten, counting starts again at 1.
+    /// Returns the failures including this attempt.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn record_failure(
+        driver: &Database,
+        kind: LoginFailureKind,
+        subject: &str,
+        window: i64,
+    ) -> Result<Self> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut tx = driver.begin().await?;
+
+        // Counted in the query itself, so concurrent attempts are all recorded
+        sqlx::query("INSERT INTO login_failures (kind, subject, failures, last_failure_at, blocked_until) VALUES (?, ?, 1, ?, 0) ON DUPLICATE KEY UPDATE failures = IF(last_failure_at <= ?, 1, failures + 1), last_failure_at = VALUES(last_failure_at)")
+            .bind(kind)
+            .bind(subject)
+            .bind(now)
+            .bind(now - window)
+            .execute(&mut *tx)
+            .await?;
+
+        let failures =
+            sqlx::query_as("SELECT * FROM login_failures WHERE kind = ? AND subject = ?")
+                .bind(kind)
+                .bind(subject)
+                .fetch_one(&mut *tx)
+                .await?;
+
+        tx.commit().await?;
+
+        Ok(failures)
+    }
+
+    /// Block attempts until the UNIX timestamp `until`.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn block_until(&mut self, driver: &Database, until: i64) -> Result<()> {
+        sqlx::query("UPDATE login_failures SET blocked_until = GREATEST(blocked_until, ?) WHERE kind = ? AND subject = ?")
+            .bind(until)
+            .bind(self.kind)
+            .bind(&self.subject)
+            .execute(&**driver)
+            .await?;
+
+        self.blocked_until = self.blocked_until.max(until);
+
+        Ok(())
+    }
+
+    /// Forget the failed attempts for an account or IP address, lifting any lockout.
+    /// Returns false if there were no failed attempts.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn clear(driver: &Database, kind: LoginFailureKind, subject: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM login_failures WHERE kind = ? AND subject = ?")
+            .bind(kind)
+            .bind(subject)
+            .execute(&**driver)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List all accounts and IP addresses which are currently blocked.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn list_blocked(driver: &Database) -> Result<Vec<Self>> {
+        sqlx::query_as(
+            "SELECT * FROM login_failures WHERE blocked_until > ? ORDER BY blocked_until DESC",
+        )
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// Delete failed attempts which are outside the window of `window` seconds,
+    /// and no longer block attempts.
+    /// Returns the number of rows deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete_expired(driver: &Database, window: i64) -> Result<u64> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        Ok(sqlx::query(
+            "DELETE FROM login_failures WHERE last_failure_at <= ? AND blocked_until <= ?",
+        )
+        .bind(now - window)
+        .bind(now)
+        .execute(&**driver)
+        .await?
+        .rows_affected())
+    }
+}
@@ -1,22 +1,229 @@
 use crate::driver::Database;
+use crate::jws::{self, JwsError};
+use crate::session::UserSession;
+use crate::signing_key::{JwtSigningAlgorithm, SigningKey};
 use crate::user::User;
-use crate::{generate_string, impl_enum_type};
-use jwt_simple::algorithms::{RS256KeyPair, RSAKeyPairLike};
+use crate::{generate_string, hash_token, impl_enum_type};
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use jwt_simple::algorithms::{
+    ECDSAP256KeyPairLike, ES256KeyPair, Ed25519KeyPair, EdDSAKeyPairLike, RS256KeyPair,
+    RSAKeyPairLike,
+};
 use jwt_simple::claims::Claims;
 use serde::{Deserialize, Serialize};
-use sqlx::{Decode, Encode, FromRow, Result};
+use sha2::{Digest, Sha256, Sha512};
+use sqlx::types::Json;
+use sqlx::{Decode, Encode, FromRow, MySql, Result, Transaction};
 use std::collections::HashSet;
+use subtle::ConstantTimeEq;
 use thiserror::Error;
 use time::{Duration, OffsetDateTime};
 use tracing::instrument;
 
+/// The lifetime of access tokens in seconds.
+/// ID tokens expire together with the access token they were issued with.
+pub const ACCESS_TOKEN_LIFETIME: i64 = 60 * 60;
+
 #[derive(Debug, Clone, FromRow)]
 pub struct OAuth2Client {
     pub name: String,
-    pub redirect_uri: String,
     pub client_id: String,
-    pub client_secret: String,
+    /// The SHA-256 hash of the client secret, hex encoded.
+    /// The secret itself is only known when it is generated.
+    pub client_secret_hash: String,
+    /// The hash of the secret that was replaced by rotating the secret.
+    /// It remains valid until `previous_client_secret_expires_at`.
+    pub previous_client_secret_hash: Option<String>,
+    pub previous_client_secret_expires_at: Option<i64>,
     pub is_internal: bool,
+    /// Public clients cannot keep a secret, e.g. SPAs and mobile apps.
+    /// They are not required to provide their `client_secret`,
+    /// but must use PKCE for the authorization code flow.
+    pub is_public: bool,
+    /// Whether a loopback redirect URI may use any port.
+    /// Native apps listening on an ephemeral port require this, see RFC8252 Section 7.3.
+    pub allow_loopback_any_port: bool,
+    /// Whether ID tokens issued to the client contain the non-standard
+    /// `sub_email`, `sub_name` and `sub_is_admin` claims, for clients relying on them.
+    pub legacy_id_token_claims: bool,
+    /// The algorithm ID tokens issued to the client are signed with
+    pub id_token_signed_response_alg: JwtSigningAlgorithm,
+    /// The format of access tokens issued to the client
+    pub access_token_format: AccessTokenFormat,
+    /// The URI logout tokens are sent to when a user logs out.
+    /// See [OpenID Connect Back-Channel Logout 1.0](https://openid.net/specs/openid-connect-backchannel-1_0.html)
+    pub backchannel_logout_uri: Option<String>,
+    /// The method the client authenticates with at the token and revocation endpoints
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    /// The JWK Set containing the keys the client signs its assertions with, as JSON.
+    /// Only used with [TokenEndpointAuthMethod::PrivateKeyJwt]
+    pub jwks: Option<String>,
+    /// The grant types the client may use at the token endpoint
+    pub grant_types: Json<Vec<GrantType>>,
+    /// The response types the client may use at the authorization endpoint
+    pub response_types: Json<Vec<AuthorizationType>>,
+    /// The URL of the client's logo
+    pub logo_uri: Option<String>,
+    /// The email addresses of the people responsible for the client
+    pub contacts: Json<Vec<String>>,
+    /// The SHA-256 hash of the token with which the client manages its own registration, hex encoded.
+    /// Only set for clients that were registered dynamically, see RFC 7592.
+    pub registration_access_token_hash: Option<String>,
+}
+
+/// A grant type a client may use.
+///
+/// # Further reading
+/// - [RFC 7591, Section 2](https://datatracker.ietf.org/doc/html/rfc7591#section-2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    AuthorizationCode,
+    Implicit,
+    RefreshToken,
+    ClientCredentials,
+}
+
+impl GrantType {
+    /// All supported grant types
+    pub const ALL: [Self; 4] = [
+        Self::AuthorizationCode,
+        Self::Implicit,
+        Self::RefreshToken,
+        Self::ClientCredentials,
+    ];
+
+    /// The name of the grant type, as used in client metadata
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AuthorizationCode => "authorization_code",
+            Self::Implicit => "implicit",
+            Self::RefreshToken => "refresh_token",
+            Self::ClientCredentials => "client_credentials",
+        }
+    }
+
+    /// Get the grant type by its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|g| g.name() == name)
+    }
+}
+
+/// The method a client authenticates with at the token endpoint.
+///
+/// # Further reading
+/// - [RFC 6749, Section 2.3.1](https://datatracker.ietf.org/doc/html/rfc6749#section-2.3.1)
+/// - [OpenID Connect Core 1.0, Section 9](https://openid.net/specs/openid-connect-core-1_0.html#ClientAuthentication)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEndpointAuthMethod {
+    /// The client secret is provided using HTTP Basic authentication
+    #[default]
+    ClientSecretBasic,
+    /// The client secret is provided in the request body
+    ClientSecretPost,
+    /// The client provides a JWT signed with one of the keys in its JWK Set
+    PrivateKeyJwt,
+    /// The client does not authenticate, for public clients
+    None,
+}
+
+impl_enum_type!(TokenEndpointAuthMethod);
+
+impl TokenEndpointAuthMethod {
+    /// All supported methods
+    pub const ALL: [Self; 4] = [
+        Self::ClientSecretBasic,
+        Self::ClientSecretPost,
+        Self::PrivateKeyJwt,
+        Self::None,
+    ];
+
+    /// The name of the method, as used in client metadata and the discovery document
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ClientSecretBasic => "client_secret_basic",
+            Self::ClientSecretPost => "client_secret_post",
+            Self::PrivateKeyJwt => "private_key_jwt",
+            Self::None => "none",
+        }
+    }
+
+    /// Get the method by its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    /// Whether the client authenticates with its client secret
+    pub fn uses_secret(&self) -> bool {
+        matches!(self, Self::ClientSecretBasic | Self::ClientSecretPost)
+    }
+}
+
+/// The format of the access tokens issued to a client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub enum AccessTokenFormat {
+    /// A random string, which resource servers must introspect
+    #[default]
+    Opaque,
+    /// A signed JWT, which resource servers can validate themselves.
+    /// See [RFC 9068](https://datatracker.ietf.org/doc/html/rfc9068)
+    Jwt,
+}
+
+impl_enum_type!(AccessTokenFormat);
+
+/// Settings of a client
+#[derive(Debug, Clone)]
+pub struct OAuth2ClientOptions {
+    /// See [OAuth2Client::is_public]
+    pub is_public: bool,
+    /// See [OAuth2Client::allow_loopback_any_port]
+    pub allow_loopback_any_port: bool,
+    /// See [OAuth2Client::legacy_id_token_claims]
+    pub legacy_id_token_claims: bool,
+    /// See [OAuth2Client::id_token_signed_response_alg]
+    pub id_token_signed_response_alg: JwtSigningAlgorithm,
+    /// See [OAuth2Client::access_token_format]
+    pub access_token_format: AccessTokenFormat,
+    /// The URIs the user may be redirected to after logging out
+    pub post_logout_redirect_uris: Vec<String>,
+    /// See [OAuth2Client::backchannel_logout_uri]
+    pub backchannel_logout_uri: Option<String>,
+    /// See [OAuth2Client::token_endpoint_auth_method]
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    /// See [OAuth2Client::jwks]
+    pub jwks: Option<String>,
+    /// See [OAuth2Client::grant_types]
+    pub grant_types: Vec<GrantType>,
+    /// See [OAuth2Client::response_types]
+    pub response_types: Vec<AuthorizationType>,
+    /// See [OAuth2Client::logo_uri]
+    pub logo_uri: Option<String>,
+    /// See [OAuth2Client::contacts]
+    pub contacts: Vec<String>,
+}
+
+impl Default for OAuth2ClientOptions {
+    /// A confidential client which may use every grant and response type
+    fn default() -> Self {
+        Self {
+            is_public: false,
+            allow_loopback_any_port: false,
+            legacy_id_token_claims: false,
+            id_token_signed_response_alg: JwtSigningAlgorithm::default(),
+            access_token_format: AccessTokenFormat::default(),
+            post_logout_redirect_uris: Vec::new(),
+            backchannel_logout_uri: None,
+            token_endpoint_auth_method: TokenEndpointAuthMethod::default(),
+            jwks: None,
+            grant_types: GrantType::ALL.to_vec(),
+            response_types: AuthorizationType::ALL.to_vec(),
+            logo_uri: None,
+            contacts: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +274,48 @@ impl OAuth2PendingAuthorization {
             Self::Unauthorized(v) => &v.nonce,
         }
     }
+
+    /// The redirect URI provided in the authorization request
+    pub fn redirect_uri(&self) -> &String {
+        match self {
+            Self::Authorized(v) => &v.redirect_uri,
+            Self::Unauthorized(v) => &v.redirect_uri,
+        }
+    }
+
+    /// The OpenID Connect `prompt` values provided in the authorization request
+    pub fn prompt(&self) -> &Option<String> {
+        match self {
+            Self::Authorized(v) => &v.prompt,
+            Self::Unauthorized(v) => &v.prompt,
+        }
+    }
+
+    pub fn code_challenge(&self) -> Option<CodeChallenge> {
+        match self {
+            Self::Authorized(v) => {
+                CodeChallenge::from_columns(&v.code_challenge, v.code_challenge_method)
+            }
+            Self::Unauthorized(v) => {
+                CodeChallenge::from_columns(&v.code_challenge, v.code_challenge_method)
+            }
+        }
+    }
+}
+
+/// The parameters of an authorization request,
+/// from which a pending authorization is created.
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest {
+    pub scopes: Option<String>,
+    pub state: Option<String>,
+    pub ty: AuthorizationType,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<CodeChallenge>,
+    /// The redirect URI, must be registered for the client
+    pub redirect_uri: String,
+    /// The OpenID Connect `prompt` values, space separated
+    pub prompt: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -77,6 +326,10 @@ pub struct OAuth2PendingAuthorizationUnauthorized {
     state: Option<String>,
     ty: AuthorizationType,
     nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<CodeChallengeMethod>,
+    redirect_uri: String,
+    prompt: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +341,19 @@ pub struct OAuth2PendingAuthorizationAuthorized {
     user_id: String,
     ty: AuthorizationType,
     nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<CodeChallengeMethod>,
+    redirect_uri: String,
+    prompt: Option<String>,
+    auth_time: Option<i64>,
+    session_id: Option<String>,
+}
+
+impl OAuth2PendingAuthorizationAuthorized {
+    /// The user who authorized the request
+    pub fn user_id(&self) -> &String {
+        &self.user_id
+    }
 }
 
 #[derive(FromRow)]
@@ -99,6 +365,12 @@ struct _OAuth2PendingAuthorization {
     user_id: Option<String>,
     ty: AuthorizationType,
     nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<CodeChallengeMethod>,
+    redirect_uri: String,
+    prompt: Option<String>,
+    auth_time: Option<i64>,
+    session_id: Option<String>,
 }
 
 #[derive(Debug, FromRow)]
@@ -109,16 +381,41 @@ pub struct OAuth2AuthorizationCode {
     pub scopes: Option<String>,
     pub user_id: String,
     pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<CodeChallengeMethod>,
+    /// The redirect URI provided in the authorization request.
+    /// The token request must provide the same redirect URI.
+    pub redirect_uri: String,
+    /// Whether the code has already been exchanged for tokens.
+    pub used: bool,
+    /// UNIX timestamp at which the user authenticated
+    pub auth_time: Option<i64>,
+    /// The login session the authorization was granted with
+    pub session_id: Option<String>,
 }
 
 #[derive(Clone, Debug, FromRow)]
 pub struct AccessToken {
     pub token: String,
+    /// Identifies the token when it is issued as a JWT.
+    /// Unlike the token itself, this is not a secret.
+    /// `None` for tokens issued before JWT access tokens were supported.
+    pub jti: Option<String>,
     pub client_id: String,
     pub expires_at: i64,
     pub issued_at: i64,
-    pub user_id: String,
+    /// The user the token was issued to.
+    /// `None` if the token was issued to the client itself, using the client credentials grant.
+    pub user_id: Option<String>,
     pub scopes: Option<String>,
+    /// The refresh token this access token was derived from, if any.
+    pub refresh_token: Option<String>,
+    /// UNIX timestamp at which the user authenticated.
+    /// `None` if the token was issued to the client itself.
+    pub auth_time: Option<i64>,
+    /// The login session the authorization was granted with.
+    /// `None` if the token was issued to the client itself, or before sessions were tracked.
+    pub session_id: Option<String>,
 }
 
 #[derive(Debug, FromRow)]
@@ -127,6 +424,21 @@ pub struct RefreshToken {
     pub client_id: String,
     pub user_id: String,
     pub scopes: Option<String>,
+    /// The first refresh token of the family this token belongs to.
+    /// All refresh tokens obtained by rotation descend from the same authorization.
+    pub family_id: String,
+    /// When this refresh token was issued.
+    pub issued_at: i64,
+    /// The absolute expiry of the family this token belongs to.
+    pub expires_at: i64,
+    /// Whether the refresh token has already been exchanged for a new token pair.
+    pub used: bool,
+    /// The authorization code the family was issued from, if any.
+    pub authorization_code: Option<String>,
+    /// UNIX timestamp at which the user authenticated
+    pub auth_time: Option<i64>,
+    /// The login session the authorization was granted with
+    pub session_id: Option<String>,
 }
 
 #[derive(Debug, Error)]
@@ -145,15 +457,78 @@ pub enum OAuth2PendingAuthorizationSetUserIdError {
     AlreadyAuthorized,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+/// The type of an authorization request, by its response type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
 pub enum AuthorizationType {
+    #[serde(rename = "code")]
     AuthorizationCode,
+    #[serde(rename = "token")]
     Implicit,
+    #[serde(rename = "id_token token")]
     IdToken,
 }
 
 impl_enum_type!(AuthorizationType);
 
+impl AuthorizationType {
+    /// All supported authorization types
+    pub const ALL: [Self; 3] = [Self::AuthorizationCode, Self::Implicit, Self::IdToken];
+
+    /// The response type of the authorization type, as used in client metadata
+    pub fn response_type(&self) -> &'static str {
+        match self {
+            Self::AuthorizationCode => "code",
+            Self::Implicit => "token",
+            Self::IdToken => "id_token token",
+        }
+    }
+
+    /// Get the authorization type by its response type
+    pub fn from_response_type(response_type: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.response_type() == response_type)
+    }
+}
+
+/// The method used to derive a PKCE code challenge from the code verifier.
+///
+/// # Further reading
+/// - [RFC 7636, Section 4.2](https://datatracker.ietf.org/doc/html/rfc7636#section-4.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Deserialize)]
+pub enum CodeChallengeMethod {
+    /// `code_challenge = code_verifier`
+    #[serde(rename(deserialize = "plain"))]
+    Plain,
+    /// `code_challenge = BASE64URL-ENCODE(SHA256(ASCII(code_verifier)))`
+    #[serde(rename(deserialize = "S256"))]
+    S256,
+}
+
+impl_enum_type!(CodeChallengeMethod);
+
+/// A PKCE code challenge provided by the client in the authorization request.
+///
+/// # Further reading
+/// - [RFC 7636](https://datatracker.ietf.org/doc/html/rfc7636)
+#[derive(Debug, Clone)]
+pub struct CodeChallenge {
+    pub challenge: String,
+    pub method: CodeChallengeMethod,
+}
+
+impl CodeChallenge {
+    fn from_columns(
+        challenge: &Option<String>,
+        method: Option<CodeChallengeMethod>,
+    ) -> Option<Self> {
+        challenge.as_ref().map(|challenge| Self {
+            challenge: challenge.clone(),
+            method: method.unwrap_or(CodeChallengeMethod::Plain),
+        })
+    }
+}
+
 impl OAuth2Client {
     fn generate_client_id() -> String {
         generate_string(32)
@@ -183,38 +558,389 @@ impl OAuth2Client {
         generate_string(32)
     }
 
+    fn generate_jti() -> String {
+        generate_string(32)
+    }
+
     fn generate_access_token_expiry() -> i64 {
-        (OffsetDateTime::now_utc() + Duration::hours(1)).unix_timestamp()
+        (OffsetDateTime::now_utc() + Duration::seconds(ACCESS_TOKEN_LIFETIME)).unix_timestamp()
+    }
+
+    fn generate_registration_access_token() -> String {
+        generate_string(48)
+    }
+
+    /// Compare the hash of a secret with an expected hash, in constant time
+    fn secret_matches(hash: &str, expected: &str) -> bool {
+        bool::from(hash.as_bytes().ct_eq(expected.as_bytes()))
     }
 
+    /// Create a new client.
+    /// Returns the client and its secret. The secret cannot be retrieved later.
+    ///
+    /// # Errors
+    ///
+    /// - If another client has the name, see [crate::driver::is_unique_violation]
+    /// - If the query fails
     #[instrument]
     pub async fn new(
         driver: &Database,
         name: String,
-        redirect_uri: String,
+        redirect_uris: &[String],
         internal: bool,
-    ) -> Result<Self> {
+        options: OAuth2ClientOptions,
+    ) -> Result<(Self, String)> {
         let client_id = Self::generate_client_id();
         let client_secret = Self::generate_client_secret();
+        let client_secret_hash = hash_token(&client_secret);
+
+        let mut tx = driver.begin().await?;
 
-        sqlx::query("INSERT INTO oauth2_clients (name, redirect_uri, client_id, client_secret, is_internal) VALUES (?, ?, ?, ?, ?)")
+        sqlx::query("INSERT INTO oauth2_clients (name, client_id, client_secret_hash, is_internal, is_public, allow_loopback_any_port, legacy_id_token_claims, id_token_signed_response_alg, access_token_format, backchannel_logout_uri, token_endpoint_auth_method, jwks, grant_types, response_types, logo_uri, contacts) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&name)
-            .bind(&redirect_uri)
             .bind(&client_id)
-            .bind(&client_secret)
+            .bind(&client_secret_hash)
             .bind(internal)
-            .execute(&**driver)
+            .bind(options.is_public)
+            .bind(options.allow_loopback_any_port)
+            .bind(options.legacy_id_token_claims)
+            .bind(options.id_token_signed_response_alg)
+            .bind(options.access_token_format)
+            .bind(&options.backchannel_logout_uri)
+            .bind(options.token_endpoint_auth_method)
+            .bind(&options.jwks)
+            .bind(Json(&options.grant_types))
+            .bind(Json(&options.response_types))
+            .bind(&options.logo_uri)
+            .bind(Json(&options.contacts))
+            .execute(&mut *tx)
             .await?;
 
-        Ok(Self {
+        Self::insert_redirect_uris(
+            &mut tx,
+            &client_id,
+            redirect_uris,
+            &options.post_logout_redirect_uris,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        let client = Self {
             name,
-            redirect_uri,
             client_id,
-            client_secret,
+            client_secret_hash,
+            previous_client_secret_hash: None,
+            previous_client_secret_expires_at: None,
             is_internal: internal,
+            is_public: options.is_public,
+            allow_loopback_any_port: options.allow_loopback_any_port,
+            legacy_id_token_claims: options.legacy_id_token_claims,
+            id_token_signed_response_alg: options.id_token_signed_response_alg,
+            access_token_format: options.access_token_format,
+            backchannel_logout_uri: options.backchannel_logout_uri,
+            token_endpoint_auth_method: options.token_endpoint_auth_method,
+            jwks: options.jwks,
+            grant_types: Json(options.grant_types),
+            response_types: Json(options.response_types),
+            logo_uri: options.logo_uri,
+            contacts: Json(options.contacts),
+            registration_access_token_hash: None,
+        };
+
+        Ok((client, client_secret))
+    }
+
+    async fn insert_redirect_uris(
+        tx: &mut Transaction<'_, MySql>,
+        client_id: &str,
+        redirect_uris: &[String],
+        post_logout_redirect_uris: &[String],
+    ) -> Result<()> {
+        for redirect_uri in redirect_uris {
+            sqlx::query(
+                "INSERT INTO oauth2_client_redirect_uris (client_id, redirect_uri) VALUES (?, ?)",
+            )
+            .bind(client_id)
+            .bind(redirect_uri)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        for redirect_uri in post_logout_redirect_uris {
+            sqlx::query(
+                "INSERT INTO oauth2_client_post_logout_redirect_uris (client_id, redirect_uri) VALUES (?, ?)",
+            )
+            .bind(client_id)
+            .bind(redirect_uri)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the client's name, redirect URIs and settings.
+    ///
+    /// # Errors
+    ///
+    /// - If another client has the name, see [crate::driver::is_unique_violation]
+    /// - If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn update(
+        &mut self,
+        driver: &Database,
+        name: String,
+        redirect_uris: &[String],
+        options: OAuth2ClientOptions,
+    ) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("UPDATE oauth2_clients SET name = ?, is_public = ?, allow_loopback_any_port = ?, legacy_id_token_claims = ?, id_token_signed_response_alg = ?, access_token_format = ?, backchannel_logout_uri = ?, token_endpoint_auth_method = ?, jwks = ?, grant_types = ?, response_types = ?, logo_uri = ?, contacts = ? WHERE client_id = ?")
+            .bind(&name)
+            .bind(options.is_public)
+            .bind(options.allow_loopback_any_port)
+            .bind(options.legacy_id_token_claims)
+            .bind(options.id_token_signed_response_alg)
+            .bind(options.access_token_format)
+            .bind(&options.backchannel_logout_uri)
+            .bind(options.token_endpoint_auth_method)
+            .bind(&options.jwks)
+            .bind(Json(&options.grant_types))
+            .bind(Json(&options.response_types))
+            .bind(&options.logo_uri)
+            .bind(Json(&options.contacts))
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM oauth2_client_redirect_uris WHERE client_id = ?")
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM oauth2_client_post_logout_redirect_uris WHERE client_id = ?")
+            .bind(&self.client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        Self::insert_redirect_uris(
+            &mut tx,
+            &self.client_id,
+            redirect_uris,
+            &options.post_logout_redirect_uris,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        self.name = name;
+        self.is_public = options.is_public;
+        self.allow_loopback_any_port = options.allow_loopback_any_port;
+        self.legacy_id_token_claims = options.legacy_id_token_claims;
+        self.id_token_signed_response_alg = options.id_token_signed_response_alg;
+        self.access_token_format = options.access_token_format;
+        self.backchannel_logout_uri = options.backchannel_logout_uri;
+        self.token_endpoint_auth_method = options.token_endpoint_auth_method;
+        self.jwks = options.jwks;
+        self.grant_types = Json(options.grant_types);
+        self.response_types = Json(options.response_types);
+        self.logo_uri = options.logo_uri;
+        self.contacts = Json(options.contacts);
+
+        Ok(())
+    }
+
+    /// The settings of the client, e.g. to modify them before an [OAuth2Client::update].
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    pub async fn options(&self, driver: &Database) -> Result<OAuth2ClientOptions> {
+        Ok(OAuth2ClientOptions {
+            is_public: self.is_public,
+            allow_loopback_any_port: self.allow_loopback_any_port,
+            legacy_id_token_claims: self.legacy_id_token_claims,
+            id_token_signed_response_alg: self.id_token_signed_response_alg,
+            access_token_format: self.access_token_format,
+            post_logout_redirect_uris: self.list_post_logout_redirect_uris(driver).await?,
+            backchannel_logout_uri: self.backchannel_logout_uri.clone(),
+            token_endpoint_auth_method: self.token_endpoint_auth_method,
+            jwks: self.jwks.clone(),
+            grant_types: self.grant_types.to_vec(),
+            response_types: self.response_types.to_vec(),
+            logo_uri: self.logo_uri.clone(),
+            contacts: self.contacts.to_vec(),
         })
     }
 
+    /// Whether the client may use the grant type
+    pub fn allows_grant_type(&self, grant_type: GrantType) -> bool {
+        self.grant_types.contains(&grant_type)
+    }
+
+    /// Whether the client may use the response type
+    pub fn allows_response_type(&self, ty: AuthorizationType) -> bool {
+        self.response_types.contains(&ty)
+    }
+
+    /// Issue a new registration access token, with which the client can manage its registration.
+    /// Any previous registration access token is invalidated.
+    /// Returns the token. It cannot be retrieved later.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn new_registration_access_token(&mut self, driver: &Database) -> Result<String> {
+        let token = Self::generate_registration_access_token();
+        let hash = hash_token(&token);
+
+        sqlx::query(
+            "UPDATE oauth2_clients SET registration_access_token_hash = ? WHERE client_id = ?",
+        )
+        .bind(&hash)
+        .bind(&self.client_id)
+        .execute(&**driver)
+        .await?;
+
+        self.registration_access_token_hash = Some(hash);
+
+        Ok(token)
+    }
+
+    /// Check whether the token is the client's registration access token.
+    /// The hashes are compared in constant time.
+    pub fn verify_registration_access_token(&self, token: &str) -> bool {
+        let hash = hash_token(token);
+        self.registration_access_token_hash
+            .as_deref()
+            .is_some_and(|expected| Self::secret_matches(&hash, expected))
+    }
+
+    /// Check whether the secret is the client's secret,
+    /// or its previous secret while the grace period after rotation lasts.
+    /// The hashes are compared in constant time.
+    pub fn verify_secret(&self, client_secret: &str) -> bool {
+        self.verify_secret_at(client_secret, OffsetDateTime::now_utc().unix_timestamp())
+    }
+
+    /// Check whether the secret is valid at the UNIX timestamp `now`, see [Self::verify_secret]
+    fn verify_secret_at(&self, client_secret: &str, now: i64) -> bool {
+        let hash = hash_token(client_secret);
+        let matches = |expected: &str| Self::secret_matches(&hash, expected);
+
+        let previous_valid = self
+            .previous_client_secret_expires_at
+            .is_some_and(|expires_at| expires_at > now);
+
+        // Both are compared regardless of the outcome of the first comparison
+        let current = matches(&self.client_secret_hash);
+        let previous = self
+            .previous_client_secret_hash
+            .as_deref()
+            .is_some_and(matches);
+
+        current | (previous_valid & previous)
+    }
+
+    /// Replace the client's secret with a newly generated secret.
+    /// If a `grace_period` in seconds is provided, the current secret remains valid for that long.
+    /// Otherwise it is invalidated immediately, as is any earlier secret still in its grace period.
+    /// Returns the new secret. It cannot be retrieved later.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn rotate_secret(
+        &mut self,
+        driver: &Database,
+        grace_period: Option<i64>,
+    ) -> Result<String> {
+        // Only updated once the new secret is stored
+        let mut rotated = self.clone();
+        let client_secret =
+            rotated.rotate_secret_at(grace_period, OffsetDateTime::now_utc().unix_timestamp());
+
+        sqlx::query("UPDATE oauth2_clients SET client_secret_hash = ?, previous_client_secret_hash = ?, previous_client_secret_expires_at = ? WHERE client_id = ?")
+            .bind(&rotated.client_secret_hash)
+            .bind(&rotated.previous_client_secret_hash)
+            .bind(rotated.previous_client_secret_expires_at)
+            .bind(&self.client_id)
+            .execute(&**driver)
+            .await?;
+
+        *self = rotated;
+
+        Ok(client_secret)
+    }
+
+    /// Replace the secret at the UNIX timestamp `now`, without storing it, see [Self::rotate_secret].
+    /// Returns the new secret.
+    fn rotate_secret_at(&mut self, grace_period: Option<i64>, now: i64) -> String {
+        let client_secret = Self::generate_client_secret();
+        let previous_hash =
+            std::mem::replace(&mut self.client_secret_hash, hash_token(&client_secret));
+
+        // A secret still in its grace period from an earlier rotation is replaced either way
+        match grace_period {
+            Some(grace_period) => {
+                self.previous_client_secret_hash = Some(previous_hash);
+                self.previous_client_secret_expires_at = Some(now + grace_period);
+            }
+            None => {
+                self.previous_client_secret_hash = None;
+                self.previous_client_secret_expires_at = None;
+            }
+        }
+
+        client_secret
+    }
+
+    /// Record the use of a client assertion with the `jti`, until it expires.
+    /// Returns `false` if the assertion was used before, in which case it must be rejected.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn record_assertion(
+        &self,
+        driver: &Database,
+        jti: &str,
+        expires_at: i64,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT IGNORE INTO oauth2_client_assertions (client_id, jti, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(&self.client_id)
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&**driver)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Delete the records of client assertions that have expired.
+    /// Expired assertions are rejected regardless, so these records are no longer needed.
+    /// Returns the number of deleted records.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn delete_expired_assertions(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM oauth2_client_assertions WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+
     #[instrument]
     pub async fn list(driver: &Database) -> Result<Vec<Self>> {
         sqlx::query_as("SELECT * FROM oauth2_clients")
@@ -222,12 +948,162 @@ impl OAuth2Client {
             .await
     }
 
+    /// Delete the client, with everything issued to it.
+    /// Its tokens are revoked, and pending authorizations and authorization codes can no longer be used.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
     #[instrument]
     pub async fn delete(self, driver: &Database) -> Result<()> {
-        sqlx::query("DELETE FROM oauth2_clients WHERE client_id = ?")
-            .bind(self.client_id)
+        let mut tx = driver.begin().await?;
+
+        for query in [
+            "DELETE FROM oauth2_pending_authorizations WHERE client_id = ?",
+            "DELETE FROM oauth2_authorization_codes WHERE client_id = ?",
+            "DELETE FROM oauth2_access_tokens WHERE client_id = ?",
+            "DELETE FROM oauth2_refresh_tokens WHERE client_id = ?",
+            "DELETE FROM oauth2_client_permitted_scopes WHERE client_id = ?",
+            "DELETE FROM oauth2_client_redirect_uris WHERE client_id = ?",
+            "DELETE FROM oauth2_client_post_logout_redirect_uris WHERE client_id = ?",
+            "DELETE FROM oauth2_client_assertions WHERE client_id = ?",
+            "DELETE FROM user_consents WHERE client_id = ?",
+            "DELETE FROM oauth2_clients WHERE client_id = ?",
+        ] {
+            sqlx::query(query)
+                .bind(&self.client_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// List the redirect URIs registered for the client.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_redirect_uris(&self, driver: &Database) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT redirect_uri FROM oauth2_client_redirect_uris WHERE client_id = ?",
+        )
+        .bind(&self.client_id)
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// List the URIs the user may be redirected to after logging out.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_post_logout_redirect_uris(&self, driver: &Database) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT redirect_uri FROM oauth2_client_post_logout_redirect_uris WHERE client_id = ?",
+        )
+        .bind(&self.client_id)
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// List the clients with a back-channel logout URI that hold valid tokens for the user,
+    /// i.e. the clients the user is logged in to.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_for_backchannel_logout(
+        driver: &Database,
+        user_id: &str,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as(
+            "SELECT * FROM oauth2_clients WHERE backchannel_logout_uri IS NOT NULL AND (\
+                client_id IN (SELECT client_id FROM oauth2_access_tokens WHERE user_id = ? AND expires_at > ?) \
+                OR client_id IN (SELECT client_id FROM oauth2_refresh_tokens WHERE user_id = ? AND used = FALSE)\
+            )",
+        )
+        .bind(user_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .bind(user_id)
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// List the clients with a back-channel logout URI that hold valid tokens
+    /// issued for authorizations granted with the login session.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_for_session_backchannel_logout(
+        driver: &Database,
+        session_id: &str,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as(
+            "SELECT * FROM oauth2_clients WHERE backchannel_logout_uri IS NOT NULL AND (\
+                client_id IN (SELECT client_id FROM oauth2_access_tokens WHERE session_id = ? AND expires_at > ?) \
+                OR client_id IN (SELECT client_id FROM oauth2_refresh_tokens WHERE session_id = ? AND used = FALSE)\
+            )",
+        )
+        .bind(session_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .bind(session_id)
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// List the scopes the client is permitted to request with the client credentials grant.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn list_permitted_scopes(&self, driver: &Database) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT scope FROM oauth2_client_permitted_scopes WHERE client_id = ?")
+            .bind(&self.client_id)
+            .fetch_all(&**driver)
+            .await
+    }
+
+    /// Grant a scope to the client. It can now request tokens with the scope
+    /// using the client credentials grant.
+    ///
+    /// # Errors
+    ///
+    /// - If the query fails
+    /// - If the scope was already granted
+    #[instrument]
+    pub async fn grant_permitted_scope(&self, driver: &Database, scope: &str) -> Result<()> {
+        sqlx::query("INSERT INTO oauth2_client_permitted_scopes (client_id, scope) VALUES (?, ?)")
+            .bind(&self.client_id)
+            .bind(scope)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove a scope that the client was permitted to request.
+    /// Does not fail if the client did not have the scope specified.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn remove_permitted_scope(&self, driver: &Database, scope: &str) -> Result<()> {
+        sqlx::query("DELETE FROM oauth2_client_permitted_scopes WHERE client_id = ? AND scope = ?")
+            .bind(&self.client_id)
+            .bind(scope)
             .execute(&**driver)
             .await?;
+
         Ok(())
     }
 
@@ -243,19 +1119,36 @@ impl OAuth2Client {
     pub async fn new_pending_authorization(
         &self,
         driver: &Database,
-        scopes: Option<String>,
-        state: Option<String>,
-        ty: AuthorizationType,
-        nonce: Option<String>,
+        request: AuthorizationRequest,
     ) -> Result<OAuth2PendingAuthorization> {
+        let AuthorizationRequest {
+            scopes,
+            state,
+            ty,
+            nonce,
+            code_challenge,
+            redirect_uri,
+            prompt,
+        } = request;
+
         let id = Self::generate_pending_authorization_id();
-        sqlx::query("INSERT INTO oauth2_pending_authorizations (id, client_id, scopes, state, ty, nonce) VALUES (?, ?, ?, ?, ?, ?)")
+        let (code_challenge, code_challenge_method) = match code_challenge {
+            Some(c) => (Some(c.challenge), Some(c.method)),
+            None => (None, None),
+        };
+
+        sqlx::query("INSERT INTO oauth2_pending_authorizations (id, client_id, scopes, state, ty, nonce, code_challenge, code_challenge_method, redirect_uri, prompt, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&id)
             .bind(&self.client_id)
             .bind(&scopes)
             .bind(&state)
-            .bind(&ty)
+            .bind(ty)
             .bind(&nonce)
+            .bind(&code_challenge)
+            .bind(code_challenge_method)
+            .bind(&redirect_uri)
+            .bind(&prompt)
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
             .execute(&**driver)
             .await?;
 
@@ -267,6 +1160,10 @@ impl OAuth2Client {
                 state,
                 ty,
                 nonce,
+                code_challenge,
+                code_challenge_method,
+                redirect_uri,
+                prompt,
             },
         ))
     }
@@ -289,13 +1186,18 @@ impl OAuth2Client {
 
         let mut tx = driver.begin().await?;
 
-        sqlx::query("INSERT INTO oauth2_authorization_codes (client_id, code, expires_at, scopes, user_id, nonce) VALUES (?, ?, ?, ?, ?, ?)")
+        sqlx::query("INSERT INTO oauth2_authorization_codes (client_id, code, expires_at, scopes, user_id, nonce, code_challenge, code_challenge_method, redirect_uri, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&self.client_id)
             .bind(&code)
             .bind(expires_at)
             .bind(&pending.scopes)
             .bind(&pending.user_id)
             .bind(&pending.nonce)
+            .bind(&pending.code_challenge)
+            .bind(pending.code_challenge_method)
+            .bind(&pending.redirect_uri)
+            .bind(pending.auth_time)
+            .bind(&pending.session_id)
             .execute(&mut *tx)
             .await?;
 
@@ -313,6 +1215,12 @@ impl OAuth2Client {
             expires_at,
             user_id: pending.user_id,
             nonce: pending.nonce,
+            code_challenge: pending.code_challenge,
+            code_challenge_method: pending.code_challenge_method,
+            redirect_uri: pending.redirect_uri,
+            used: false,
+            auth_time: pending.auth_time,
+            session_id: pending.session_id,
         })
     }
 
@@ -330,18 +1238,22 @@ impl OAuth2Client {
         };
 
         let atoken = Self::generate_access_token();
+        let jti = Self::generate_jti();
         let expires_at = Self::generate_access_token_expiry();
         let issued_at = OffsetDateTime::now_utc().unix_timestamp();
 
         let mut tx = driver.begin().await?;
 
-        sqlx::query("INSERT INTO oauth2_access_tokens (token, client_id, expires_at, issued_at, user_id, scopes) VALUES (?, ?, ?, ?, ?, ?)")
+        sqlx::query("INSERT INTO oauth2_access_tokens (token, jti, client_id, expires_at, issued_at, user_id, scopes, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&atoken)
+            .bind(&jti)
             .bind(&self.client_id)
             .bind(expires_at)
             .bind(issued_at)
             .bind(&authorization.user_id)
             .bind(&authorization.scopes)
+            .bind(authorization.auth_time)
+            .bind(&authorization.session_id)
             .execute(&mut *tx)
             .await?;
 
@@ -354,100 +1266,253 @@ impl OAuth2Client {
 
         Ok(AccessToken {
             token: atoken,
+            jti: Some(jti),
             issued_at,
             expires_at,
-            user_id: authorization.user_id,
+            user_id: Some(authorization.user_id),
             scopes: authorization.scopes,
             client_id: self.client_id.clone(),
+            refresh_token: None,
+            auth_time: authorization.auth_time,
+            session_id: authorization.session_id,
+        })
+    }
+
+    /// Create a new access token for the client itself, rather than for a user.
+    /// Used for the client credentials grant. No refresh token is issued.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn new_client_access_token(
+        &self,
+        driver: &Database,
+        scopes: Option<String>,
+    ) -> Result<AccessToken> {
+        let atoken = Self::generate_access_token();
+        let jti = Self::generate_jti();
+        let expires_at = Self::generate_access_token_expiry();
+        let issued_at = OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query("INSERT INTO oauth2_access_tokens (token, jti, client_id, expires_at, issued_at, user_id, scopes) VALUES (?, ?, ?, ?, ?, NULL, ?)")
+            .bind(&atoken)
+            .bind(&jti)
+            .bind(&self.client_id)
+            .bind(expires_at)
+            .bind(issued_at)
+            .bind(&scopes)
+            .execute(&**driver)
+            .await?;
+
+        Ok(AccessToken {
+            token: atoken,
+            jti: Some(jti),
+            client_id: self.client_id.clone(),
+            expires_at,
+            issued_at,
+            user_id: None,
+            scopes,
+            refresh_token: None,
+            auth_time: None,
+            session_id: None,
         })
     }
 
+    /// Exchange an authorization code for a new access token and refresh token.
+    /// The refresh token starts a new family, which expires after `refresh_token_lifetime` seconds.
+    /// The authorization code is marked as used.
+    ///
+    /// Returns `None` if the authorization code has already been used.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
     #[instrument]
     pub async fn new_token_pair(
         &self,
         driver: &Database,
-        authorization: OAuth2AuthorizationCode,
-    ) -> Result<(AccessToken, RefreshToken)> {
+        authorization: &OAuth2AuthorizationCode,
+        refresh_token_lifetime: i64,
+    ) -> Result<Option<(AccessToken, RefreshToken)>> {
         let atoken = Self::generate_access_token();
+        let jti = Self::generate_jti();
         let rtoken = Self::generate_refresh_token();
         let expires_at = Self::generate_access_token_expiry();
         let issued_at = OffsetDateTime::now_utc().unix_timestamp();
+        let refresh_expires_at = issued_at + refresh_token_lifetime;
 
         let mut tx = driver.begin().await?;
 
+        // Marking the code as used only succeeds once,
+        // this guards against the same code being exchanged concurrently.
+        let marked = sqlx::query(
+            "UPDATE oauth2_authorization_codes SET used = TRUE WHERE code = ? AND used = FALSE",
+        )
+        .bind(&authorization.code)
+        .execute(&mut *tx)
+        .await?;
+
+        if marked.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
         // Access token
-        sqlx::query("INSERT INTO oauth2_access_tokens (token, client_id, expires_at, issued_at, user_id, scopes) VALUES (?, ?, ?, ?, ?, ?)")
+        sqlx::query("INSERT INTO oauth2_access_tokens (token, jti, client_id, expires_at, issued_at, user_id, scopes, refresh_token, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&atoken)
+            .bind(&jti)
             .bind(&self.client_id)
             .bind(expires_at)
             .bind(issued_at)
             .bind(&authorization.user_id)
             .bind(&authorization.scopes)
+            .bind(&rtoken)
+            .bind(authorization.auth_time)
+            .bind(&authorization.session_id)
             .execute(&mut *tx)
             .await?;
 
-        // Refresh token
-        sqlx::query("INSERT INTO oauth2_refresh_tokens (token, client_id, user_id, scopes) VALUES (?, ?, ?, ?)")
+        // Refresh token, the first of its family
+        sqlx::query("INSERT INTO oauth2_refresh_tokens (token, client_id, user_id, scopes, family_id, issued_at, expires_at, authorization_code, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&rtoken)
             .bind(&self.client_id)
             .bind(&authorization.user_id)
             .bind(&authorization.scopes)
-            .execute(&mut *tx)
-            .await?;
-
-        // Remove authorization
-        sqlx::query("DELETE FROM oauth2_authorization_codes WHERE code = ?")
+            .bind(&rtoken)
+            .bind(issued_at)
+            .bind(refresh_expires_at)
             .bind(&authorization.code)
+            .bind(authorization.auth_time)
+            .bind(&authorization.session_id)
             .execute(&mut *tx)
             .await?;
 
         tx.commit().await?;
 
-        Ok((
+        Ok(Some((
             AccessToken {
                 token: atoken,
+                jti: Some(jti),
                 client_id: self.client_id.clone(),
                 expires_at,
                 issued_at,
-                user_id: authorization.user_id.clone(),
+                user_id: Some(authorization.user_id.clone()),
                 scopes: authorization.scopes.clone(),
+                refresh_token: Some(rtoken.clone()),
+                auth_time: authorization.auth_time,
+                session_id: authorization.session_id.clone(),
             },
             RefreshToken {
+                family_id: rtoken.clone(),
                 token: rtoken,
                 client_id: self.client_id.clone(),
                 user_id: authorization.user_id.clone(),
                 scopes: authorization.scopes.clone(),
+                issued_at,
+                expires_at: refresh_expires_at,
+                used: false,
+                authorization_code: Some(authorization.code.clone()),
+                auth_time: authorization.auth_time,
+                session_id: authorization.session_id.clone(),
             },
-        ))
+        )))
     }
 
+    /// Exchange a refresh token for a new access token and refresh token.
+    /// The provided refresh token is marked as used, the new refresh token
+    /// belongs to the same family and inherits its absolute expiry.
+    ///
+    /// Returns `None` if the refresh token has already been used.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
     #[instrument]
-    pub async fn refresh_access_token(
+    pub async fn rotate_refresh_token(
         &self,
         driver: &Database,
         refresh_token: &RefreshToken,
-    ) -> Result<AccessToken> {
+    ) -> Result<Option<(AccessToken, RefreshToken)>> {
         let atoken = Self::generate_access_token();
+        let jti = Self::generate_jti();
+        let rtoken = Self::generate_refresh_token();
         let expires_at = Self::generate_access_token_expiry();
         let issued_at = OffsetDateTime::now_utc().unix_timestamp();
 
-        sqlx::query("INSERT INTO oauth2_access_tokens (token, client_id, expires_at, issued_at, user_id, scopes) VALUES (?, ?, ?, ?, ?, ?)")
+        let mut tx = driver.begin().await?;
+
+        // Marking the token as used only succeeds once,
+        // this guards against the same token being exchanged concurrently.
+        let marked = sqlx::query(
+            "UPDATE oauth2_refresh_tokens SET used = TRUE WHERE token = ? AND used = FALSE",
+        )
+        .bind(&refresh_token.token)
+        .execute(&mut *tx)
+        .await?;
+
+        if marked.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        sqlx::query("INSERT INTO oauth2_refresh_tokens (token, client_id, user_id, scopes, family_id, issued_at, expires_at, authorization_code, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(&rtoken)
+            .bind(&self.client_id)
+            .bind(&refresh_token.user_id)
+            .bind(&refresh_token.scopes)
+            .bind(&refresh_token.family_id)
+            .bind(issued_at)
+            .bind(refresh_token.expires_at)
+            .bind(&refresh_token.authorization_code)
+            .bind(refresh_token.auth_time)
+            .bind(&refresh_token.session_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO oauth2_access_tokens (token, jti, client_id, expires_at, issued_at, user_id, scopes, refresh_token, auth_time, session_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .bind(&atoken)
+            .bind(&jti)
             .bind(&self.client_id)
             .bind(expires_at)
+            .bind(issued_at)
             .bind(&refresh_token.user_id)
             .bind(&refresh_token.scopes)
-            .execute(&**driver)
+            .bind(&rtoken)
+            .bind(refresh_token.auth_time)
+            .bind(&refresh_token.session_id)
+            .execute(&mut *tx)
             .await?;
 
-        Ok(AccessToken {
-            token: atoken,
-            client_id: self.client_id.clone(),
-            scopes: refresh_token.scopes.clone(),
-            issued_at,
-            expires_at,
-            user_id: refresh_token.user_id.clone(),
-        })
+        tx.commit().await?;
+
+        Ok(Some((
+            AccessToken {
+                token: atoken,
+                jti: Some(jti),
+                client_id: self.client_id.clone(),
+                scopes: refresh_token.scopes.clone(),
+                issued_at,
+                expires_at,
+                user_id: Some(refresh_token.user_id.clone()),
+                refresh_token: Some(rtoken.clone()),
+                auth_time: refresh_token.auth_time,
+                session_id: refresh_token.session_id.clone(),
+            },
+            RefreshToken {
+                token: rtoken,
+                client_id: self.client_id.clone(),
+                user_id: refresh_token.user_id.clone(),
+                scopes: refresh_token.scopes.clone(),
+                family_id: refresh_token.family_id.clone(),
+                issued_at,
+                expires_at: refresh_token.expires_at,
+                used: false,
+                authorization_code: refresh_token.authorization_code.clone(),
+                auth_time: refresh_token.auth_time,
+                session_id: refresh_token.session_id.clone(),
+            },
+        )))
     }
 }
 
@@ -460,6 +1525,20 @@ impl AccessToken {
             .await
     }
 
+    /// Get an access token by its JWT ID.
+    /// The JWT the ID was taken from must be verified first.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn get_by_jti(driver: &Database, jti: &str) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM oauth2_access_tokens WHERE jti = ?")
+            .bind(jti)
+            .fetch_optional(&**driver)
+            .await
+    }
+
     #[instrument]
     pub async fn get_with_validation(
         driver: &Database,
@@ -488,6 +1567,39 @@ impl AccessToken {
             .map(|f| f.split(" ").map(|c| c.to_string()).collect::<HashSet<_>>())
             .unwrap_or_default()
     }
+
+    /// Delete all expired access tokens.
+    /// Returns the number of deleted access tokens.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn delete_expired(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM oauth2_access_tokens WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    /// Revoke the access token.
+    /// The refresh token it was derived from, if any, is not affected.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn revoke(self, driver: &Database) -> Result<()> {
+        sqlx::query("DELETE FROM oauth2_access_tokens WHERE token = ?")
+            .bind(&self.token)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
 }
 
 impl RefreshToken {
@@ -498,28 +1610,115 @@ impl RefreshToken {
             .fetch_optional(&**driver)
             .await
     }
+
+    /// Whether the refresh token may no longer be used.
+    /// A token expires when its family reaches its absolute expiry,
+    /// or when it has not been used for `idle_lifetime` seconds.
+    pub fn is_expired(&self, idle_lifetime: i64) -> bool {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        now >= self.expires_at || now >= self.issued_at + idle_lifetime
+    }
+
+    /// Delete all expired refresh tokens.
+    /// Used refresh tokens are kept until their family expires, so that their reuse can still be detected.
+    /// Returns the number of deleted refresh tokens.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn delete_expired(driver: &Database, idle_lifetime: i64) -> Result<u64> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        Ok(sqlx::query(
+            "DELETE FROM oauth2_refresh_tokens WHERE expires_at <= ? OR (used = FALSE AND issued_at <= ?)",
+        )
+        .bind(now)
+        .bind(now - idle_lifetime)
+        .execute(&**driver)
+        .await?
+        .rows_affected())
+    }
+
+    /// Revoke the entire family of the refresh token,
+    /// and all access tokens derived from any refresh token in the family.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn revoke(self, driver: &Database) -> Result<()> {
+        Self::revoke_family(driver, &self.family_id).await
+    }
+
+    #[instrument]
+    async fn revoke_family(driver: &Database, family_id: &str) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM oauth2_access_tokens WHERE refresh_token IN (SELECT token FROM oauth2_refresh_tokens WHERE family_id = ?)")
+            .bind(family_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM oauth2_refresh_tokens WHERE family_id = ?")
+            .bind(family_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
 }
 
 impl OAuth2PendingAuthorization {
+    /// Get a pending authorization by its ID.
+    /// Pending authorizations older than `max_age` seconds are considered expired, and are not returned.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
     #[instrument]
     pub async fn get_by_id(
         driver: &Database,
         id: &str,
+        max_age: i64,
     ) -> Result<Option<OAuth2PendingAuthorization>> {
+        Ok(sqlx::query_as(
+            "SELECT * FROM oauth2_pending_authorizations WHERE id = ? AND created_at > ?",
+        )
+        .bind(id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp() - max_age)
+        .fetch_optional(&**driver)
+        .await?
+        .map(|v: _OAuth2PendingAuthorization| OAuth2PendingAuthorization::from(v)))
+    }
+
+    /// Delete all pending authorizations older than `max_age` seconds.
+    /// Returns the number of deleted pending authorizations.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn delete_expired(driver: &Database, max_age: i64) -> Result<u64> {
         Ok(
-            sqlx::query_as("SELECT * FROM oauth2_pending_authorizations WHERE id = ?")
-                .bind(id)
-                .fetch_optional(&**driver)
+            sqlx::query("DELETE FROM oauth2_pending_authorizations WHERE created_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp() - max_age)
+                .execute(&**driver)
                 .await?
-                .map(|v: _OAuth2PendingAuthorization| OAuth2PendingAuthorization::from(v)),
+                .rows_affected(),
         )
     }
 
-    #[instrument]
-    pub async fn set_user_id(
+    /// Mark the authorization as authorized by the user of the login session.
+    /// The session's `auth_time` may be earlier than now if an existing login is reused.
+    /// Tokens issued for the authorization are bound to the session.
+    #[instrument(skip(session))]
+    pub async fn set_session(
         self,
         driver: &Database,
-        user_id: &str,
+        session: &UserSession,
     ) -> std::result::Result<Self, OAuth2PendingAuthorizationSetUserIdError> {
         let id = match &self {
             Self::Unauthorized(v) => &v.id,
@@ -528,21 +1727,31 @@ impl OAuth2PendingAuthorization {
             }
         };
 
-        sqlx::query("UPDATE oauth2_pending_authorizations SET user_id = ? WHERE id = ?")
-            .bind(user_id)
-            .bind(id)
-            .execute(&**driver)
-            .await?;
+        sqlx::query(
+            "UPDATE oauth2_pending_authorizations SET user_id = ?, auth_time = ?, session_id = ? WHERE id = ?",
+        )
+        .bind(&session.user_id)
+        .bind(session.auth_time)
+        .bind(&session.session_id)
+        .bind(id)
+        .execute(&**driver)
+        .await?;
 
         let new_self = match self {
             Self::Unauthorized(v) => Self::Authorized(OAuth2PendingAuthorizationAuthorized {
                 id: v.id,
                 client_id: v.client_id,
-                user_id: user_id.to_string(),
+                user_id: session.user_id.clone(),
                 state: v.state,
                 scopes: v.scopes,
                 ty: v.ty,
                 nonce: v.nonce,
+                code_challenge: v.code_challenge,
+                code_challenge_method: v.code_challenge_method,
+                redirect_uri: v.redirect_uri,
+                prompt: v.prompt,
+                auth_time: Some(session.auth_time),
+                session_id: Some(session.session_id.clone()),
             }),
             Self::Authorized(_) => unreachable!(),
         };
@@ -552,6 +1761,11 @@ impl OAuth2PendingAuthorization {
 }
 
 impl OAuth2AuthorizationCode {
+    /// The PKCE code challenge provided in the authorization request, if any.
+    pub fn code_challenge(&self) -> Option<CodeChallenge> {
+        CodeChallenge::from_columns(&self.code_challenge, self.code_challenge_method)
+    }
+
     #[instrument]
     pub async fn get_by_code(driver: &Database, code: &str) -> Result<Option<Self>> {
         sqlx::query_as("SELECT * FROM oauth2_authorization_codes WHERE code = ?")
@@ -559,6 +1773,46 @@ impl OAuth2AuthorizationCode {
             .fetch_optional(&**driver)
             .await
     }
+
+    /// Revoke all tokens issued from this authorization code.
+    /// This revokes the refresh token families descending from the code,
+    /// including all access tokens derived from them.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn revoke_issued_tokens(&self, driver: &Database) -> Result<()> {
+        let families: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT family_id FROM oauth2_refresh_tokens WHERE authorization_code = ?",
+        )
+        .bind(&self.code)
+        .fetch_all(&**driver)
+        .await?;
+
+        for family_id in families {
+            RefreshToken::revoke_family(driver, &family_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete all expired authorization codes.
+    /// Returns the number of deleted authorization codes.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument]
+    pub async fn delete_expired(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM oauth2_authorization_codes WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
 }
 
 impl From<_OAuth2PendingAuthorization> for OAuth2PendingAuthorization {
@@ -572,6 +1826,12 @@ impl From<_OAuth2PendingAuthorization> for OAuth2PendingAuthorization {
                 user_id,
                 ty: value.ty,
                 nonce: value.nonce,
+                code_challenge: value.code_challenge,
+                code_challenge_method: value.code_challenge_method,
+                redirect_uri: value.redirect_uri,
+                prompt: value.prompt,
+                auth_time: value.auth_time,
+                session_id: value.session_id,
             })
         } else {
             Self::Unauthorized(OAuth2PendingAuthorizationUnauthorized {
@@ -581,6 +1841,10 @@ impl From<_OAuth2PendingAuthorization> for OAuth2PendingAuthorization {
                 state: value.state,
                 ty: value.ty,
                 nonce: value.nonce,
+                code_challenge: value.code_challenge,
+                code_challenge_method: value.code_challenge_method,
+                redirect_uri: value.redirect_uri,
+                prompt: value.prompt,
             })
         }
     }
@@ -590,10 +1854,6 @@ impl From<_OAuth2PendingAuthorization> for OAuth2PendingAuthorization {
 pub struct IdTokenClaims {
     /// Issuer Identifier for the Issuer of the response. The iss value is a case-sensitive URL using the https scheme that contains scheme, host, and optionally, port number and path components and no query or fragment components.
     iss: String,
-    /// Subject Identifier.
-    /// A locally unique and never reassigned identifier within the Issuer for the End-User, which is intended to be consumed by the Client, e.g., 24400320 or AItOawmwtWwcT0k51BayewNvutrJUqsvl6qs7A4.
-    /// It MUST NOT exceed 255 ASCII [RFC20] characters in length. The sub value is a case-sensitive string.
-    sub: String,
     /// Audience(s) that this ID Token is intended for. It MUST contain the OAuth 2.0 client_id of the Relying Party as an audience value. It MAY also contain identifiers for other audiences.
     /// In the general case, the aud value is an array of case-sensitive strings. In the common special case when there is one audience, the aud value MAY be a single case-sensitive string.
     aud: String,
@@ -603,18 +1863,94 @@ pub struct IdTokenClaims {
     iat: i64,
     /// String value used to associate a Client session with an ID Token, and to mitigate replay attacks. The value is passed through unmodified from the Authentication Request to the ID Token
     nonce: Option<String>,
+    /// Time when the End-User authentication occurred. Required when `max_age` was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_time: Option<i64>,
     ///  Authorized party - the party to which the ID Token was issued. If present, it MUST contain the OAuth 2.0 Client ID of this party.
     azp: String,
+    /// Access Token hash value. Its value is the base64url encoding of the left-most half of the hash of the octets of the ASCII representation of the access_token value.
+    at_hash: String,
+    /// Claims about the End-User, including the subject identifier
+    #[serde(flatten)]
+    user: UserClaims,
+}
 
-    // We also have some custom claims, this is allowed by the JWT spec
-    sub_email: String,
-    sub_name: String,
-    sub_is_admin: bool,
+/// Claims about a user, as included in ID tokens and returned by the UserInfo endpoint.
+/// Which claims are present depends on the scopes granted.
+///
+/// # Further reading
+/// - [OpenID Connect Core 1.0, Section 5.1](https://openid.net/specs/openid-connect-core-1_0.html#StandardClaims)
+/// - [OpenID Connect Core 1.0, Section 5.4](https://openid.net/specs/openid-connect-core-1_0.html#ScopeClaims)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserClaims {
+    /// Subject Identifier.
+    /// A locally unique and never reassigned identifier within the Issuer for the End-User, which is intended to be consumed by the Client, e.g., 24400320 or AItOawmwtWwcT0k51BayewNvutrJUqsvl6qs7A4.
+    /// It MUST NOT exceed 255 ASCII [RFC20] characters in length. The sub value is a case-sensitive string.
+    pub sub: String,
+    /// Present with the `profile` scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Present with the `profile` scope. The email address, as users log in with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    /// Present with the `profile` scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// Present with the `profile` scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+    /// Present with the `email` scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Present with the `email` scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+
+    // Non-standard claims, only present for clients with `legacy_id_token_claims` set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_is_admin: Option<bool>,
 }
 
-#[derive(Debug)]
-pub enum JwtSigningAlgorithm {
-    RS256,
+impl UserClaims {
+    /// Collect the claims about the user allowed by the granted scopes.
+    /// The non-standard claims are included if `legacy` is set.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn new(
+        driver: &Database,
+        user: &User,
+        scopes: &HashSet<String>,
+        legacy: bool,
+    ) -> Result<Self> {
+        let profile = scopes.contains("profile");
+        let email = scopes.contains("email");
+
+        let email_verified = if email {
+            Some(user.is_email_verified(driver).await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            sub: user.user_id.clone(),
+            name: profile.then(|| user.name.clone()),
+            preferred_username: profile.then(|| user.email.clone()),
+            locale: profile.then(|| user.locale.language_tag().to_string()),
+            updated_at: profile.then_some(user.updated_at),
+            email: email.then(|| user.email.clone()),
+            email_verified,
+            sub_email: legacy.then(|| user.email.clone()),
+            sub_name: legacy.then(|| user.name.clone()),
+            sub_is_admin: legacy.then_some(user.is_admin),
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -625,47 +1961,306 @@ pub enum IdTokenCreationError {
     Signing(String),
 }
 
-#[instrument]
+/// Create an ID token for the user.
+/// The claims about the user should be collected with [UserClaims::new],
+/// using the scopes of the access token.
+/// The token is signed with the algorithm of the signing key,
+/// which should match the client's `id_token_signed_response_alg`.
+/// The `kid` of the signing key is placed in the token header.
+///
+/// `issued_access_token` is the access token as given to the client,
+/// which is a JWT if the client uses JWT access tokens.
+#[instrument(skip(signing_key))]
 pub fn create_id_token(
     issuer: String,
     client: &OAuth2Client,
-    user: &User,
-    oidc_signing_key_pem: &str,
+    user: UserClaims,
+    signing_key: &SigningKey,
     access_token: &AccessToken,
+    issued_access_token: &str,
     nonce: Option<String>,
-    jwt_signing_algorithm: JwtSigningAlgorithm,
 ) -> std::result::Result<String, IdTokenCreationError> {
     let iat = OffsetDateTime::now_utc();
 
     let id_claims = IdTokenClaims {
-        // Standard claims
         iss: issuer,
-        sub: user.user_id.clone(),
         aud: client.client_id.clone(),
         exp: access_token.expires_at,
         iat: iat.unix_timestamp(),
         nonce,
+        auth_time: access_token.auth_time,
         azp: client.client_id.clone(),
-        // Custom claims
-        sub_name: user.name.clone(),
-        sub_is_admin: user.is_admin,
-        sub_email: user.email.clone(),
+        at_hash: access_token_hash(issued_access_token, signing_key.algorithm),
+        user,
+    };
+
+    let claims = Claims::with_custom_claims(
+        id_claims,
+        jwt_simple::reexports::coarsetime::Duration::from_secs(
+            (access_token.expires_at - iat.unix_timestamp()) as u64,
+        ),
+    );
+
+    let keypair_error = |e: jwt_simple::Error| IdTokenCreationError::Keypair(e.to_string());
+    let token = match signing_key.algorithm {
+        JwtSigningAlgorithm::RS256 => RS256KeyPair::from_pem(&signing_key.private_key)
+            .map_err(keypair_error)?
+            .with_key_id(&signing_key.kid)
+            .sign(claims),
+        JwtSigningAlgorithm::ES256 => ES256KeyPair::from_pem(&signing_key.private_key)
+            .map_err(keypair_error)?
+            .with_key_id(&signing_key.kid)
+            .sign(claims),
+        JwtSigningAlgorithm::EdDSA => Ed25519KeyPair::from_pem(&signing_key.private_key)
+            .map_err(keypair_error)?
+            .with_key_id(&signing_key.kid)
+            .sign(claims),
+    };
+
+    token.map_err(|e| IdTokenCreationError::Signing(e.to_string()))
+}
+
+/// Claims of a JWT access token.
+///
+/// # Further reading
+/// - [RFC 9068, Section 2.2](https://datatracker.ietf.org/doc/html/rfc9068#section-2.2)
+#[derive(Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    /// The issuer of the token
+    iss: String,
+    /// Expiration time of the token
+    exp: i64,
+    /// The resource server the token is intended for.
+    /// Resource indicators are not supported, so this is always the issuer.
+    aud: String,
+    /// The user the token was issued to,
+    /// or the client for tokens issued with the client credentials grant
+    sub: String,
+    /// The client the token was issued to
+    client_id: String,
+    /// Time at which the token was issued
+    iat: i64,
+    /// Identifies the token, so it can be looked up for revocation
+    jti: String,
+    /// The scopes the token is authorized for, space separated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    /// Time when the user authenticated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_time: Option<i64>,
+}
+
+/// The `typ` header of JWT access tokens.
+/// See [RFC 9068, Section 2.1](https://datatracker.ietf.org/doc/html/rfc9068#section-2.1)
+const ACCESS_TOKEN_JWT_TYPE: &str = "at+jwt";
+
+#[derive(Debug, Error)]
+pub enum AccessTokenCreationError {
+    #[error("Access token has no JWT ID")]
+    MissingJti,
+    #[error("{0}")]
+    Jws(#[from] JwsError),
+}
+
+/// Create a JWT for the access token.
+/// The access token itself stays in the database, the JWT refers to it by its `jti`,
+/// so it can still be revoked.
+///
+/// # Errors
+///
+/// - If the access token has no `jti`
+/// - If signing fails
+#[instrument(skip(signing_key))]
+pub fn create_access_token_jwt(
+    issuer: String,
+    signing_key: &SigningKey,
+    access_token: &AccessToken,
+) -> std::result::Result<String, AccessTokenCreationError> {
+    let claims = AccessTokenClaims {
+        aud: issuer.clone(),
+        iss: issuer,
+        exp: access_token.expires_at,
+        sub: access_token
+            .user_id
+            .clone()
+            .unwrap_or_else(|| access_token.client_id.clone()),
+        client_id: access_token.client_id.clone(),
+        iat: access_token.issued_at,
+        jti: access_token
+            .jti
+            .clone()
+            .ok_or(AccessTokenCreationError::MissingJti)?,
+        scope: access_token.scopes.clone(),
+        auth_time: access_token.auth_time,
+    };
+
+    Ok(jws::sign(signing_key, ACCESS_TOKEN_JWT_TYPE, &claims)?)
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogoutTokenClaims {
+    /// The issuer of the token
+    iss: String,
+    /// The client the token is intended for
+    aud: String,
+    /// Time at which the token was issued
+    iat: i64,
+    /// Expiration time of the token
+    exp: i64,
+    /// Identifies the token, so the client can detect replays
+    jti: String,
+    /// The user that logged out
+    sub: String,
+    /// Identifies the token as a logout token
+    events: LogoutTokenEvents,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogoutTokenEvents {
+    #[serde(rename = "http://schemas.openid.net/event/backchannel-logout")]
+    backchannel_logout: BackchannelLogoutEvent,
+}
+
+/// The back-channel logout event has no members
+#[derive(Debug, Serialize)]
+pub struct BackchannelLogoutEvent {}
+
+/// The `typ` header of logout tokens.
+/// See [OpenID Connect Back-Channel Logout 1.0, Section 2.4](https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken)
+const LOGOUT_TOKEN_JWT_TYPE: &str = "logout+jwt";
+
+/// How long a logout token is valid for, in seconds
+const LOGOUT_TOKEN_LIFETIME: i64 = 120;
+
+/// Create a logout token, notifying the client the user logged out.
+///
+/// # Errors
+///
+/// If signing fails
+#[instrument(skip(signing_key))]
+pub fn create_logout_token(
+    issuer: String,
+    client: &OAuth2Client,
+    signing_key: &SigningKey,
+    user_id: &str,
+) -> std::result::Result<String, JwsError> {
+    let iat = OffsetDateTime::now_utc().unix_timestamp();
+
+    let claims = LogoutTokenClaims {
+        iss: issuer,
+        aud: client.client_id.clone(),
+        iat,
+        exp: iat + LOGOUT_TOKEN_LIFETIME,
+        jti: generate_string(32),
+        sub: user_id.to_string(),
+        events: LogoutTokenEvents {
+            backchannel_logout: BackchannelLogoutEvent {},
+        },
     };
 
-    match jwt_signing_algorithm {
-        JwtSigningAlgorithm::RS256 => {
-            let key = RS256KeyPair::from_pem(oidc_signing_key_pem)
-                .map_err(|e| IdTokenCreationError::Keypair(e.to_string()))?;
-
-            let claims = Claims::with_custom_claims(
-                id_claims,
-                jwt_simple::reexports::coarsetime::Duration::from_secs(
-                    (access_token.expires_at - iat.unix_timestamp()) as u64,
-                ),
-            );
-            Ok(key
-                .sign(claims)
-                .map_err(|e| IdTokenCreationError::Signing(e.to_string()))?)
+    jws::sign(signing_key, LOGOUT_TOKEN_JWT_TYPE, &claims)
+}
+
+/// Compute the `at_hash` claim for an access token.
+/// The hash algorithm is the one used by the signing algorithm:
+/// SHA-256 for RS256 and ES256, SHA-512 for EdDSA with Ed25519.
+///
+/// # Further reading
+/// - [OpenID Connect Core 1.0, Section 3.1.3.6](https://openid.net/specs/openid-connect-core-1_0.html#CodeIDToken)
+fn access_token_hash(access_token: &str, algorithm: JwtSigningAlgorithm) -> String {
+    let digest = match algorithm {
+        JwtSigningAlgorithm::RS256 | JwtSigningAlgorithm::ES256 => {
+            Sha256::digest(access_token.as_bytes()).to_vec()
+        }
+        JwtSigningAlgorithm::EdDSA => Sha512::digest(access_token.as_bytes()).to_vec(),
+    };
+
+    BASE64_URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    fn client(secret: &str) -> OAuth2Client {
+        OAuth2Client {
+            name: "client".to_string(),
+            client_id: "client".to_string(),
+            client_secret_hash: hash_token(secret),
+            previous_client_secret_hash: None,
+            previous_client_secret_expires_at: None,
+            is_internal: false,
+            is_public: false,
+            allow_loopback_any_port: false,
+            legacy_id_token_claims: false,
+            id_token_signed_response_alg: JwtSigningAlgorithm::default(),
+            access_token_format: AccessTokenFormat::default(),
+            backchannel_logout_uri: None,
+            token_endpoint_auth_method: TokenEndpointAuthMethod::default(),
+            jwks: None,
+            grant_types: Json(Vec::new()),
+            response_types: Json(Vec::new()),
+            logo_uri: None,
+            contacts: Json(Vec::new()),
+            registration_access_token_hash: None,
         }
     }
+
+    #[test]
+    fn current_secret() {
+        let client = client("secret");
+
+        assert!(client.verify_secret_at("secret", NOW));
+        assert!(!client.verify_secret_at("other", NOW));
+    }
+
+    #[test]
+    fn rotate_with_grace_period() {
+        let mut client = client("secret");
+        let new_secret = client.rotate_secret_at(Some(60), NOW);
+
+        assert!(client.verify_secret_at(&new_secret, NOW));
+        assert!(client.verify_secret_at("secret", NOW));
+        assert!(client.verify_secret_at("secret", NOW + 59));
+        assert!(!client.verify_secret_at("other", NOW));
+    }
+
+    #[test]
+    fn previous_secret_expires() {
+        let mut client = client("secret");
+        let new_secret = client.rotate_secret_at(Some(60), NOW);
+
+        assert!(!client.verify_secret_at("secret", NOW + 60));
+        assert!(client.verify_secret_at(&new_secret, NOW + 60));
+    }
+
+    #[test]
+    fn rotate_without_grace_period() {
+        let mut client = client("secret");
+        let new_secret = client.rotate_secret_at(None, NOW);
+
+        assert!(client.verify_secret_at(&new_secret, NOW));
+        assert!(!client.verify_secret_at("secret", NOW));
+    }
+
+    #[test]
+    fn rotate_again_during_grace_period() {
+        let mut client = client("secret");
+        let second = client.rotate_secret_at(Some(60), NOW);
+        let third = client.rotate_secret_at(Some(60), NOW + 30);
+
+        // Only the secret replaced last remains valid, with a new grace period
+        assert!(!client.verify_secret_at("secret", NOW + 30));
+        assert!(client.verify_secret_at(&second, NOW + 89));
+        assert!(!client.verify_secret_at(&second, NOW + 90));
+        assert!(client.verify_secret_at(&third, NOW + 90));
+
+        // Rotating without grace period also invalidates the secret in its grace period
+        let fourth = client.rotate_secret_at(None, NOW + 40);
+        assert!(!client.verify_secret_at(&second, NOW + 40));
+        assert!(!client.verify_secret_at(&third, NOW + 40));
+        assert!(client.verify_secret_at(&fourth, NOW + 40));
+    }
 }
@@ -0,0 +1,264 @@
+use crate::driver::Database;
+use crate::{generate_string, impl_enum_type};
+use sqlx::{Decode, Encode, FromRow, Result};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// The time in seconds a WebAuthn ceremony must be completed in
+const CHALLENGE_LIFETIME: i64 = 5 * 60;
+
+/// A WebAuthn credential registered by a user.
+/// Used to log in without password, or as second factor instead of a TOTP code.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserPasskey {
+    /// The base64url encoded credential ID, chosen by the authenticator
+    pub credential_id: String,
+    pub user_id: String,
+    /// The name the user gave the passkey, e.g. the device it is stored on
+    pub name: String,
+    /// The base64url encoded COSE public key
+    pub public_key: String,
+    /// The signature counter reported by the authenticator when the passkey was last used.
+    /// Authenticators that do not implement a counter always report 0.
+    pub sign_count: i64,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+/// The WebAuthn ceremony a challenge was issued for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Ceremony {
+    /// Registering a new passkey
+    Registration,
+    /// Logging in with a passkey
+    Authentication,
+}
+
+impl_enum_type!(Ceremony);
+
+/// A challenge a WebAuthn ceremony in progress has to sign.
+#[derive(Debug, Clone, FromRow)]
+pub struct WebauthnChallenge {
+    /// The base64url encoded challenge
+    pub challenge: String,
+    pub ceremony: Ceremony,
+    /// The user registering a passkey. Not known in advance when logging in.
+    pub user_id: Option<String>,
+    pub expires_at: i64,
+}
+
+impl UserPasskey {
+    /// Store a newly registered passkey.
+    ///
+    /// # Errors
+    ///
+    /// - If the query fails
+    /// - If a passkey with the credential ID already exists
+    #[instrument(skip(driver, public_key))]
+    pub async fn new(
+        driver: &Database,
+        credential_id: String,
+        user_id: String,
+        name: String,
+        public_key: String,
+        sign_count: i64,
+    ) -> Result<Self> {
+        let passkey = Self {
+            credential_id,
+            user_id,
+            name,
+            public_key,
+            sign_count,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            last_used_at: None,
+        };
+
+        sqlx::query("INSERT INTO user_passkeys (credential_id, user_id, name, public_key, sign_count, created_at, last_used_at) VALUES (?, ?, ?, ?, ?, ?, ?)")
+            .bind(&passkey.credential_id)
+            .bind(&passkey.user_id)
+            .bind(&passkey.name)
+            .bind(&passkey.public_key)
+            .bind(passkey.sign_count)
+            .bind(passkey.created_at)
+            .bind(passkey.last_used_at)
+            .execute(&**driver)
+            .await?;
+
+        Ok(passkey)
+    }
+
+    /// Get a passkey by its base64url encoded credential ID.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get_by_credential_id(
+        driver: &Database,
+        credential_id: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM user_passkeys WHERE credential_id = ?")
+            .bind(credential_id)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// List the passkeys of the user.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn list_for_user(driver: &Database, user_id: &str) -> Result<Vec<Self>> {
+        sqlx::query_as("SELECT * FROM user_passkeys WHERE user_id = ? ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(&**driver)
+            .await
+    }
+
+    /// Record that the passkey was used to log in, with the signature counter reported by the authenticator.
+    /// Returns false if the counter did not increase, which indicates the passkey was cloned.
+    /// Counters which are always 0 are not checked.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn record_use(&mut self, driver: &Database, sign_count: i64) -> Result<bool> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        // Checked in the query itself, so concurrent logins cannot report the same counter
+        let result = sqlx::query("UPDATE user_passkeys SET sign_count = ?, last_used_at = ? WHERE credential_id = ? AND (sign_count < ? OR (sign_count = 0 AND ? = 0))")
+            .bind(sign_count)
+            .bind(now)
+            .bind(&self.credential_id)
+            .bind(sign_count)
+            .bind(sign_count)
+            .execute(&**driver)
+            .await?;
+
+        let accepted = result.rows_affected() == 1;
+        if accepted {
+            self.sign_count = sign_count;
+            self.last_used_at = Some(now);
+        }
+
+        Ok(accepted)
+    }
+
+    /// Rename the passkey.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn set_name(&mut self, driver: &Database, name: &str) -> Result<()> {
+        sqlx::query("UPDATE user_passkeys SET name = ? WHERE credential_id = ?")
+            .bind(name)
+            .bind(&self.credential_id)
+            .execute(&**driver)
+            .await?;
+
+        self.name = name.to_string();
+
+        Ok(())
+    }
+
+    /// Remove the passkey. It can no longer be used to log in.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn delete(self, driver: &Database) -> Result<()> {
+        sqlx::query("DELETE FROM user_passkeys WHERE credential_id = ?")
+            .bind(&self.credential_id)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl WebauthnChallenge {
+    /// Issue a new challenge for a ceremony.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn new(
+        driver: &Database,
+        ceremony: Ceremony,
+        user_id: Option<String>,
+    ) -> Result<Self> {
+        let challenge = Self {
+            // Alphanumeric characters are valid base64url,
+            // and 43 characters encode at least 32 random bytes as recommended by WebAuthn
+            challenge: generate_string(43),
+            ceremony,
+            user_id,
+            expires_at: OffsetDateTime::now_utc().unix_timestamp() + CHALLENGE_LIFETIME,
+        };
+
+        sqlx::query("INSERT INTO webauthn_challenges (challenge, ceremony, user_id, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(&challenge.challenge)
+            .bind(challenge.ceremony)
+            .bind(&challenge.user_id)
+            .bind(challenge.expires_at)
+            .execute(&**driver)
+            .await?;
+
+        Ok(challenge)
+    }
+
+    /// Take a challenge issued for the ceremony, so it cannot be used again.
+    /// Expired challenges are not returned.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn take(
+        driver: &Database,
+        challenge: &str,
+        ceremony: Ceremony,
+    ) -> Result<Option<Self>> {
+        let mut tx = driver.begin().await?;
+
+        let found: Option<Self> = sqlx::query_as(
+            "SELECT * FROM webauthn_challenges WHERE challenge = ? AND ceremony = ? FOR UPDATE",
+        )
+        .bind(challenge)
+        .bind(ceremony)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM webauthn_challenges WHERE challenge = ?")
+            .bind(challenge)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Ok(found.filter(|c| c.expires_at > now))
+    }
+
+    /// Delete all expired challenges.
+    /// Returns the number of challenges deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete_expired(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM webauthn_challenges WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
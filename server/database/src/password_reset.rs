@@ -0,0 +1,167 @@
+use crate::driver::Database;
+use crate::{generate_string, hash_token};
+use sqlx::{FromRow, Result};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// The time in seconds a password reset link is valid for
+const TOKEN_LIFETIME: i64 = 60 * 60;
+
+/// A single-use token allowing a user who forgot their password to set a new password.
+/// Only the hash of the token is stored, the token itself is emailed to the user.
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordResetToken {
+    pub token_hash: String,
+    pub user_id: String,
+    pub expires_at: i64,
+}
+
+impl PasswordResetToken {
+    /// Issue a new token for the user.
+    /// Tokens issued to the user earlier can no longer be used.
+    /// Returns the token to send to the user.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn new(driver: &Database, user_id: &str) -> Result<String> {
+        let (token, reset) = Self::generate(user_id, OffsetDateTime::now_utc().unix_timestamp());
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(&reset.token_hash)
+        .bind(&reset.user_id)
+        .bind(reset.expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(token)
+    }
+
+    /// Take a token, so it cannot be used again.
+    /// Expired tokens are not returned.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver, token))]
+    pub async fn take(driver: &Database, token: &str) -> Result<Option<Self>> {
+        let token_hash = hash_token(token);
+        let mut tx = driver.begin().await?;
+
+        let found: Option<Self> =
+            sqlx::query_as("SELECT * FROM password_reset_tokens WHERE token_hash = ? FOR UPDATE")
+                .bind(&token_hash)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        sqlx::query("DELETE FROM password_reset_tokens WHERE token_hash = ?")
+            .bind(&token_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Ok(found.filter(|t| !t.is_expired(now)))
+    }
+
+    /// Make a token taken with [Self::take] usable again, e.g. because the password could not be changed.
+    /// The token is not restored if a new token was issued to the user in the meantime,
+    /// as that invalidated this token.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip_all)]
+    pub async fn restore(&self, driver: &Database) -> Result<()> {
+        sqlx::query("INSERT INTO password_reset_tokens (token_hash, user_id, expires_at) SELECT ?, ?, ? FROM DUAL WHERE NOT EXISTS (SELECT 1 FROM password_reset_tokens WHERE user_id = ?)")
+            .bind(&self.token_hash)
+            .bind(&self.user_id)
+            .bind(self.expires_at)
+            .bind(&self.user_id)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all expired tokens.
+    /// Returns the number of tokens deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete_expired(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    /// Generate a token for the user, issued at the UNIX timestamp `now`.
+    /// Returns the token to send to the user, and the record to store.
+    fn generate(user_id: &str, now: i64) -> (String, Self) {
+        let token = generate_string(43);
+        let reset = Self {
+            token_hash: hash_token(&token),
+            user_id: user_id.to_string(),
+            expires_at: now + TOKEN_LIFETIME,
+        };
+
+        (token, reset)
+    }
+
+    /// Whether the token has expired at the UNIX timestamp `now`
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    #[test]
+    fn only_hash_is_stored() {
+        let (token, reset) = PasswordResetToken::generate("user", NOW);
+
+        assert_eq!(token.len(), 43);
+        assert_ne!(reset.token_hash, token);
+        assert_eq!(reset.token_hash, hash_token(&token));
+        assert_eq!(reset.user_id, "user");
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        let (first, _) = PasswordResetToken::generate("user", NOW);
+        let (second, _) = PasswordResetToken::generate("user", NOW);
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn expiry() {
+        let (_, reset) = PasswordResetToken::generate("user", NOW);
+
+        assert!(!reset.is_expired(NOW));
+        assert!(!reset.is_expired(NOW + TOKEN_LIFETIME - 1));
+        assert!(reset.is_expired(NOW + TOKEN_LIFETIME));
+    }
+}
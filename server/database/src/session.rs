@@ -0,0 +1,220 @@
+use crate::driver::Database;
+use crate::{generate_string, hash_token};
+use sqlx::{FromRow, Result};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// A login session of a user.
+/// While the session is valid, the user does not have to enter their credentials
+/// again to authorize another client.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserSession {
+    /// Identifies the session, may be shown to the user
+    pub session_id: String,
+    /// The SHA-256 hash of the secret stored in the user's session cookie.
+    /// The secret itself is not stored.
+    pub token_hash: String,
+    pub user_id: String,
+    /// UNIX timestamp at which the user authenticated
+    pub auth_time: i64,
+    /// The authentication methods used, space separated.
+    /// See [RFC 8176](https://datatracker.ietf.org/doc/html/rfc8176)
+    pub amr: String,
+    pub created_at: i64,
+    /// UNIX timestamp at which the session was last used to authorize a client
+    pub last_used_at: i64,
+    pub expires_at: i64,
+    /// The user agent the user logged in with
+    pub user_agent: Option<String>,
+}
+
+/// Authentication method reference values.
+/// See [RFC 8176, Section 2](https://datatracker.ietf.org/doc/html/rfc8176#section-2)
+pub mod amr {
+    /// Password-based authentication
+    pub const PASSWORD: &str = "pwd";
+    /// One-time password
+    pub const OTP: &str = "otp";
+    /// Proof-of-possession of a hardware-secured key, e.g. a passkey
+    pub const HARDWARE_KEY: &str = "hwk";
+    /// Multiple-factor authentication
+    pub const MFA: &str = "mfa";
+}
+
+impl UserSession {
+    /// Start a new session for the user, valid for `lifetime` seconds.
+    /// Returns the session and the token to store in the session cookie.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn new(
+        driver: &Database,
+        user_id: &str,
+        amr: &[&str],
+        lifetime: i64,
+        user_agent: Option<String>,
+    ) -> Result<(Self, String)> {
+        let token = generate_string(64);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let session = Self {
+            session_id: generate_string(32),
+            token_hash: hash_token(&token),
+            user_id: user_id.to_string(),
+            auth_time: now,
+            amr: amr.join(" "),
+            created_at: now,
+            last_used_at: now,
+            expires_at: now + lifetime,
+            user_agent,
+        };
+
+        sqlx::query("INSERT INTO user_sessions (session_id, token_hash, user_id, auth_time, amr, created_at, last_used_at, expires_at, user_agent) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(&session.session_id)
+            .bind(&session.token_hash)
+            .bind(&session.user_id)
+            .bind(session.auth_time)
+            .bind(&session.amr)
+            .bind(session.created_at)
+            .bind(session.last_used_at)
+            .bind(session.expires_at)
+            .bind(&session.user_agent)
+            .execute(&**driver)
+            .await?;
+
+        Ok((session, token))
+    }
+
+    /// Get a session by the token from the session cookie.
+    /// Expired sessions are not returned.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip_all)]
+    pub async fn get_by_token(driver: &Database, token: &str) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM user_sessions WHERE token_hash = ? AND expires_at > ?")
+            .bind(hash_token(token))
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// List the sessions of the user that have not expired.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn list_for_user(driver: &Database, user_id: &str) -> Result<Vec<Self>> {
+        sqlx::query_as(
+            "SELECT * FROM user_sessions WHERE user_id = ? AND expires_at > ? ORDER BY last_used_at DESC",
+        )
+        .bind(user_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .fetch_all(&**driver)
+        .await
+    }
+
+    /// Record that the session was used to authorize a client.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip_all)]
+    pub async fn touch(&self, driver: &Database) -> Result<()> {
+        sqlx::query("UPDATE user_sessions SET last_used_at = ? WHERE session_id = ?")
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .bind(&self.session_id)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Terminate the session. The user has to log in again.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip_all)]
+    pub async fn terminate(self, driver: &Database) -> Result<()> {
+        sqlx::query("DELETE FROM user_sessions WHERE session_id = ?")
+            .bind(&self.session_id)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Log out of the session: terminate it, and revoke the authorization codes,
+    /// access tokens and refresh tokens issued for authorizations granted with it.
+    /// Other sessions of the user, and the tokens issued for them, are not affected.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip_all)]
+    pub async fn logout(self, driver: &Database) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        for query in [
+            "DELETE FROM oauth2_pending_authorizations WHERE session_id = ?",
+            "DELETE FROM oauth2_authorization_codes WHERE session_id = ?",
+            "DELETE FROM oauth2_access_tokens WHERE session_id = ?",
+            "DELETE FROM oauth2_refresh_tokens WHERE session_id = ?",
+            "DELETE FROM user_sessions WHERE session_id = ?",
+        ] {
+            sqlx::query(query)
+                .bind(&self.session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Terminate all sessions of the user.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn terminate_all(driver: &Database, user_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM user_sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&**driver)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all expired sessions.
+    /// Returns the number of sessions deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete_expired(driver: &Database) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM user_sessions WHERE expires_at <= ?")
+                .bind(OffsetDateTime::now_utc().unix_timestamp())
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    /// The authentication methods used to start the session.
+    pub fn amr(&self) -> Vec<String> {
+        self.amr
+            .split(' ')
+            .filter(|m| !m.is_empty())
+            .map(|m| m.to_string())
+            .collect()
+    }
+}
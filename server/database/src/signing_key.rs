@@ -0,0 +1,255 @@
+use crate::driver::Database;
+use crate::impl_enum_type;
+use serde::{Deserialize, Serialize};
+use sqlx::{Decode, Encode, FromRow, Result};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// A key used to sign OpenID Connect ID tokens.
+#[derive(Debug, Clone, FromRow)]
+pub struct SigningKey {
+    /// The key ID, the RFC 7638 thumbprint of the public key
+    pub kid: String,
+    /// PEM encoded private key
+    pub private_key: String,
+    /// PEM encoded public key
+    pub public_key: String,
+    /// The algorithm the key is used with
+    pub algorithm: JwtSigningAlgorithm,
+    pub state: SigningKeyState,
+    pub created_at: i64,
+    /// When the key became the active key
+    pub activated_at: Option<i64>,
+    /// When the key was retired
+    pub retired_at: Option<i64>,
+}
+
+/// The lifecycle of a signing key: `Next` -> `Active` -> `Retired`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize)]
+pub enum SigningKeyState {
+    /// Published, but not yet used for signing.
+    /// Clients can pick up the key before it is used.
+    Next,
+    /// Used for signing. There is at most one active key per algorithm.
+    Active,
+    /// No longer used for signing.
+    /// Published until ID tokens signed with it have expired.
+    Retired,
+}
+
+impl_enum_type!(SigningKeyState);
+
+/// The algorithms ID tokens can be signed with.
+/// Every algorithm has its own set of keys.
+///
+/// # Further reading
+/// - [RFC 7518, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7518#section-3.1)
+/// - [RFC 8037, Section 3.1](https://datatracker.ietf.org/doc/html/rfc8037#section-3.1)
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Encode, Decode, Serialize, Deserialize,
+)]
+pub enum JwtSigningAlgorithm {
+    /// RSASSA-PKCS1-v1_5 using SHA-256
+    #[default]
+    RS256,
+    /// ECDSA using P-256 and SHA-256
+    ES256,
+    /// EdDSA using Ed25519
+    EdDSA,
+}
+
+impl_enum_type!(JwtSigningAlgorithm);
+
+impl JwtSigningAlgorithm {
+    /// All supported algorithms
+    pub const ALL: [Self; 3] = [Self::RS256, Self::ES256, Self::EdDSA];
+
+    /// The name of the algorithm, as used in the `alg` JOSE header
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RS256 => "RS256",
+            Self::ES256 => "ES256",
+            Self::EdDSA => "EdDSA",
+        }
+    }
+}
+
+impl SigningKey {
+    /// Store a new signing key.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver, private_key))]
+    pub async fn new(
+        driver: &Database,
+        kid: String,
+        private_key: String,
+        public_key: String,
+        algorithm: JwtSigningAlgorithm,
+        state: SigningKeyState,
+    ) -> Result<Self> {
+        let created_at = OffsetDateTime::now_utc().unix_timestamp();
+        let activated_at = (state == SigningKeyState::Active).then_some(created_at);
+
+        sqlx::query("INSERT INTO oidc_signing_keys (kid, private_key, public_key, algorithm, state, created_at, activated_at) VALUES (?, ?, ?, ?, ?, ?, ?)")
+            .bind(&kid)
+            .bind(&private_key)
+            .bind(&public_key)
+            .bind(algorithm)
+            .bind(state)
+            .bind(created_at)
+            .bind(activated_at)
+            .execute(&**driver)
+            .await?;
+
+        Ok(Self {
+            kid,
+            private_key,
+            public_key,
+            algorithm,
+            state,
+            created_at,
+            activated_at,
+            retired_at: None,
+        })
+    }
+
+    /// Get the key currently used for signing with the algorithm.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get_active(
+        driver: &Database,
+        algorithm: JwtSigningAlgorithm,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM oidc_signing_keys WHERE state = ? AND algorithm = ?")
+            .bind(SigningKeyState::Active)
+            .bind(algorithm)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// Get the key that becomes active on the next rotation with the algorithm.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get_next(
+        driver: &Database,
+        algorithm: JwtSigningAlgorithm,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM oidc_signing_keys WHERE state = ? AND algorithm = ?")
+            .bind(SigningKeyState::Next)
+            .bind(algorithm)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// Get a key by its key ID.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get_by_kid(driver: &Database, kid: &str) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM oidc_signing_keys WHERE kid = ?")
+            .bind(kid)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// List all keys.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn list(driver: &Database) -> Result<Vec<Self>> {
+        sqlx::query_as("SELECT * FROM oidc_signing_keys ORDER BY created_at")
+            .fetch_all(&**driver)
+            .await
+    }
+
+    /// List the keys that should be published, i.e. the next and active key,
+    /// and keys retired less than `retired_key_lifetime` seconds ago.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn list_published(driver: &Database, retired_key_lifetime: i64) -> Result<Vec<Self>> {
+        sqlx::query_as("SELECT * FROM oidc_signing_keys WHERE state != ? OR retired_at > ? ORDER BY created_at")
+            .bind(SigningKeyState::Retired)
+            .bind(OffsetDateTime::now_utc().unix_timestamp() - retired_key_lifetime)
+            .fetch_all(&**driver)
+            .await
+    }
+
+    /// Make the next key the active key, and retire the currently active key,
+    /// for the algorithm. Returns `false` if there is no next key.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn promote_next(driver: &Database, algorithm: JwtSigningAlgorithm) -> Result<bool> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut tx = driver.begin().await?;
+
+        let next: Option<String> = sqlx::query_scalar(
+            "SELECT kid FROM oidc_signing_keys WHERE state = ? AND algorithm = ? FOR UPDATE",
+        )
+        .bind(SigningKeyState::Next)
+        .bind(algorithm)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(next) = next else {
+            tx.rollback().await?;
+            return Ok(false);
+        };
+
+        sqlx::query(
+            "UPDATE oidc_signing_keys SET state = ?, retired_at = ? WHERE state = ? AND algorithm = ?",
+        )
+        .bind(SigningKeyState::Retired)
+        .bind(now)
+        .bind(SigningKeyState::Active)
+        .bind(algorithm)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE oidc_signing_keys SET state = ?, activated_at = ? WHERE kid = ?")
+            .bind(SigningKeyState::Active)
+            .bind(now)
+            .bind(&next)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Delete keys retired more than `retired_key_lifetime` seconds ago.
+    /// Returns the number of deleted keys.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete_expired(driver: &Database, retired_key_lifetime: i64) -> Result<u64> {
+        Ok(
+            sqlx::query("DELETE FROM oidc_signing_keys WHERE state = ? AND retired_at <= ?")
+                .bind(SigningKeyState::Retired)
+                .bind(OffsetDateTime::now_utc().unix_timestamp() - retired_key_lifetime)
+                .execute(&**driver)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
@@ -0,0 +1,237 @@
+use crate::driver::Database;
+use crate::hash_token;
+use rand::Rng;
+use sqlx::{FromRow, MySql, Result, Transaction};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+/// The number of recovery codes generated for a user
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters recovery codes consist of. Characters that are easily confused are left out.
+const RECOVERY_CODE_CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// The TOTP second factor of a user, RFC 6238.
+/// Only used for users of the local authorization provider.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserTotp {
+    pub user_id: String,
+    /// The base32 encoded shared secret
+    pub secret: String,
+    /// Whether the user confirmed the enrollment with a first code.
+    /// Codes are only required at login if enabled.
+    pub enabled: bool,
+    pub created_at: i64,
+    /// The last time step a code was accepted for
+    pub last_used_step: Option<i64>,
+}
+
+impl UserTotp {
+    /// Get the TOTP configuration of the user, if any.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn get(driver: &Database, user_id: &str) -> Result<Option<Self>> {
+        sqlx::query_as("SELECT * FROM user_totp WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(&**driver)
+            .await
+    }
+
+    /// Start enrolling the user with a new secret.
+    /// Replaces an enrollment which was not yet confirmed.
+    /// TOTP is not enabled until [Self::enable] is called.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver, secret))]
+    pub async fn new_pending(driver: &Database, user_id: &str, secret: String) -> Result<Self> {
+        let totp = Self {
+            user_id: user_id.to_string(),
+            secret,
+            enabled: false,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            last_used_step: None,
+        };
+
+        sqlx::query("REPLACE INTO user_totp (user_id, secret, enabled, created_at, last_used_step) VALUES (?, ?, ?, ?, ?)")
+            .bind(&totp.user_id)
+            .bind(&totp.secret)
+            .bind(totp.enabled)
+            .bind(totp.created_at)
+            .bind(totp.last_used_step)
+            .execute(&**driver)
+            .await?;
+
+        Ok(totp)
+    }
+
+    /// Enable TOTP after the user confirmed the enrollment with the code for `step`.
+    /// Returns the recovery codes of the user, these cannot be retrieved later.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn enable(&mut self, driver: &Database, step: i64) -> Result<Vec<String>> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("UPDATE user_totp SET enabled = TRUE, last_used_step = ? WHERE user_id = ?")
+            .bind(step)
+            .bind(&self.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let codes = Self::insert_recovery_codes(&mut tx, &self.user_id).await?;
+        tx.commit().await?;
+
+        self.enabled = true;
+        self.last_used_step = Some(step);
+
+        Ok(codes)
+    }
+
+    /// Record that a code for `step` was used.
+    /// Returns false if a code for this or a later step was used before,
+    /// in which case the code must be rejected as replayed.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn use_step(&mut self, driver: &Database, step: i64) -> Result<bool> {
+        // Checked in the query itself, so concurrent logins cannot use the same code
+        let result = sqlx::query("UPDATE user_totp SET last_used_step = ? WHERE user_id = ? AND (last_used_step IS NULL OR last_used_step < ?)")
+            .bind(step)
+            .bind(&self.user_id)
+            .bind(step)
+            .execute(&**driver)
+            .await?;
+
+        let accepted = result.rows_affected() == 1;
+        if accepted {
+            self.last_used_step = Some(step);
+        }
+
+        Ok(accepted)
+    }
+
+    /// Use a recovery code. Each recovery code can be used once.
+    /// Returns false if the code is not a recovery code of the user.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver, code))]
+    pub async fn use_recovery_code(&self, driver: &Database, code: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM user_totp_recovery_codes WHERE user_id = ? AND code_hash = ?")
+                .bind(&self.user_id)
+                .bind(hash_recovery_code(code))
+                .execute(&**driver)
+                .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Get the number of recovery codes the user has left.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn count_recovery_codes(&self, driver: &Database) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(1) FROM user_totp_recovery_codes WHERE user_id = ?")
+            .bind(&self.user_id)
+            .fetch_one(&**driver)
+            .await
+    }
+
+    /// Replace the recovery codes of the user with new codes.
+    /// Returns the new codes, these cannot be retrieved later.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(self, driver))]
+    pub async fn regenerate_recovery_codes(&self, driver: &Database) -> Result<Vec<String>> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM user_totp_recovery_codes WHERE user_id = ?")
+            .bind(&self.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let codes = Self::insert_recovery_codes(&mut tx, &self.user_id).await?;
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Remove TOTP and the recovery codes of the user, whether enabled or not.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn delete(driver: &Database, user_id: &str) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM user_totp_recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert_recovery_codes(
+        tx: &mut Transaction<'_, MySql>,
+        user_id: &str,
+    ) -> Result<Vec<String>> {
+        let codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect::<Vec<_>>();
+
+        for code in &codes {
+            sqlx::query("INSERT INTO user_totp_recovery_codes (user_id, code_hash) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(hash_recovery_code(code))
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        Ok(codes)
+    }
+}
+
+/// Generate a recovery code of the form `xxxxx-xxxxx`
+fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code = (0..10)
+        .map(|_| char::from(RECOVERY_CODE_CHARSET[rng.gen_range(0..RECOVERY_CODE_CHARSET.len())]))
+        .collect::<String>();
+    code.insert(5, '-');
+    code
+}
+
+/// Hash a recovery code. Separators, whitespace and case are ignored,
+/// so users may enter the code the way it was written down.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<String>();
+
+    hash_token(&normalized)
+}
@@ -15,6 +15,8 @@ pub struct User {
     pub email: String,
     pub is_admin: bool,
     pub locale: Locale,
+    /// UNIX timestamp at which the name, email address or locale last changed
+    pub updated_at: i64,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -41,6 +43,16 @@ pub enum Locale {
 
 impl_enum_type!(Locale);
 
+impl Locale {
+    /// The BCP 47 language tag of the locale, e.g. `en`.
+    pub fn language_tag(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Nl => "nl",
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SetEmailAddressError {
     #[error(transparent)]
@@ -69,14 +81,16 @@ impl User {
     ) -> Result<(Self, Option<UserEmailVerification>)> {
         let mut tx = driver.begin().await?;
 
+        let updated_at = current_time();
         sqlx::query(
-            "INSERT INTO users (user_id, name, email, is_admin, locale) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO users (user_id, name, email, is_admin, locale, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
         )
         .bind(&user_id)
         .bind(&name)
         .bind(&email)
         .bind(is_admin)
         .bind(locale)
+        .bind(updated_at)
         .execute(&mut *tx)
         .await?;
 
@@ -115,6 +129,7 @@ impl User {
             email,
             is_admin,
             locale,
+            updated_at,
         };
 
         tx.commit().await?;
@@ -244,13 +259,16 @@ impl User {
     /// If the query fails
     #[instrument(skip(driver))]
     pub async fn set_name(&mut self, driver: &Database, name: &str) -> Result<()> {
-        sqlx::query("UPDATE users SET name = ? WHERE user_id = ?")
+        let updated_at = current_time();
+        sqlx::query("UPDATE users SET name = ?, updated_at = ? WHERE user_id = ?")
             .bind(name)
+            .bind(updated_at)
             .bind(&self.user_id)
             .execute(&**driver)
             .await?;
 
         self.name = name.to_string();
+        self.updated_at = updated_at;
 
         Ok(())
     }
@@ -289,13 +307,16 @@ impl User {
         }
 
         // We can now update the current email
-        sqlx::query("UPDATE users SET email = ? WHERE user_id = ?")
+        let updated_at = current_time();
+        sqlx::query("UPDATE users SET email = ?, updated_at = ? WHERE user_id = ?")
             .bind(email)
+            .bind(updated_at)
             .bind(&self.user_id)
             .execute(&**driver)
             .await?;
 
         self.email = email.to_string();
+        self.updated_at = updated_at;
 
         Ok(())
     }
@@ -307,13 +328,16 @@ impl User {
     /// If the query fails
     #[instrument(skip(driver))]
     pub async fn set_locale(&mut self, driver: &Database, new_locale: Locale) -> Result<()> {
-        sqlx::query("UPDATE users set locale = ? WHERE user_id = ?")
+        let updated_at = current_time();
+        sqlx::query("UPDATE users set locale = ?, updated_at = ? WHERE user_id = ?")
             .bind(new_locale)
+            .bind(updated_at)
             .bind(&self.user_id)
             .execute(&**driver)
             .await?;
 
         self.locale = new_locale;
+        self.updated_at = updated_at;
 
         Ok(())
     }
@@ -519,6 +543,11 @@ impl User {
             .execute(&mut *tx)
             .await?;
 
+        sqlx::query("DELETE FROM user_consents WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
         sqlx::query("DELETE FROM user_email_verifications WHERE user_id = ?")
             .bind(id)
             .execute(&mut *tx)
@@ -529,6 +558,36 @@ impl User {
             .execute(&mut *tx)
             .await?;
 
+        sqlx::query("DELETE FROM user_sessions WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_totp_recovery_codes WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_passkeys WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM webauthn_challenges WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
         // Lastly, delete from users table
         sqlx::query("DELETE FROM users WHERE user_id = ?")
             .bind(id)
@@ -540,6 +599,30 @@ impl User {
         Ok(())
     }
 
+    /// Revoke all access and refresh tokens issued to the user, logging them out of every client.
+    ///
+    /// # Errors
+    ///
+    /// If the query fails
+    #[instrument(skip(driver))]
+    pub async fn revoke_tokens(driver: &Database, id: &str) -> Result<()> {
+        let mut tx = driver.begin().await?;
+
+        sqlx::query("DELETE FROM oauth2_access_tokens WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM oauth2_refresh_tokens WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     /// Check whether the current email address is verified.
     /// This is generally true, except for newly created accounts.
     ///
@@ -0,0 +1,29 @@
+use crate::email::Mailable;
+use database::user::Locale;
+use serde::Serialize;
+
+pub struct AccountLockedMail;
+
+#[derive(Serialize)]
+pub struct AccountLockedData {
+    pub name: String,
+    /// The number of failed login attempts
+    pub failures: u32,
+    /// The number of minutes the account is locked for
+    pub locked_minutes: i64,
+}
+
+impl Mailable for AccountLockedMail {
+    type Data = AccountLockedData;
+
+    fn template_name() -> &'static str {
+        "account_locked"
+    }
+
+    fn subject(locale: &Locale) -> &'static str {
+        match locale {
+            Locale::En => "Your account was locked",
+            Locale::Nl => "Je account is geblokkeerd",
+        }
+    }
+}
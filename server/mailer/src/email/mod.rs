@@ -1,8 +1,10 @@
+mod account_locked;
 mod email_changed;
 mod password_changed;
 mod password_forgotten;
 mod verify_email;
 
+pub use account_locked::*;
 pub use email_changed::*;
 pub use password_changed::*;
 pub use password_forgotten::*;
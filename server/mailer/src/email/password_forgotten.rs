@@ -7,7 +7,8 @@ pub struct PasswordForgottenMail;
 #[derive(Serialize)]
 pub struct PasswordForgottenData {
     pub name: String,
-    pub temporary_password: String,
+    /// The link to the page at which the user can set a new password
+    pub reset_link: String,
 }
 
 impl Mailable for PasswordForgottenMail {
@@ -19,8 +20,8 @@ impl Mailable for PasswordForgottenMail {
 
     fn subject(locale: &Locale) -> &'static str {
         match locale {
-            Locale::Nl => "Tijdelijk wachtwoord",
-            Locale::En => "Temporary password",
+            Locale::Nl => "Wachtwoord herstellen",
+            Locale::En => "Reset your password",
         }
     }
 }
@@ -40,7 +41,7 @@ mod test {
             "t.debruijn@array21.dev",
             &PasswordForgottenData {
                 name: "Tobias".to_string(),
-                temporary_password: "foobar".to_string(),
+                reset_link: "https://example.com/reset-password?token=foobar".to_string(),
             },
             Locale::Nl,
             vec![banner_partial()],
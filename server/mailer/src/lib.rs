@@ -4,7 +4,7 @@
 //!
 //! ```no_run
 //! # use mailer::Mailable;
-//! # async fn main () {
+//! # async fn send_example() {
 //! // Get IPV4 address
 //! let addr = mailer::net::get_local_v4().await.unwrap();
 //! // Establish a connection
@@ -21,7 +21,7 @@
 //!     "sender@array21.dev",
 //!     &mailer::PasswordForgottenData {
 //!         name: "Reciever name".to_string(),
-//!         temporary_password: "foobarbaz".to_string(),
+//!         reset_link: "https://example.com/reset-password?token=foobarbaz".to_string(),
 //!     },
 //!     database::user::Locale::En,
 //!     // You can specify custom Handlebars partials to be used in the templates!
@@ -55,7 +55,7 @@ pub mod net {
 
 #[cfg(test)]
 pub(crate) mod test {
-    use crate::email::HbsTemplate;
+    use crate::HbsTemplate;
     use lettre::transport::smtp::client::AsyncSmtpConnection;
     use std::net::Ipv4Addr;
 
@@ -0,0 +1,173 @@
+//! Access tokens as given to clients.
+//!
+//! Depending on the client's `access_token_format`, the access token is either the opaque token
+//! stored in the database, or a JWT referring to it by its `jti`. Either way the database row
+//! remains the source of truth, so revoked tokens are rejected.
+
+use database::driver::Database;
+use database::oauth2_client::{
+    create_access_token_jwt, AccessToken, AccessTokenCreationError, AccessTokenFormat, OAuth2Client,
+};
+use database::signing_key::{JwtSigningAlgorithm, SigningKey};
+use jwt_simple::claims::NoCustomClaims;
+use jwt_simple::token::Token;
+use thiserror::Error;
+
+use crate::signing_keys;
+
+/// The `typ` header of JWT access tokens.
+/// See [RFC 9068, Section 2.1](https://datatracker.ietf.org/doc/html/rfc9068#section-2.1)
+const JWT_TYPE: &str = "at+jwt";
+
+#[derive(Debug, Error)]
+pub enum AccessTokenError {
+    #[error("{0}")]
+    Database(#[from] database::driver::Error),
+    #[error("No active signing key for {0:?}")]
+    NoSigningKey(JwtSigningAlgorithm),
+    #[error("{0}")]
+    Creation(#[from] AccessTokenCreationError),
+}
+
+/// Get the access token in the format the client expects.
+/// JWT access tokens are signed with the client's `id_token_signed_response_alg`.
+///
+/// # Errors
+///
+/// - If there is no active signing key
+/// - If signing fails
+pub async fn issue(
+    database: &Database,
+    issuer: &str,
+    client: &OAuth2Client,
+    access_token: &AccessToken,
+) -> Result<String, AccessTokenError> {
+    match client.access_token_format {
+        AccessTokenFormat::Opaque => Ok(access_token.token.clone()),
+        AccessTokenFormat::Jwt => {
+            let algorithm = client.id_token_signed_response_alg;
+            let signing_key = SigningKey::get_active(database, algorithm)
+                .await?
+                .ok_or(AccessTokenError::NoSigningKey(algorithm))?;
+
+            Ok(create_access_token_jwt(
+                issuer.to_string(),
+                &signing_key,
+                access_token,
+            )?)
+        }
+    }
+}
+
+/// Look up the access token presented by a client or resource server.
+/// Both opaque tokens and JWT access tokens are accepted.
+/// A JWT is only accepted if its signature is valid.
+/// Returns `None` if the token does not exist or has been revoked.
+/// Expiry is not checked.
+///
+/// # Errors
+///
+/// If a query fails
+pub async fn resolve(
+    database: &Database,
+    token: &str,
+) -> Result<Option<AccessToken>, database::driver::Error> {
+    // Opaque tokens are alphanumeric, JWTs consist of three dot-separated parts
+    if !token.contains('.') {
+        return AccessToken::get_by_token(database, token).await;
+    }
+
+    // Decoding the header does not verify the token, it is only used to
+    // reject other JWTs, such as ID tokens, before the signature is checked
+    let Ok(metadata) = Token::decode_metadata(token) else {
+        return Ok(None);
+    };
+
+    if metadata.signature_type() != Some(JWT_TYPE) {
+        return Ok(None);
+    }
+
+    let Some(claims) = signing_keys::verify::<NoCustomClaims>(database, token, None).await? else {
+        return Ok(None);
+    };
+
+    match claims.jwt_id {
+        Some(jti) => AccessToken::get_by_jti(database, &jti).await,
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use database::signing_key::SigningKeyState;
+    use jwt_simple::algorithms::{ES256KeyPair, Ed25519KeyPair};
+    use jwt_simple::claims::JWTClaims;
+
+    fn signing_key(
+        algorithm: JwtSigningAlgorithm,
+        private_key: String,
+        public_key: String,
+    ) -> SigningKey {
+        SigningKey {
+            kid: "kid".to_string(),
+            private_key,
+            public_key,
+            algorithm,
+            state: SigningKeyState::Active,
+            created_at: 0,
+            activated_at: Some(0),
+            retired_at: None,
+        }
+    }
+
+    fn access_token() -> AccessToken {
+        AccessToken {
+            token: "token".to_string(),
+            jti: Some("jti".to_string()),
+            client_id: "client".to_string(),
+            expires_at: i64::MAX / 2,
+            issued_at: 0,
+            user_id: Some("user".to_string()),
+            scopes: Some("openid".to_string()),
+            refresh_token: None,
+            auth_time: None,
+            session_id: None,
+        }
+    }
+
+    fn round_trip(signing_key: SigningKey) {
+        let token =
+            create_access_token_jwt("issuer".to_string(), &signing_key, &access_token()).unwrap();
+
+        let metadata = Token::decode_metadata(&token).unwrap();
+        assert_eq!(metadata.signature_type(), Some(JWT_TYPE));
+        assert_eq!(metadata.algorithm(), signing_key.algorithm.name());
+        assert_eq!(metadata.key_id(), Some("kid"));
+
+        let claims: JWTClaims<NoCustomClaims> =
+            signing_keys::verify_with_key(&signing_key, &token, None).unwrap();
+        assert_eq!(claims.jwt_id.as_deref(), Some("jti"));
+        assert_eq!(claims.subject.as_deref(), Some("user"));
+    }
+
+    #[test]
+    fn es256() {
+        let key = ES256KeyPair::generate();
+        round_trip(signing_key(
+            JwtSigningAlgorithm::ES256,
+            key.to_pem().unwrap(),
+            key.public_key().to_pem().unwrap(),
+        ));
+    }
+
+    #[test]
+    fn eddsa() {
+        let key = Ed25519KeyPair::generate();
+        round_trip(signing_key(
+            JwtSigningAlgorithm::EdDSA,
+            key.to_pem(),
+            key.public_key().to_pem(),
+        ));
+    }
+}
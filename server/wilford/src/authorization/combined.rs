@@ -3,7 +3,8 @@ use crate::authorization::local_provider::{
     LocalAuthorizationProvider, LocalAuthorizationProviderError,
 };
 use crate::authorization::{
-    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, UserInformation,
+    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, SecondFactor,
+    UserInformation,
 };
 use crate::config::{AuthorizationProviderType, Config};
 use database::driver::Database;
@@ -68,17 +69,40 @@ impl AuthorizationProvider for CombinedAuthorizationProvider<'_> {
         &self,
         username: &str,
         password: &str,
-        totp_code: Option<&str>,
+        second_factor: Option<SecondFactor<'_>>,
     ) -> Result<CredentialsValidationResult, AuthorizationError<CombinedAuthorizationProviderError>>
     {
         Ok(match self {
             Self::Local(credentials_provider) => credentials_provider
-                .validate_credentials(username, password, totp_code)
+                .validate_credentials(username, password, second_factor)
                 .await
                 .map_err(AuthorizationError::convert)?,
 
             Self::EspoCrm(espocrm) => espocrm
-                .validate_credentials(username, password, totp_code)
+                .validate_credentials(username, password, second_factor)
+                .await
+                .map_err(AuthorizationError::convert)?,
+        })
+    }
+
+    fn supports_passkey_login(&self) -> bool {
+        match self {
+            Self::Local(credentials_provider) => credentials_provider.supports_passkey_login(),
+            Self::EspoCrm(espocrm) => espocrm.supports_passkey_login(),
+        }
+    }
+
+    async fn validate_user(
+        &self,
+        user_id: &str,
+    ) -> Result<UserInformation, AuthorizationError<Self::Error>> {
+        Ok(match self {
+            Self::Local(credentials_provider) => credentials_provider
+                .validate_user(user_id)
+                .await
+                .map_err(AuthorizationError::convert)?,
+            Self::EspoCrm(espocrm) => espocrm
+                .validate_user(user_id)
                 .await
                 .map_err(AuthorizationError::convert)?,
         })
@@ -1,5 +1,6 @@
 use crate::authorization::{
-    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, UserInformation,
+    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, SecondFactor,
+    UserInformation,
 };
 use crate::espo::user::{EspoUser, LoginStatus};
 use database::driver::Database;
@@ -41,24 +42,45 @@ pub enum EspoAuthorizationProviderError {
 impl AuthorizationProvider for EspoAuthorizationProvider<'_> {
     type Error = EspoAuthorizationProviderError;
 
-    #[instrument(skip(self, password))]
+    #[instrument(skip(self, password, second_factor))]
     async fn validate_credentials(
         &self,
         username: &str,
         password: &str,
-        totp_code: Option<&str>,
+        second_factor: Option<SecondFactor<'_>>,
     ) -> Result<CredentialsValidationResult, AuthorizationError<Self::Error>> {
-        // Check the credentials with the EspoCRM instance
-        // This will yield the user ID
-        let user_id = match EspoUser::try_login(self.host, username, password, totp_code)
-            .await
-            .map_err(Self::Error::from)?
-        {
-            LoginStatus::Ok(user_id) => user_id,
-            LoginStatus::SecondStepRequired => return Err(AuthorizationError::TotpNeeded),
-            LoginStatus::Err => return Err(AuthorizationError::InvalidCredentials),
+        // Two-factor authentication is configured in EspoCRM, which only knows TOTP codes
+        let totp_code = match second_factor {
+            Some(SecondFactor::Totp(code)) if !code.trim().is_empty() => Some(code),
+            _ => None,
         };
 
+        // Check the credentials with the EspoCRM instance
+        // This will yield the user ID.
+        // The code is only sent if EspoCRM asks for it, so the second factor is only
+        // reported as verified if EspoCRM required and accepted it.
+        let (user_id, second_factor_verified) =
+            match EspoUser::try_login(self.host, username, password, None)
+                .await
+                .map_err(Self::Error::from)?
+            {
+                LoginStatus::Ok(user_id) => (user_id, false),
+                LoginStatus::SecondStepRequired => {
+                    let Some(totp_code) = totp_code else {
+                        return Err(AuthorizationError::TotpNeeded);
+                    };
+
+                    match EspoUser::try_login(self.host, username, password, Some(totp_code))
+                        .await
+                        .map_err(Self::Error::from)?
+                    {
+                        LoginStatus::Ok(user_id) => (user_id, true),
+                        _ => return Err(AuthorizationError::InvalidCredentials),
+                    }
+                }
+                LoginStatus::Err => return Err(AuthorizationError::InvalidCredentials),
+            };
+
         // Fetch user information
         let espo_user = EspoUser::get_by_id(&self.espocrm_client, &user_id)
             .await
@@ -120,6 +142,34 @@ impl AuthorizationProvider for EspoAuthorizationProvider<'_> {
                 is_admin: espo_user.user_type.eq("admin"),
             },
             require_password_change: false,
+            second_factor_verified,
+        })
+    }
+
+    fn supports_passkey_login(&self) -> bool {
+        // Logging in with a passkey would bypass EspoCRM's own two-factor authentication
+        false
+    }
+
+    #[instrument(skip_all)]
+    async fn validate_user(
+        &self,
+        user_id: &str,
+    ) -> Result<UserInformation, AuthorizationError<Self::Error>> {
+        let espo_user = EspoUser::get_by_id(&self.espocrm_client, user_id)
+            .await
+            .map_err(Self::Error::from)?;
+
+        if !espo_user.is_active {
+            return Err(AuthorizationError::InvalidCredentials);
+        }
+
+        Ok(UserInformation {
+            is_admin: espo_user.user_type.eq("admin"),
+            id: espo_user.id,
+            name: espo_user.name,
+            email: espo_user.email_address,
+            email_verification: None,
         })
     }
 
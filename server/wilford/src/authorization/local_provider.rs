@@ -1,8 +1,12 @@
 use crate::authorization::{
-    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, UserInformation,
+    AuthorizationError, AuthorizationProvider, CredentialsValidationResult, SecondFactor,
+    UserInformation,
 };
+use crate::totp;
+use actix_web::cookie::time::OffsetDateTime;
 use bcrypt::{hash_with_result, verify, Version};
 use database::driver::Database;
+use database::totp::UserTotp;
 use database::user::{Locale, User};
 use rand::Rng;
 use tap::TapOptional;
@@ -29,17 +33,70 @@ impl<'a> LocalAuthorizationProvider<'a> {
     pub fn new(driver: &'a Database) -> Self {
         Self { driver }
     }
+
+    /// Check the second factor of the user.
+    /// If the user enabled TOTP, a second factor is required. A TOTP code,
+    /// one of the user's recovery codes or one of the user's passkeys is accepted.
+    /// Returns whether a second factor was verified.
+    ///
+    /// # Errors
+    ///
+    /// - If the user enabled TOTP, but no second factor was provided
+    /// - If the code is invalid, or was used before
+    /// - If the passkey belongs to another user
+    /// - If a query fails
+    pub async fn verify_second_factor(
+        &self,
+        user: &User,
+        second_factor: Option<SecondFactor<'_>>,
+    ) -> Result<bool, AuthorizationError<LocalAuthorizationProviderError>> {
+        // The passkey was verified by the caller, it only has to belong to the user
+        if let Some(SecondFactor::Passkey(owner)) = second_factor {
+            return if owner == user.user_id {
+                Ok(true)
+            } else {
+                Err(AuthorizationError::InvalidCredentials)
+            };
+        }
+
+        let mut totp = match UserTotp::get(self.driver, &user.user_id)
+            .await
+            .map_err(LocalAuthorizationProviderError::from)?
+        {
+            Some(totp) if totp.enabled => totp,
+            _ => return Ok(false),
+        };
+
+        let code = match second_factor {
+            Some(SecondFactor::Totp(code)) if !code.trim().is_empty() => code,
+            _ => return Err(AuthorizationError::TotpNeeded),
+        };
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let accepted = match totp::verify(&totp.secret, code, now) {
+            // A code is only valid once, even within its period
+            Some(step) => totp.use_step(self.driver, step).await,
+            None => totp.use_recovery_code(self.driver, code).await,
+        }
+        .map_err(LocalAuthorizationProviderError::from)?;
+
+        if accepted {
+            Ok(true)
+        } else {
+            Err(AuthorizationError::InvalidCredentials)
+        }
+    }
 }
 
 impl AuthorizationProvider for LocalAuthorizationProvider<'_> {
     type Error = LocalAuthorizationProviderError;
 
-    #[instrument(skip(self, password))]
+    #[instrument(skip(self, password, second_factor))]
     async fn validate_credentials(
         &self,
         username: &str,
         password: &str,
-        _: Option<&str>,
+        second_factor: Option<SecondFactor<'_>>,
     ) -> Result<CredentialsValidationResult, AuthorizationError<Self::Error>> {
         // Fetch the user
         let user = User::get_by_email(self.driver, username)
@@ -67,20 +124,46 @@ impl AuthorizationProvider for LocalAuthorizationProvider<'_> {
             // Unwrap is safe, only None if there is no password either.
             .unwrap();
 
-        if ok {
-            Ok(CredentialsValidationResult {
-                user_information: UserInformation {
-                    id: user.user_id,
-                    email: user.email,
-                    name: user.name,
-                    is_admin: user.is_admin,
-                    email_verification: None,
-                },
-                require_password_change,
-            })
-        } else {
-            Err(AuthorizationError::InvalidCredentials)
+        if !ok {
+            return Err(AuthorizationError::InvalidCredentials);
         }
+
+        let second_factor_verified = self.verify_second_factor(&user, second_factor).await?;
+
+        Ok(CredentialsValidationResult {
+            user_information: UserInformation {
+                id: user.user_id,
+                email: user.email,
+                name: user.name,
+                is_admin: user.is_admin,
+                email_verification: None,
+            },
+            require_password_change,
+            second_factor_verified,
+        })
+    }
+
+    fn supports_passkey_login(&self) -> bool {
+        true
+    }
+
+    #[instrument(skip(self))]
+    async fn validate_user(
+        &self,
+        user_id: &str,
+    ) -> Result<UserInformation, AuthorizationError<Self::Error>> {
+        let user = User::get_by_id(self.driver, user_id)
+            .await
+            .map_err(Self::Error::from)?
+            .ok_or(AuthorizationError::InvalidCredentials)?;
+
+        Ok(UserInformation {
+            id: user.user_id,
+            email: user.email,
+            name: user.name,
+            is_admin: user.is_admin,
+            email_verification: None,
+        })
     }
 
     fn supports_password_change(&self) -> bool {
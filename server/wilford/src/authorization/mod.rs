@@ -58,6 +58,16 @@ pub struct UserInformation {
     pub email_verification: Option<UserEmailVerification>,
 }
 
+/// A second factor presented in addition to the password
+#[derive(Debug, Clone, Copy)]
+pub enum SecondFactor<'a> {
+    /// A TOTP code. The local provider also accepts recovery codes.
+    Totp(&'a str),
+    /// A passkey, of which the caller verified the WebAuthn assertion.
+    /// Contains the ID of the user the passkey belongs to.
+    Passkey(&'a str),
+}
+
 pub struct CredentialsValidationResult {
     /// Information about the user
     pub user_information: UserInformation,
@@ -66,6 +76,8 @@ pub struct CredentialsValidationResult {
     /// this field is also provided via the `/user/info` endpoint.
     #[allow(unused)]
     pub require_password_change: bool,
+    /// Whether a second factor was verified in addition to the password
+    pub second_factor_verified: bool,
 }
 
 pub trait AuthorizationProvider {
@@ -83,9 +95,26 @@ pub trait AuthorizationProvider {
         &self,
         username: &str,
         password: &str,
-        totp_code: Option<&str>,
+        second_factor: Option<SecondFactor<'_>>,
     ) -> Result<CredentialsValidationResult, AuthorizationError<Self::Error>>;
 
+    /// Whether users may log in with a passkey instead of their password.
+    fn supports_passkey_login(&self) -> bool;
+
+    /// Validate a user who did not enter their password, e.g. because they logged in with a passkey,
+    /// or resume their login session.
+    /// The caller is responsible for verifying the credential or session of the user,
+    /// the provider checks whether the user may still log in.
+    ///
+    /// # Errors
+    /// - If the user may not log in
+    /// - If the user does not exist
+    /// - If an underlying operation fails
+    async fn validate_user(
+        &self,
+        user_id: &str,
+    ) -> Result<UserInformation, AuthorizationError<Self::Error>>;
+
     /// Whether the authorization provider supports changing the user's password.
     fn supports_password_change(&self) -> bool;
 
@@ -131,6 +160,7 @@ pub trait AuthorizationProvider {
     /// # Errors
     /// - If the operation is not supported
     /// - IF the underlying operation fails
+    #[allow(unused)]
     async fn set_email(
         &mut self,
         user_id: &str,
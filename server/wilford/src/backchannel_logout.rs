@@ -0,0 +1,72 @@
+//! Notifying clients a user logged out, by sending a logout token to their back-channel logout URI.
+//!
+//! # Further reading
+//! - [OpenID Connect Back-Channel Logout 1.0](https://openid.net/specs/openid-connect-backchannel-1_0.html)
+
+use database::driver::Database;
+use database::jws::JwsError;
+use database::oauth2_client::{create_logout_token, OAuth2Client};
+use database::signing_key::{JwtSigningAlgorithm, SigningKey};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// How long to wait for a client to respond to a logout request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum BackchannelLogoutError {
+    #[error("{0}")]
+    Database(#[from] database::driver::Error),
+    #[error("No active signing key for {0:?}")]
+    NoSigningKey(JwtSigningAlgorithm),
+    #[error("Failed to create logout token: {0}")]
+    Jws(#[from] JwsError),
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Send a logout token to every client in the background.
+/// Logout tokens are signed with the client's `id_token_signed_response_alg`.
+/// Failures are logged, but do not prevent the logout.
+pub fn notify(database: Database, issuer: String, clients: Vec<OAuth2Client>, user_id: String) {
+    tokio::spawn(async move {
+        for client in clients {
+            if let Err(e) = notify_client(&database, &issuer, &client, &user_id).await {
+                warn!(
+                    "Failed to send logout token to client {}: {e}",
+                    client.client_id
+                );
+            }
+        }
+    });
+}
+
+async fn notify_client(
+    database: &Database,
+    issuer: &str,
+    client: &OAuth2Client,
+    user_id: &str,
+) -> Result<(), BackchannelLogoutError> {
+    let Some(uri) = &client.backchannel_logout_uri else {
+        return Ok(());
+    };
+
+    let algorithm = client.id_token_signed_response_alg;
+    let signing_key = SigningKey::get_active(database, algorithm)
+        .await?
+        .ok_or(BackchannelLogoutError::NoSigningKey(algorithm))?;
+
+    let logout_token = create_logout_token(issuer.to_string(), client, &signing_key, user_id)?;
+
+    reqwest::Client::new()
+        .post(uri)
+        .timeout(REQUEST_TIMEOUT)
+        .form(&[("logout_token", logout_token)])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    info!("Sent logout token to client {}", client.client_id);
+    Ok(())
+}
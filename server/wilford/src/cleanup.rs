@@ -0,0 +1,75 @@
+use crate::config::Config;
+use database::driver::Database;
+use database::login_failure::LoginFailures;
+use database::oauth2_client::{
+    AccessToken, OAuth2AuthorizationCode, OAuth2Client, OAuth2PendingAuthorization, RefreshToken,
+};
+use database::passkey::WebauthnChallenge;
+use database::password_reset::PasswordResetToken;
+use database::session::UserSession;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Periodically purge expired pending authorizations, authorization codes, tokens, sessions,
+/// client assertion records, WebAuthn challenges and failed login attempts.
+/// Runs until the process exits.
+async fn run(
+    database: Database,
+    pending_authorization_max_age: i64,
+    refresh_token_idle_lifetime: i64,
+    failure_window: i64,
+    interval: u64,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = purge_expired(
+            &database,
+            pending_authorization_max_age,
+            refresh_token_idle_lifetime,
+            failure_window,
+        )
+        .await
+        {
+            warn!("Failed to purge expired rows: {e}");
+        }
+    }
+}
+
+/// Spawn the cleanup task in the background.
+pub fn spawn(database: Database, config: &Config) {
+    tokio::spawn(run(
+        database,
+        config.cleanup.pending_authorization_max_age,
+        config.refresh_token.idle_lifetime,
+        config.lockout.failure_window,
+        config.cleanup.interval,
+    ));
+}
+
+async fn purge_expired(
+    database: &Database,
+    pending_authorization_max_age: i64,
+    refresh_token_idle_lifetime: i64,
+    failure_window: i64,
+) -> Result<(), database::driver::Error> {
+    let pending_authorizations =
+        OAuth2PendingAuthorization::delete_expired(database, pending_authorization_max_age).await?;
+    let authorization_codes = OAuth2AuthorizationCode::delete_expired(database).await?;
+    let access_tokens = AccessToken::delete_expired(database).await?;
+    let refresh_tokens =
+        RefreshToken::delete_expired(database, refresh_token_idle_lifetime).await?;
+    let sessions = UserSession::delete_expired(database).await?;
+    let client_assertions = OAuth2Client::delete_expired_assertions(database).await?;
+    let webauthn_challenges = WebauthnChallenge::delete_expired(database).await?;
+    let login_failures = LoginFailures::delete_expired(database, failure_window).await?;
+    let password_resets = PasswordResetToken::delete_expired(database).await?;
+
+    debug!(
+        "Purged {pending_authorizations} pending authorizations, {authorization_codes} authorization codes, {access_tokens} access tokens, {refresh_tokens} refresh tokens, {sessions} sessions, {client_assertions} client assertions, {webauthn_challenges} WebAuthn challenges, {login_failures} failed login attempts and {password_resets} password reset tokens"
+    );
+
+    Ok(())
+}
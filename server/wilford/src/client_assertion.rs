@@ -0,0 +1,364 @@
+//! Verification of client assertions, used by clients authenticating with the `private_key_jwt` method.
+//!
+//! The client signs a short-lived JWT with one of the keys in the JWK Set registered for it.
+//! Every assertion can be used only once, the caller is responsible for recording its `jti`.
+//!
+//! # Further reading
+//! - [RFC 7523, Section 2.2 and 3](https://datatracker.ietf.org/doc/html/rfc7523#section-2.2)
+//! - [OpenID Connect Core 1.0, Section 9](https://openid.net/specs/openid-connect-core-1_0.html#ClientAuthentication)
+
+use actix_web::cookie::time::OffsetDateTime;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use database::signing_key::JwtSigningAlgorithm;
+use jwt_simple::algorithms::{
+    ECDSAP256PublicKeyLike, ES256PublicKey, Ed25519PublicKey, EdDSAPublicKeyLike, RS256PublicKey,
+    RSAPublicKeyLike,
+};
+use jwt_simple::claims::{JWTClaims, NoCustomClaims};
+use jwt_simple::common::VerificationOptions;
+use jwt_simple::prelude::Duration;
+use jwt_simple::token::Token;
+use serde::Deserialize;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// The value of the `client_assertion_type` parameter for JWT client assertions
+pub const ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// The maximum time an assertion may be valid for, in seconds.
+/// Used assertions are recorded until they expire, this bounds how long that is.
+const MAX_LIFETIME: i64 = 60 * 60;
+
+/// The allowed clock skew between the client and us, in seconds
+const TIME_TOLERANCE: i64 = 60;
+
+#[derive(Debug, Error)]
+pub enum ClientAssertionError {
+    #[error("Invalid JWK Set: {0}")]
+    Jwks(#[from] serde_json::Error),
+    #[error("Invalid key in JWK Set: {0}")]
+    Key(String),
+    #[error("The JWK Set contains no keys")]
+    EmptyJwks,
+    #[error("No key in the JWK Set matches the assertion")]
+    NoMatchingKey,
+    #[error("Invalid assertion: {0}")]
+    Invalid(jwt_simple::Error),
+    #[error("The assertion has no `exp` or `jti` claim")]
+    MissingClaim,
+    #[error("The assertion is valid for too long")]
+    TooLongLived,
+}
+
+/// A verified client assertion
+#[derive(Debug)]
+pub struct ClientAssertion {
+    /// The unique identifier of the assertion
+    pub jti: String,
+    /// The time until which the assertion must be remembered to prevent replay, as UNIX timestamp
+    pub remember_until: i64,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    use_: Option<String>,
+    #[serde(flatten)]
+    params: JwkParams,
+}
+
+/// The public key members of a JWK, see [crate::signing_keys::PublicKeyParams]
+#[derive(Deserialize)]
+#[serde(tag = "kty")]
+enum JwkParams {
+    #[serde(rename = "RSA")]
+    Rsa { n: String, e: String },
+    #[serde(rename = "EC")]
+    Ec { crv: String, x: String, y: String },
+    #[serde(rename = "OKP")]
+    Okp { crv: String, x: String },
+}
+
+enum PublicKey {
+    Rs256(Box<RS256PublicKey>),
+    Es256(ES256PublicKey),
+    EdDSA(Ed25519PublicKey),
+}
+
+impl Jwk {
+    /// Whether the key may be used to verify a token signed with the algorithm
+    fn usable_for(&self, algorithm: &str, kid: Option<&str>) -> bool {
+        let algorithm_matches = self.algorithm().name() == algorithm
+            && self.alg.as_deref().is_none_or(|alg| alg == algorithm);
+        let kid_matches = kid.is_none_or(|kid| self.kid.as_deref() == Some(kid));
+        let for_signing = self.use_.as_deref().is_none_or(|u| u == "sig");
+
+        algorithm_matches && kid_matches && for_signing
+    }
+
+    fn algorithm(&self) -> JwtSigningAlgorithm {
+        match self.params {
+            JwkParams::Rsa { .. } => JwtSigningAlgorithm::RS256,
+            JwkParams::Ec { .. } => JwtSigningAlgorithm::ES256,
+            JwkParams::Okp { .. } => JwtSigningAlgorithm::EdDSA,
+        }
+    }
+
+    fn public_key(&self) -> Result<PublicKey, ClientAssertionError> {
+        Ok(match &self.params {
+            JwkParams::Rsa { n, e } => PublicKey::Rs256(
+                RS256PublicKey::from_components(&decode(n)?, &decode(e)?)
+                    .map(Box::new)
+                    .map_err(key_error)?,
+            ),
+            JwkParams::Ec { crv, x, y } => {
+                if crv != "P-256" {
+                    return Err(ClientAssertionError::Key(format!(
+                        "Unsupported curve {crv}"
+                    )));
+                }
+
+                // SEC1 uncompressed point: 0x04 || x || y
+                let mut point = vec![0x04];
+                point.extend(decode(x)?);
+                point.extend(decode(y)?);
+
+                PublicKey::Es256(ES256PublicKey::from_bytes(&point).map_err(key_error)?)
+            }
+            JwkParams::Okp { crv, x } => {
+                if crv != "Ed25519" {
+                    return Err(ClientAssertionError::Key(format!(
+                        "Unsupported curve {crv}"
+                    )));
+                }
+
+                PublicKey::EdDSA(Ed25519PublicKey::from_bytes(&decode(x)?).map_err(key_error)?)
+            }
+        })
+    }
+}
+
+impl PublicKey {
+    fn verify_token(
+        &self,
+        token: &str,
+        options: VerificationOptions,
+    ) -> Result<JWTClaims<NoCustomClaims>, jwt_simple::Error> {
+        match self {
+            Self::Rs256(key) => key.verify_token(token, Some(options)),
+            Self::Es256(key) => key.verify_token(token, Some(options)),
+            Self::EdDSA(key) => key.verify_token(token, Some(options)),
+        }
+    }
+}
+
+fn parse_jwks(jwks: &str) -> Result<Vec<Jwk>, ClientAssertionError> {
+    let jwks: Jwks = serde_json::from_str(jwks)?;
+    if jwks.keys.is_empty() {
+        return Err(ClientAssertionError::EmptyJwks);
+    }
+
+    Ok(jwks.keys)
+}
+
+/// Check that a JWK Set can be used to verify client assertions.
+/// Only RSA, P-256 and Ed25519 keys are supported.
+///
+/// # Errors
+///
+/// If the JWK Set cannot be parsed, is empty or contains an unsupported key
+pub fn validate_jwks(jwks: &str) -> Result<(), ClientAssertionError> {
+    for key in parse_jwks(jwks)? {
+        key.public_key()?;
+    }
+
+    Ok(())
+}
+
+/// Verify a client assertion, signed with a key from the client's JWK Set.
+/// The `iss` and `sub` claims must be the client ID, and the `aud` claim one of the `audiences`.
+///
+/// # Errors
+///
+/// If the JWK Set is invalid, no key matches the assertion or the assertion is not valid
+pub fn verify(
+    jwks: &str,
+    assertion: &str,
+    client_id: &str,
+    audiences: HashSet<String>,
+) -> Result<ClientAssertion, ClientAssertionError> {
+    let metadata = Token::decode_metadata(assertion).map_err(ClientAssertionError::Invalid)?;
+
+    let keys = parse_jwks(jwks)?
+        .into_iter()
+        .filter(|key| key.usable_for(metadata.algorithm(), metadata.key_id()))
+        .collect::<Vec<_>>();
+
+    let options = VerificationOptions {
+        allowed_issuers: Some(HashSet::from([client_id.to_string()])),
+        required_subject: Some(client_id.to_string()),
+        allowed_audiences: Some(audiences),
+        time_tolerance: Some(Duration::from_secs(TIME_TOLERANCE as u64)),
+        ..Default::default()
+    };
+
+    // Without a `kid`, multiple keys may match
+    let mut result = Err(ClientAssertionError::NoMatchingKey);
+    for key in keys {
+        result = key
+            .public_key()?
+            .verify_token(assertion, options.clone())
+            .map_err(ClientAssertionError::Invalid);
+
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    let claims = result?;
+    let (Some(expires_at), Some(jti)) = (claims.expires_at, claims.jwt_id) else {
+        return Err(ClientAssertionError::MissingClaim);
+    };
+
+    let expires_at = expires_at.as_secs() as i64;
+    if expires_at > OffsetDateTime::now_utc().unix_timestamp() + MAX_LIFETIME {
+        return Err(ClientAssertionError::TooLongLived);
+    }
+
+    Ok(ClientAssertion {
+        jti,
+        remember_until: expires_at + TIME_TOLERANCE,
+    })
+}
+
+/// Get the issuer of an assertion without verifying it, to find the client it claims to be from.
+/// The assertion must still be verified.
+pub fn unverified_issuer(assertion: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Claims {
+        iss: String,
+    }
+
+    let payload = assertion.split('.').nth(1)?;
+    let claims: Claims =
+        serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+    Some(claims.iss)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, ClientAssertionError> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|e| ClientAssertionError::Key(e.to_string()))
+}
+
+fn key_error(e: jwt_simple::Error) -> ClientAssertionError {
+    ClientAssertionError::Key(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signing_keys::PublicKeyParams;
+    use jwt_simple::algorithms::{
+        ECDSAP256KeyPairLike, ES256KeyPair, Ed25519KeyPair, EdDSAKeyPairLike,
+    };
+    use jwt_simple::claims::Claims;
+
+    const CLIENT_ID: &str = "client";
+    const AUDIENCE: &str = "https://wilford.example.com/api/oauth/token";
+
+    fn jwks(params: PublicKeyParams, kid: &str) -> String {
+        let mut key = serde_json::to_value(params).unwrap();
+        key["kid"] = kid.into();
+
+        serde_json::json!({ "keys": [key] }).to_string()
+    }
+
+    fn claims() -> JWTClaims<NoCustomClaims> {
+        Claims::create(Duration::from_mins(5))
+            .with_issuer(CLIENT_ID)
+            .with_subject(CLIENT_ID)
+            .with_audience(AUDIENCE)
+            .with_jwt_id("jti")
+    }
+
+    fn audiences() -> HashSet<String> {
+        HashSet::from([AUDIENCE.to_string()])
+    }
+
+    #[test]
+    fn es256_assertion() {
+        let key = ES256KeyPair::generate().with_key_id("key");
+        let jwks = jwks(
+            PublicKeyParams::from_pem(
+                JwtSigningAlgorithm::ES256,
+                &key.public_key().to_pem().unwrap(),
+            )
+            .unwrap(),
+            "key",
+        );
+
+        validate_jwks(&jwks).unwrap();
+
+        let assertion = key.sign(claims()).unwrap();
+        let verified = verify(&jwks, &assertion, CLIENT_ID, audiences()).unwrap();
+        assert_eq!(verified.jti, "jti");
+
+        // Another client cannot use the assertion
+        assert!(verify(&jwks, &assertion, "other", audiences()).is_err());
+    }
+
+    #[test]
+    fn eddsa_assertion() {
+        let key = Ed25519KeyPair::generate();
+        let jwks = jwks(
+            PublicKeyParams::from_pem(JwtSigningAlgorithm::EdDSA, &key.public_key().to_pem())
+                .unwrap(),
+            "key",
+        );
+
+        // Without a `kid`, the key is found by its algorithm
+        let assertion = key.sign(claims()).unwrap();
+        assert!(verify(&jwks, &assertion, CLIENT_ID, audiences()).is_ok());
+
+        let other_audience = HashSet::from(["https://other.example.com".to_string()]);
+        assert!(verify(&jwks, &assertion, CLIENT_ID, other_audience).is_err());
+    }
+
+    #[test]
+    fn missing_jti() {
+        let key = Ed25519KeyPair::generate();
+        let jwks = jwks(
+            PublicKeyParams::from_pem(JwtSigningAlgorithm::EdDSA, &key.public_key().to_pem())
+                .unwrap(),
+            "key",
+        );
+
+        let claims = Claims::create(Duration::from_mins(5))
+            .with_issuer(CLIENT_ID)
+            .with_subject(CLIENT_ID)
+            .with_audience(AUDIENCE);
+        let assertion = key.sign(claims).unwrap();
+
+        assert!(matches!(
+            verify(&jwks, &assertion, CLIENT_ID, audiences()),
+            Err(ClientAssertionError::MissingClaim)
+        ));
+    }
+
+    #[test]
+    fn invalid_jwks() {
+        assert!(validate_jwks(r#"{"keys":[]}"#).is_err());
+        assert!(validate_jwks(r#"{"keys":[{"kty":"oct","k":"c2VjcmV0"}]}"#).is_err());
+        assert!(validate_jwks(r#"{"keys":[{"kty":"OKP","crv":"X25519","x":"AAAA"}]}"#).is_err());
+    }
+}
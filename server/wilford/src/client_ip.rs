@@ -0,0 +1,93 @@
+//! Determining the IP address of the client, to count attempts and requests per client.
+//!
+//! Wilford is usually deployed behind a reverse proxy, in which case the peer of the connection
+//! is the proxy. The proxy adds the client's address to the `X-Forwarded-For` header.
+//! As clients can set the header themselves, it is only used if the peer is a trusted proxy.
+
+use actix_web::http::header::X_FORWARDED_FOR;
+use actix_web::HttpRequest;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// The IP address of the client.
+/// Returns `None` if the peer address is not known.
+pub fn client_ip(req: &HttpRequest, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip().to_canonical();
+
+    // Multiple headers are equivalent to a single comma-separated header
+    let forwarded_for = req
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Some(resolve(peer, &forwarded_for, trusted_proxies))
+}
+
+/// Walk the `X-Forwarded-For` header from the right, as every proxy appends the address it received
+/// the request from. The first address which is not a trusted proxy is the client.
+/// Addresses to the left of it could have been set by the client, and are not used.
+fn resolve(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        if !is_trusted(&client) {
+            break;
+        }
+
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => client = ip.to_canonical(),
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn proxies() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        // The header is ignored if the peer is not a proxy
+        assert_eq!(
+            resolve(ip("203.0.113.5"), "198.51.100.1", &proxies()),
+            ip("203.0.113.5")
+        );
+        assert_eq!(resolve(ip("10.0.0.2"), "198.51.100.1", &[]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn trusted_proxies() {
+        assert_eq!(
+            resolve(ip("10.0.0.2"), "198.51.100.1", &proxies()),
+            ip("198.51.100.1")
+        );
+
+        // Through two proxies, the client spoofed the first address
+        assert_eq!(
+            resolve(ip("::1"), "192.0.2.1, 198.51.100.1, 10.0.0.3", &proxies()),
+            ip("198.51.100.1")
+        );
+
+        // The proxy did not set the header
+        assert_eq!(resolve(ip("10.0.0.2"), "", &proxies()), ip("10.0.0.2"));
+
+        // Malformed header
+        assert_eq!(
+            resolve(ip("10.0.0.2"), "unknown", &proxies()),
+            ip("10.0.0.2")
+        );
+    }
+}
@@ -1,4 +1,8 @@
+use crate::signing_keys;
+use color_eyre::eyre::bail;
 use color_eyre::Result;
+use database::oauth2_client::ACCESS_TOKEN_LIFETIME;
+use ipnet::IpNet;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -27,18 +31,55 @@ pub struct Config {
     /// This client is used by Wilford itself.
     pub default_client: DefaultClientConfig,
     /// The path to the private key used to sign OIDC ID tokens.
-    /// Should be the matching private key of [oidc_public_key]
+    /// Should be the matching private key of [oidc_public_key].
+    /// Imported as the first signing key, later keys are generated by key rotation.
     pub oidc_signing_key: PathBuf,
     /// The path to the public key used to sign OIDC ID tokens.
     /// Should be the matching public key of [oidc_signing_key]
     pub oidc_public_key: PathBuf,
     /// The issuer of OpenID Connect ID tokens.
-    /// E.g. `mrfriendly.nl`.
+    /// This is the URL at which Wilford's server can be reached, e.g. `https://auth.mrfriendly.nl`.
+    /// The URLs of the endpoints in the OpenID configuration are derived from it,
+    /// unless they are set explicitly in [HttpConfig].
     pub oidc_issuer: String,
     /// Email configuration.
     /// If this is not set, no emails will be sent,
     /// useful for debugging
     pub email: Option<EmailConfig>,
+    /// Lifetimes of OAuth2 refresh tokens.
+    #[serde(default)]
+    pub refresh_token: RefreshTokenConfig,
+    /// Expiry and cleanup of pending authorizations, authorization codes and tokens.
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+    /// Rotation of the keys used to sign OIDC ID tokens.
+    #[serde(default)]
+    pub signing_keys: SigningKeysConfig,
+    /// Login sessions, shared by all clients.
+    #[serde(default)]
+    pub session: SessionConfig,
+    /// The relying party passkeys are registered for.
+    #[serde(default)]
+    pub webauthn: WebauthnConfig,
+    /// Protection against guessing passwords
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+    /// Rate limits of the endpoints which can be called without authentication
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenConfig {
+    /// The maximum lifetime of a refresh token in seconds, counted from the original authorization.
+    /// Rotating the refresh token does not extend this lifetime.
+    /// Defaults to 30 days.
+    #[serde(default = "default_refresh_token_absolute_lifetime")]
+    pub absolute_lifetime: i64,
+    /// The maximum time in seconds a refresh token may go unused before it expires.
+    /// Defaults to 7 days.
+    #[serde(default = "default_refresh_token_idle_lifetime")]
+    pub idle_lifetime: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,15 +111,52 @@ pub struct HttpConfig {
     /// The URL at which the frontend's email verification page can be found.
     /// The server will append a `code` and a `user_id` query parameter to the URL
     pub ui_email_verification_path: String,
+    /// The URL at which the frontend's password reset page can be found.
+    /// The server will append a `token` query parameter to the URL.
+    /// Derived from `ui_login_path` if not set, as the page `reset-password` next to the login page.
+    pub ui_password_reset_path: Option<String>,
+    /// The URL at which the frontend's logout confirmation page can be found.
+    /// The server will append the parameters of the logout request to the URL.
+    /// Derived from `ui_login_path` if not set, as the page `logout` next to the login page.
+    pub ui_logout_path: Option<String>,
     /// The URL at which the OAuth2 authorization endpoint can be found.
-    /// Should point to the route `/oauth/authorize`.
-    pub authorization_endpoint: String,
+    /// Should point to the route `/api/oauth/authorize`.
+    /// Derived from `oidc_issuer` if not set.
+    pub authorization_endpoint: Option<String>,
     /// The URL at which the OAuth2 token endpoint can be found.
-    /// Should point to the route `/oauth/token`.
-    pub token_endpoint: String,
+    /// Should point to the route `/api/oauth/token`.
+    /// Derived from `oidc_issuer` if not set.
+    pub token_endpoint: Option<String>,
     /// The URL at which the JWKS document can be found.
     /// Should point to the route `/.well-known/jwks.json`.
-    pub jwks_uri_endpoint: String,
+    /// Derived from `oidc_issuer` if not set.
+    pub jwks_uri_endpoint: Option<String>,
+    /// The URL at which the OAuth2 token revocation endpoint can be found.
+    /// Should point to the route `/api/oauth/revoke`.
+    /// Derived from `oidc_issuer` if not set.
+    pub revocation_endpoint: Option<String>,
+    /// The URL at which the OAuth2 token introspection endpoint can be found.
+    /// Should point to the route `/api/oauth/introspect`.
+    /// Derived from `oidc_issuer` if not set.
+    pub introspection_endpoint: Option<String>,
+    /// The URL at which the OpenID Connect UserInfo endpoint can be found.
+    /// Should point to the route `/api/oauth/userinfo`.
+    /// Derived from `oidc_issuer` if not set.
+    pub userinfo_endpoint: Option<String>,
+    /// The URL at which the OpenID Connect end session endpoint can be found.
+    /// Should point to the route `/api/oauth/logout`.
+    /// Derived from `oidc_issuer` if not set.
+    pub end_session_endpoint: Option<String>,
+    /// The URL at which the OAuth2 dynamic client registration endpoint can be found.
+    /// Should point to the route `/api/oauth/register`.
+    /// Derived from `oidc_issuer` if not set.
+    pub registration_endpoint: Option<String>,
+    /// The IP addresses or networks of reverse proxies, e.g. `10.0.0.0/8`.
+    /// The client's IP address is taken from the `X-Forwarded-For` header
+    /// only if the request was forwarded by one of these proxies.
+    /// Defaults to none, the `X-Forwarded-For` header is ignored.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -110,8 +188,270 @@ pub struct DefaultClientConfig {
     /// Should be the location at which the frontend's `login-ok` page can be found.
     pub redirect_uri: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct CleanupConfig {
+    /// The maximum age of a pending authorization in seconds.
+    /// The user must log in and grant the authorization within this time.
+    /// Defaults to 15 minutes.
+    #[serde(default = "default_pending_authorization_max_age")]
+    pub pending_authorization_max_age: i64,
+    /// The interval in seconds at which expired pending authorizations,
+    /// authorization codes and tokens are purged from the database.
+    /// Must be greater than 0. Defaults to 5 minutes.
+    #[serde(default = "default_cleanup_interval")]
+    pub interval: u64,
+}
+#[derive(Debug, Deserialize)]
+pub struct SigningKeysConfig {
+    /// The time in seconds after which the active signing key is replaced by the next key.
+    /// Must be at least one hour, as rotation is only checked hourly. Defaults to 90 days.
+    #[serde(default = "default_signing_key_rotation_interval")]
+    pub rotation_interval: i64,
+    /// The time in seconds a retired key remains published, so ID tokens signed with it can still be verified.
+    /// Must be at least the lifetime of ID tokens and JWT access tokens, which is one hour.
+    /// Defaults to 1 day.
+    #[serde(default = "default_retired_signing_key_lifetime")]
+    pub retired_key_lifetime: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionConfig {
+    /// The time in seconds a login session is valid for.
+    /// Within this time, the user does not have to log in again to authorize a client.
+    /// Using the session does not extend it.
+    /// Defaults to 7 days.
+    #[serde(default = "default_session_lifetime")]
+    pub lifetime: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnConfig {
+    /// The relying party ID passkeys are scoped to, e.g. `mrfriendly.nl`.
+    /// Must be the host of the frontend, or a registrable suffix of it.
+    /// Changing it invalidates all registered passkeys.
+    /// Derived from the host of `http.ui_login_path` if not set.
+    pub rp_id: Option<String>,
+    /// The name shown to users when registering a passkey.
+    /// Defaults to `Wilford`.
+    #[serde(default = "default_webauthn_rp_name")]
+    pub rp_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LockoutConfig {
+    /// The number of failed attempts after which every further attempt is delayed.
+    /// The delay doubles with every failed attempt, starting at 1 second.
+    /// Defaults to 3.
+    #[serde(default = "default_lockout_delay_after")]
+    pub delay_after: u32,
+    /// The maximum delay in seconds between failed attempts.
+    /// Defaults to 30 seconds.
+    #[serde(default = "default_lockout_max_delay")]
+    pub max_delay: i64,
+    /// The number of failed attempts for an account after which it is locked.
+    /// The user is notified by email when their account is locked.
+    /// Defaults to 10.
+    #[serde(default = "default_lockout_account_threshold")]
+    pub account_threshold: u32,
+    /// The number of failed attempts from an IP address after which it is locked.
+    /// Defaults to 50.
+    #[serde(default = "default_lockout_ip_threshold")]
+    pub ip_threshold: u32,
+    /// The time in seconds an account or IP address is locked for.
+    /// Admins can unlock accounts before then.
+    /// Defaults to 15 minutes.
+    #[serde(default = "default_lockout_duration")]
+    pub duration: i64,
+    /// The time in seconds after which failed attempts are forgotten.
+    /// Defaults to 15 minutes.
+    #[serde(default = "default_lockout_failure_window")]
+    pub failure_window: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimitConfig {
+    /// Logging in, per IP address.
+    /// Defaults to 10 requests per minute.
+    #[serde(default = "default_login_rate_limit")]
+    pub login: RateLimit,
+    /// Registering and requesting a new password, which send emails, per IP address.
+    /// Defaults to 10 requests per hour.
+    #[serde(default = "default_email_rate_limit")]
+    pub email: RateLimit,
+    /// Registering and requesting a new password, per email address the email is sent to.
+    /// Defaults to 3 requests per hour.
+    #[serde(default = "default_email_address_rate_limit")]
+    pub email_address: RateLimit,
+    /// The endpoints OAuth2 clients call, e.g. the token endpoint, per IP address.
+    /// Defaults to 60 requests per minute.
+    #[serde(default = "default_client_rate_limit")]
+    pub client: RateLimit,
+}
+
+/// A token bucket: at most `requests` requests can be made at once,
+/// after which requests are allowed again at a rate of `requests` per `period`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimit {
+    /// The number of requests. Requests are not limited if 0.
+    pub requests: u32,
+    /// The period in seconds
+    pub period: u64,
+}
 /* ANCHOR_END: config */
 
+impl Default for RefreshTokenConfig {
+    fn default() -> Self {
+        Self {
+            absolute_lifetime: default_refresh_token_absolute_lifetime(),
+            idle_lifetime: default_refresh_token_idle_lifetime(),
+        }
+    }
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            pending_authorization_max_age: default_pending_authorization_max_age(),
+            interval: default_cleanup_interval(),
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: default_session_lifetime(),
+        }
+    }
+}
+
+impl Default for WebauthnConfig {
+    fn default() -> Self {
+        Self {
+            rp_id: None,
+            rp_name: default_webauthn_rp_name(),
+        }
+    }
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            delay_after: default_lockout_delay_after(),
+            max_delay: default_lockout_max_delay(),
+            account_threshold: default_lockout_account_threshold(),
+            ip_threshold: default_lockout_ip_threshold(),
+            duration: default_lockout_duration(),
+            failure_window: default_lockout_failure_window(),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            login: default_login_rate_limit(),
+            email: default_email_rate_limit(),
+            email_address: default_email_address_rate_limit(),
+            client: default_client_rate_limit(),
+        }
+    }
+}
+
+impl Default for SigningKeysConfig {
+    fn default() -> Self {
+        Self {
+            rotation_interval: default_signing_key_rotation_interval(),
+            retired_key_lifetime: default_retired_signing_key_lifetime(),
+        }
+    }
+}
+
+fn default_pending_authorization_max_age() -> i64 {
+    15 * 60
+}
+
+fn default_cleanup_interval() -> u64 {
+    5 * 60
+}
+
+fn default_refresh_token_absolute_lifetime() -> i64 {
+    30 * 24 * 60 * 60
+}
+
+fn default_refresh_token_idle_lifetime() -> i64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_signing_key_rotation_interval() -> i64 {
+    90 * 24 * 60 * 60
+}
+
+fn default_retired_signing_key_lifetime() -> i64 {
+    24 * 60 * 60
+}
+
+fn default_session_lifetime() -> i64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_webauthn_rp_name() -> String {
+    "Wilford".to_string()
+}
+
+fn default_lockout_delay_after() -> u32 {
+    3
+}
+
+fn default_lockout_max_delay() -> i64 {
+    30
+}
+
+fn default_lockout_account_threshold() -> u32 {
+    10
+}
+
+fn default_lockout_ip_threshold() -> u32 {
+    50
+}
+
+fn default_lockout_duration() -> i64 {
+    15 * 60
+}
+
+fn default_lockout_failure_window() -> i64 {
+    15 * 60
+}
+
+fn default_login_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 10,
+        period: 60,
+    }
+}
+
+fn default_email_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 10,
+        period: 60 * 60,
+    }
+}
+
+fn default_email_address_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 3,
+        period: 60 * 60,
+    }
+}
+
+fn default_client_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 60,
+        period: 60,
+    }
+}
+
 impl EnvConfig {
     fn new() -> Result<Self> {
         Ok(envy::from_env()?)
@@ -129,6 +469,65 @@ impl Config {
         Ok(serde_json::from_slice(&buf)?)
     }
 
+    /// Reject settings which cannot work, so Wilford fails at startup rather than while running.
+    ///
+    /// # Errors
+    ///
+    /// If a setting is invalid
+    fn validate(&self) -> Result<()> {
+        if self.cleanup.interval == 0 {
+            bail!("cleanup.interval must be greater than 0");
+        }
+
+        // Rotation is only checked periodically, a shorter interval would not be honoured
+        let check_interval = signing_keys::CHECK_INTERVAL.as_secs() as i64;
+        if self.signing_keys.rotation_interval < check_interval {
+            bail!(
+                "signing_keys.rotation_interval must be at least the rotation check interval of {check_interval} seconds"
+            );
+        }
+
+        // Otherwise a key would no longer be published while tokens signed with it are valid
+        if self.signing_keys.retired_key_lifetime < ACCESS_TOKEN_LIFETIME {
+            bail!(
+                "signing_keys.retired_key_lifetime must be at least the token lifetime of {ACCESS_TOKEN_LIFETIME} seconds"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Get the URL of an endpoint.
+    /// If no URL is configured, it is derived from `oidc_issuer` and the endpoint's `path`.
+    pub fn endpoint_url(&self, configured: &Option<String>, path: &str) -> String {
+        configured
+            .clone()
+            .unwrap_or_else(|| format!("{}{path}", self.oidc_issuer.trim_end_matches('/')))
+    }
+
+    /// Get the URL of a page of the frontend.
+    /// If no URL is configured, the page `name` next to the login page is used.
+    fn ui_page_url(&self, configured: &Option<String>, name: &str) -> String {
+        configured.clone().unwrap_or_else(|| {
+            let base = self
+                .http
+                .ui_login_path
+                .rsplit_once('/')
+                .map_or(self.http.ui_login_path.as_str(), |(base, _)| base);
+            format!("{base}/{name}")
+        })
+    }
+
+    /// Get the URL of the frontend's password reset page.
+    pub fn password_reset_page(&self) -> String {
+        self.ui_page_url(&self.http.ui_password_reset_path, "reset-password")
+    }
+
+    /// Get the URL of the frontend's logout confirmation page.
+    pub fn logout_page(&self) -> String {
+        self.ui_page_url(&self.http.ui_logout_path, "logout")
+    }
+
     pub async fn read_oidc_signing_key(&self) -> Result<String> {
         let absolute = self.oidc_signing_key.canonicalize()?;
         trace!("Reading OIDC signing key from {absolute:?}");
@@ -158,5 +557,6 @@ impl Config {
 pub async fn get_config() -> Result<Config> {
     let env = EnvConfig::new()?;
     let config = Config::open(&env.config_path).await?;
+    config.validate()?;
     Ok(config)
 }
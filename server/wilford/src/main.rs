@@ -1,11 +1,11 @@
 use crate::config::{get_config, DefaultClientConfig};
-use crate::routes::{OidcPublicKey, OidcSigningKey, WOidcPublicKey, WOidcSigningKey};
+use crate::rate_limit::RateLimiter;
 use actix_cors::Cors;
 use actix_route_config::Routable;
 use actix_web::{web, App, HttpServer};
 use color_eyre::Result;
 use database::driver::Database;
-use database::oauth2_client::OAuth2Client;
+use database::oauth2_client::{OAuth2Client, OAuth2ClientOptions};
 use noiseless_tracing_actix_web::NoiselessRootSpanBuilder;
 use tracing::info;
 use tracing_actix_web::TracingLogger;
@@ -15,12 +15,21 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
+mod access_tokens;
 mod authorization;
+mod backchannel_logout;
+mod cleanup;
+mod client_assertion;
+mod client_ip;
 mod config;
 mod espo;
 mod mail;
+mod rate_limit;
 mod response_types;
 mod routes;
+mod signing_keys;
+mod totp;
+mod webauthn;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -38,13 +47,14 @@ async fn main() -> Result<()> {
     .await?;
 
     ensure_internal_oauth_client_exists(&database, &config.default_client).await?;
+    cleanup::spawn(database.clone(), &config);
+    signing_keys::init(&database, &config).await?;
+    signing_keys::spawn(database.clone(), &config);
 
     let w_database = web::Data::new(database);
-    let w_oidc_signing_key =
-        WOidcSigningKey::new(OidcSigningKey(config.read_oidc_signing_key().await?));
-    let w_oidc_public_key =
-        WOidcPublicKey::new(OidcPublicKey(config.read_oidc_public_key().await?));
     let w_config = web::Data::new(config);
+    // Shared by all workers, so limits apply to the instance as a whole
+    let w_rate_limiter = web::Data::new(RateLimiter::default());
 
     HttpServer::new(move || {
         App::new()
@@ -52,8 +62,7 @@ async fn main() -> Result<()> {
             .wrap(TracingLogger::<NoiselessRootSpanBuilder>::new())
             .app_data(w_database.clone())
             .app_data(w_config.clone())
-            .app_data(w_oidc_signing_key.clone())
-            .app_data(w_oidc_public_key.clone())
+            .app_data(w_rate_limiter.clone())
             .configure(routes::Router::configure)
     })
     .bind("0.0.0.0:2521")?
@@ -72,17 +81,18 @@ async fn ensure_internal_oauth_client_exists(
         return Ok(());
     }
 
-    let client = OAuth2Client::new(
+    let (client, client_secret) = OAuth2Client::new(
         driver,
         "Wilford".to_string(),
-        config.redirect_uri.clone(),
+        std::slice::from_ref(&config.redirect_uri),
         true,
+        OAuth2ClientOptions::default(),
     )
     .await?;
 
     info!("No internal OAuth2 exists yet. Created a new one. This client is for logging in with Wilford itself.");
     info!("Default client `client_id`: {}", client.client_id);
-    info!("Default client `client_secret`: {}", client.client_secret);
+    info!("Default client `client_secret`: {client_secret}");
 
     Ok(())
 }
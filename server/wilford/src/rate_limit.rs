@@ -0,0 +1,282 @@
+//! Token bucket rate limiting of the endpoints which can be called without authentication.
+//!
+//! Buckets are kept in memory, so limits apply per Wilford instance and are reset on restart.
+
+use crate::config::{RateLimit, RateLimitConfig};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The maximum number of buckets kept.
+/// Beyond it, the oldest buckets are removed, so memory use and the cost of a request stay bounded.
+const MAX_BUCKETS: usize = 100_000;
+
+/// The length of the IPv6 prefix requests are counted for.
+/// A single client usually has a whole /64 at its disposal.
+const IPV6_PREFIX_LEN: u32 = 64;
+
+/// Endpoints sharing a rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitGroup {
+    /// Logging in
+    Login,
+    /// Endpoints sending emails, e.g. registering
+    Email,
+    /// Endpoints called by OAuth2 clients, e.g. the token endpoint
+    Client,
+}
+
+impl RateLimitGroup {
+    /// The configured limit of the group
+    pub fn limit(self, config: &RateLimitConfig) -> &RateLimit {
+        match self {
+            Self::Login => &config.login,
+            Self::Email => &config.email,
+            Self::Client => &config.client,
+        }
+    }
+}
+
+/// What requests are counted for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// Requests from an IP address to a group of endpoints.
+    /// Construct with [RateLimitKey::ip], so IPv6 addresses are counted per prefix.
+    Ip(RateLimitGroup, IpAddr),
+    /// Requests sending an email to an email address
+    EmailAddress(String),
+}
+
+impl RateLimitKey {
+    /// Requests from an IP address to a group of endpoints.
+    /// IPv6 addresses are counted per /64 prefix, so a client cannot bypass the limit
+    /// by rotating through the addresses in its prefix.
+    pub fn ip(group: RateLimitGroup, ip: IpAddr) -> Self {
+        let ip = match ip.to_canonical() {
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX << (128 - IPV6_PREFIX_LEN);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+            ip => ip,
+        };
+
+        Self::Ip(group, ip)
+    }
+}
+
+/// The buckets of all clients.
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+    max_buckets: usize,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<RateLimitKey, TokenBucket>,
+    /// The keys in the order their buckets were created, to remove the oldest first
+    created: VecDeque<RateLimitKey>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::with_max_buckets(MAX_BUCKETS)
+    }
+}
+
+impl RateLimiter {
+    fn with_max_buckets(max_buckets: usize) -> Self {
+        Self {
+            buckets: Mutex::default(),
+            max_buckets,
+        }
+    }
+
+    /// Take a token from the bucket of `key` for a request.
+    /// If the bucket is empty, returns the number of seconds after which the request may be retried.
+    pub fn take(&self, key: RateLimitKey, limit: &RateLimit) -> Result<(), i64> {
+        if limit.requests == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut state = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let Buckets { buckets, created } = &mut *state;
+
+        if !buckets.contains_key(&key) {
+            while buckets.len() >= self.max_buckets {
+                let Some(oldest) = created.pop_front() else {
+                    break;
+                };
+                buckets.remove(&oldest);
+            }
+
+            created.push_back(key.clone());
+        }
+
+        buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::full(limit, now))
+            .take(limit, now)
+            .map_err(|retry_after| retry_after.as_secs_f64().ceil() as i64)
+    }
+
+    /// Take a token for a request sending an email to the email address.
+    /// The address is normalized, so writing it differently does not bypass the limit.
+    pub fn take_email_address(&self, address: &str, config: &RateLimitConfig) -> Result<(), i64> {
+        self.take(
+            RateLimitKey::EmailAddress(address.trim().to_lowercase()),
+            &config.email_address,
+        )
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.requests as f64,
+            updated_at: now,
+        }
+    }
+
+    /// Take a token. If the bucket is empty, returns the time until a token is available.
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        let capacity = limit.requests as f64;
+        // Tokens per second
+        let rate = capacity / limit.period.max(1) as f64;
+
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        requests: 3,
+        period: 60,
+    };
+
+    #[test]
+    fn burst_then_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full(&LIMIT, start);
+
+        for _ in 0..3 {
+            assert!(bucket.take(&LIMIT, start).is_ok());
+        }
+
+        // One token per 20 seconds
+        let retry_after = |result: Result<(), Duration>| result.unwrap_err().as_secs_f64().round();
+        assert_eq!(retry_after(bucket.take(&LIMIT, start)), 20.0);
+        assert_eq!(
+            retry_after(bucket.take(&LIMIT, start + Duration::from_secs(5))),
+            15.0
+        );
+        assert!(bucket.take(&LIMIT, start + Duration::from_secs(21)).is_ok());
+        assert!(bucket
+            .take(&LIMIT, start + Duration::from_secs(21))
+            .is_err());
+
+        // Never more than the capacity
+        let later = start + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert!(bucket.take(&LIMIT, later).is_ok());
+        }
+        assert!(bucket.take(&LIMIT, later).is_err());
+    }
+
+    #[test]
+    fn keys_are_independent() {
+        let limiter = RateLimiter::default();
+        let a = RateLimitKey::ip(RateLimitGroup::Login, "192.0.2.1".parse().unwrap());
+        let b = RateLimitKey::ip(RateLimitGroup::Email, "192.0.2.1".parse().unwrap());
+
+        for _ in 0..3 {
+            assert!(limiter.take(a.clone(), &LIMIT).is_ok());
+        }
+        assert!(limiter.take(a, &LIMIT).is_err());
+        assert!(limiter.take(b, &LIMIT).is_ok());
+    }
+
+    #[test]
+    fn ipv6_per_prefix() {
+        let key = |ip: &str| RateLimitKey::ip(RateLimitGroup::Login, ip.parse().unwrap());
+
+        assert_eq!(
+            key("2001:db8:1:2::1"),
+            key("2001:db8:1:2:ffff:ffff:ffff:ffff")
+        );
+        assert_ne!(key("2001:db8:1:2::1"), key("2001:db8:1:3::1"));
+        // IPv4-mapped addresses are counted as the IPv4 address
+        assert_eq!(key("::ffff:192.0.2.1"), key("192.0.2.1"));
+        assert_ne!(key("192.0.2.1"), key("192.0.2.2"));
+    }
+
+    #[test]
+    fn oldest_buckets_removed() {
+        let limiter = RateLimiter::with_max_buckets(2);
+        let key = |ip: &str| RateLimitKey::ip(RateLimitGroup::Login, ip.parse().unwrap());
+
+        for _ in 0..3 {
+            assert!(limiter.take(key("192.0.2.1"), &LIMIT).is_ok());
+        }
+        assert!(limiter.take(key("192.0.2.1"), &LIMIT).is_err());
+
+        // Creating buckets beyond the maximum removes the oldest
+        assert!(limiter.take(key("192.0.2.2"), &LIMIT).is_ok());
+        assert!(limiter.take(key("192.0.2.3"), &LIMIT).is_ok());
+
+        let state = limiter.buckets.lock().unwrap();
+        assert_eq!(state.buckets.len(), 2);
+        assert_eq!(state.created.len(), 2);
+        assert!(!state.buckets.contains_key(&key("192.0.2.1")));
+    }
+
+    #[test]
+    fn email_address_normalized() {
+        let limiter = RateLimiter::default();
+        let config = RateLimitConfig::default();
+
+        for _ in 0..config.email_address.requests {
+            assert!(limiter
+                .take_email_address("User@Example.com", &config)
+                .is_ok());
+        }
+        assert!(limiter
+            .take_email_address(" user@example.com", &config)
+            .is_err());
+    }
+
+    #[test]
+    fn unlimited() {
+        let limiter = RateLimiter::default();
+        let limit = RateLimit {
+            requests: 0,
+            period: 60,
+        };
+
+        for _ in 0..100 {
+            assert!(limiter
+                .take(RateLimitKey::EmailAddress(String::new()), &limit)
+                .is_ok());
+        }
+    }
+}
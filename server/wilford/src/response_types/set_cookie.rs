@@ -9,6 +9,10 @@ pub struct SetCookie<'c, I> {
     name: Cow<'c, str>,
     /// The cookie value
     value: Cow<'c, str>,
+    /// When the cookie expires
+    expires: OffsetDateTime,
+    /// Whether the cookie is hidden from scripts
+    http_only: bool,
     /// The inner responder
     responder: I,
 }
@@ -24,9 +28,24 @@ impl<'c, I> SetCookie<'c, I> {
         Self {
             name: name.into(),
             value: value.into(),
+            // Perhaps make this configurable?
+            expires: OffsetDateTime::now_utc() + Duration::days(30),
+            http_only: false,
             responder,
         }
     }
+
+    /// Expire the cookie at the provided time, instead of after 30 days.
+    pub fn expires(mut self, expires: OffsetDateTime) -> Self {
+        self.expires = expires;
+        self
+    }
+
+    /// Hide the cookie from scripts running in the browser.
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
 }
 
 impl<I> Responder for SetCookie<'_, I>
@@ -40,11 +59,8 @@ where
         let mut inner_response = self.responder.respond_to(req);
         let mut cookie = Cookie::new(self.name, self.value);
         cookie.set_secure(true);
-
-        // Perhaps make this configurable?
-        cookie.set_expires(Expiration::DateTime(
-            OffsetDateTime::now_utc() + Duration::days(30),
-        ));
+        cookie.set_http_only(self.http_only);
+        cookie.set_expires(Expiration::DateTime(self.expires));
 
         cookie.set_same_site(SameSite::None);
         cookie.set_path("/");
@@ -54,6 +70,46 @@ where
     }
 }
 
+/// Remove a cookie on response
+pub struct RemoveCookie<'c, I> {
+    /// The cookie name
+    name: Cow<'c, str>,
+    /// The inner responder
+    responder: I,
+}
+
+impl<'c, I> RemoveCookie<'c, I> {
+    /// Remove the cookie and respond with the responder provided.
+    pub fn new<N>(name: N, responder: I) -> Self
+    where
+        I: Responder,
+        N: Into<Cow<'c, str>>,
+    {
+        Self {
+            name: name.into(),
+            responder,
+        }
+    }
+}
+
+impl<I> Responder for RemoveCookie<'_, I>
+where
+    I: Responder,
+{
+    type Body = I::Body;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let mut inner_response = self.responder.respond_to(req);
+
+        // Must match the path the cookie was set with
+        let mut cookie = Cookie::build(self.name, "").path("/").finish();
+        cookie.make_removal();
+
+        inner_response.add_cookie(&cookie).unwrap();
+        inner_response
+    }
+}
+
 /// Set a cookie, but not always. Useful for when a responder should not always
 /// set a cookie.
 pub struct MaybeCookie<'c, I> {
@@ -1,10 +1,13 @@
+use crate::access_tokens;
 use crate::routes::appdata::WDatabase;
 use crate::routes::error::{WebError, WebErrorKind, WebResult};
 use actix_web::cookie::time::OffsetDateTime;
 use actix_web::dev::Payload;
 use actix_web::{FromRequest, HttpRequest};
 use database::constant_access_tokens::ConstantAccessToken;
+use database::driver::Database;
 use database::oauth2_client::AccessToken;
+use database::session::UserSession;
 use database::user::User;
 use std::collections::HashSet;
 use std::future::Future;
@@ -31,20 +34,16 @@ impl FromRequest for Auth {
             .clone();
 
         Box::pin(async move {
-            let token = get_authorization_token(&req)?;
+            let token_info = get_access_token(&req, &database).await?;
+
+            // Tokens issued with the client credentials grant have no user behind them,
+            // these cannot be used for endpoints acting on behalf of a user.
+            let user_id = token_info
+                .user_id
+                .as_ref()
+                .ok_or(WebError::from(WebErrorKind::Forbidden))?;
 
-            let token_info = match AccessToken::get_by_token(&database, &token).await? {
-                Some(v) => {
-                    if v.expires_at < OffsetDateTime::now_utc().unix_timestamp() {
-                        return Err(WebErrorKind::Unauthorized.into());
-                    } else {
-                        v
-                    }
-                }
-                None => return Err(WebErrorKind::Unauthorized.into()),
-            };
-
-            let user = User::get_by_id(&database, &token_info.user_id)
+            let user = User::get_by_id(&database, user_id)
                 .await?
                 .ok_or(WebError::from(WebErrorKind::InternalServerError))?;
 
@@ -72,6 +71,92 @@ impl Auth {
     }
 }
 
+/// Authorization using an OAuth2 access token.
+/// Unlike [Auth], the token does not have to belong to a user.
+/// Tokens issued with the client credentials grant are accepted as well.
+#[derive(Debug, Clone)]
+pub struct TokenAuth {
+    token: AccessToken,
+}
+
+impl FromRequest for TokenAuth {
+    type Error = WebError;
+    type Future = Pin<Box<dyn Future<Output = WebResult<Self>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let database = req
+            .app_data::<WDatabase>()
+            .expect("Getting AppData for type WDatabase")
+            .clone();
+
+        Box::pin(async move {
+            let token = get_access_token(&req, &database).await?;
+            Ok(Self { token })
+        })
+    }
+}
+
+impl TokenAuth {
+    /// The ID of the client the token was issued to.
+    pub fn client_id(&self) -> &str {
+        &self.token.client_id
+    }
+
+    /// The ID of the user the token was issued to.
+    /// `None` if the token was issued to the client itself.
+    pub fn user_id(&self) -> Option<&str> {
+        self.token.user_id.as_deref()
+    }
+
+    /// Get the set of scopes the authorization is authorized for.
+    pub fn scopes(&self) -> HashSet<String> {
+        self.token.scopes()
+    }
+}
+
+/// The name of the cookie holding the login session token
+pub const SESSION_COOKIE: &str = "Session";
+
+/// Authentication using the login session cookie.
+/// Sessions are only used to authorize clients without logging in again,
+/// they cannot be used to access the API.
+#[derive(Debug, Clone)]
+pub struct SessionAuth {
+    pub session: UserSession,
+    pub user: User,
+}
+
+impl FromRequest for SessionAuth {
+    type Error = WebError;
+    type Future = Pin<Box<dyn Future<Output = WebResult<Self>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let database = req
+            .app_data::<WDatabase>()
+            .expect("Getting AppData for type WDatabase")
+            .clone();
+
+        Box::pin(async move {
+            let token = req
+                .cookie(SESSION_COOKIE)
+                .map(|c| c.value().to_string())
+                .ok_or(WebErrorKind::Unauthorized)?;
+
+            let session = UserSession::get_by_token(&database, &token)
+                .await?
+                .ok_or(WebErrorKind::Unauthorized)?;
+
+            let user = User::get_by_id(&database, &session.user_id)
+                .await?
+                .ok_or(WebErrorKind::Unauthorized)?;
+
+            Ok(Self { session, user })
+        })
+    }
+}
+
 /// Authentication using a constant token.
 /// These tokens are created manually.
 pub struct ConstantAccessTokenAuth {
@@ -108,6 +193,21 @@ impl FromRequest for ConstantAccessTokenAuth {
     }
 }
 
+/// Get the access token provided with the request.
+///
+/// # Errors
+/// - If no token is provided
+/// - If the token does not exist
+/// - If the token has expired
+async fn get_access_token(req: &HttpRequest, database: &Database) -> WebResult<AccessToken> {
+    let token = get_authorization_token(req)?;
+
+    match access_tokens::resolve(database, &token).await? {
+        Some(v) if v.expires_at >= OffsetDateTime::now_utc().unix_timestamp() => Ok(v),
+        _ => Err(WebErrorKind::Unauthorized.into()),
+    }
+}
+
 /// Get an authorization token from, in order:
 /// - The `Authorization` header.
 /// - The `Authorization` cookie.
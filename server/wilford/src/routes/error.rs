@@ -1,5 +1,6 @@
+use actix_web::http::header::{ContentType, RETRY_AFTER};
 use actix_web::http::StatusCode;
-use actix_web::ResponseError;
+use actix_web::{HttpResponse, ResponseError};
 use std::fmt;
 use std::fmt::{Formatter, Write};
 use thiserror::Error;
@@ -10,7 +11,7 @@ pub type WebResult<T> = Result<T, WebError>;
 #[derive(Error)]
 #[error("{}", kind)]
 pub struct WebError {
-    kind: WebErrorKind,
+    kind: Box<WebErrorKind>,
     context: SpanTrace,
 }
 
@@ -31,7 +32,7 @@ where
     #[track_caller]
     fn from(value: E) -> Self {
         Self {
-            kind: value.into(),
+            kind: Box::new(value.into()),
             context: SpanTrace::capture(),
         }
     }
@@ -63,11 +64,16 @@ pub enum WebErrorKind {
     Email(#[from] crate::mail::MailerError),
     #[error("Your email address is not verified")]
     EmailNotVerified,
+    /// The number of seconds after which the request may be retried
+    #[error("Too many attempts, try again in {0} seconds")]
+    TooManyRequests(i64),
+    #[error("{0}")]
+    SigningKey(#[from] crate::signing_keys::SigningKeyError),
 }
 
 impl ResponseError for WebError {
     fn status_code(&self) -> StatusCode {
-        match self.kind {
+        match *self.kind {
             WebErrorKind::NotFound => StatusCode::NOT_FOUND,
             WebErrorKind::BadRequest => StatusCode::BAD_REQUEST,
             WebErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
@@ -79,7 +85,20 @@ impl ResponseError for WebError {
             WebErrorKind::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
             WebErrorKind::RsaPkcs8Spki(_) => StatusCode::INTERNAL_SERVER_ERROR,
             WebErrorKind::Email(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebErrorKind::SigningKey(_) => StatusCode::INTERNAL_SERVER_ERROR,
             WebErrorKind::EmailNotVerified => StatusCode::UNAUTHORIZED,
+            WebErrorKind::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let WebErrorKind::TooManyRequests(retry_after) = *self.kind {
+            response.insert_header((RETRY_AFTER, retry_after));
+        }
+
+        response
+            .content_type(ContentType::plaintext())
+            .body(self.to_string())
+    }
 }
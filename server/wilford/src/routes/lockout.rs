@@ -0,0 +1,221 @@
+//! Protection against guessing passwords.
+//!
+//! Failed attempts are counted per account and per client IP address.
+//! After a few failures every further attempt is delayed, with a delay that doubles on every failure.
+//! After more failures the account or IP address is locked for a while.
+//! Attempts are rejected with `429 Too Many Requests` while delayed or locked,
+//! without checking the credentials, so the authorization provider is not consulted either.
+
+use crate::authorization::AuthorizationError;
+use crate::client_ip::client_ip;
+use crate::config::{Config, LockoutConfig};
+use crate::mail::WilfordMailer;
+use crate::routes::error::{WebErrorKind, WebResult};
+use actix_web::cookie::time::OffsetDateTime;
+use actix_web::HttpRequest;
+use database::driver::Database;
+use database::login_failure::{LoginFailureKind, LoginFailures};
+use database::user::User;
+use mailer::{AccountLockedData, AccountLockedMail};
+use tap::TapFallible;
+use tracing::{instrument, warn};
+
+/// Guards the password checks of one request.
+pub struct LoginThrottle<'a> {
+    database: &'a Database,
+    config: &'a Config,
+    ip: Option<String>,
+}
+
+impl<'a> LoginThrottle<'a> {
+    pub fn new(database: &'a Database, config: &'a Config, req: &HttpRequest) -> Self {
+        Self {
+            database,
+            config,
+            ip: client_ip(req, &config.http.trusted_proxies).map(|ip| ip.to_string()),
+        }
+    }
+
+    /// Check whether credentials may be checked for the account,
+    /// the username or email address the user logs in with.
+    ///
+    /// # Errors
+    ///
+    /// - If the account or the client's IP address is delayed or locked
+    /// - If a query fails
+    #[instrument(skip(self))]
+    pub async fn check(&self, account: &str) -> WebResult<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        for (kind, subject) in self.subjects(account) {
+            if let Some(failures) = LoginFailures::get(self.database, kind, &subject).await? {
+                if failures.blocked_until > now {
+                    warn!("Rejected login attempt for blocked {kind:?} {subject}");
+                    return Err(WebErrorKind::TooManyRequests(failures.blocked_until - now).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the result of checking the credentials for the account.
+    /// Invalid credentials count as failed attempt for both the account and the IP address.
+    /// Valid credentials forget the failed attempts for the account,
+    /// but not for the IP address, so an attacker cannot reset the count with their own account.
+    ///
+    /// # Errors
+    ///
+    /// If a query fails
+    #[instrument(skip(self, result))]
+    pub async fn record<T, E: std::error::Error>(
+        &self,
+        account: &str,
+        result: &Result<T, AuthorizationError<E>>,
+    ) -> WebResult<()> {
+        match result {
+            Ok(_) => {
+                LoginFailures::clear(
+                    self.database,
+                    LoginFailureKind::Account,
+                    &normalize(account),
+                )
+                .await?;
+            }
+            Err(AuthorizationError::InvalidCredentials) => self.record_failure(account).await?,
+            Err(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Record a failed attempt, and delay or lock the account and IP address if needed.
+    ///
+    /// # Errors
+    ///
+    /// If a query fails
+    pub async fn record_failure(&self, account: &str) -> WebResult<()> {
+        let lockout = &self.config.lockout;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        for (kind, subject) in self.subjects(account) {
+            let mut failures = LoginFailures::record_failure(
+                self.database,
+                kind,
+                &subject,
+                lockout.failure_window,
+            )
+            .await?;
+
+            let count = failures.failures.max(0) as u32;
+            let threshold = match kind {
+                LoginFailureKind::Account => lockout.account_threshold,
+                LoginFailureKind::Ip => lockout.ip_threshold,
+            };
+
+            let duration = block_duration(lockout, count, threshold);
+            if duration > 0 {
+                failures.block_until(self.database, now + duration).await?;
+            }
+
+            // Only notify once, further failures are rejected until the lock expires
+            if count == threshold {
+                warn!("Locked {kind:?} {subject} after {count} failed login attempts");
+
+                if kind == LoginFailureKind::Account {
+                    self.notify_locked(&subject, count).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inform the user their account was locked.
+    /// Failures are logged, the attempt is rejected either way.
+    async fn notify_locked(&self, account: &str, failures: u32) {
+        let Some(email_cfg) = &self.config.email else {
+            return;
+        };
+
+        // Users of EspoCRM may log in with a username instead,
+        // those cannot be notified as we do not know who they are
+        let Ok(Some(user)) = User::get_by_email(self.database, account)
+            .await
+            .tap_err(|e| warn!("{e}"))
+        else {
+            return;
+        };
+
+        let _ = WilfordMailer::new(email_cfg)
+            .send_email(
+                &user.email,
+                AccountLockedMail,
+                &AccountLockedData {
+                    name: user.name,
+                    failures,
+                    locked_minutes: self.config.lockout.duration / 60,
+                },
+                user.locale,
+            )
+            .await
+            .tap_err(|e| warn!("Failed to send account locked email: {e}"));
+    }
+
+    /// The account and the IP address, if known, attempts are counted for
+    fn subjects(&self, account: &str) -> Vec<(LoginFailureKind, String)> {
+        let mut subjects = vec![(LoginFailureKind::Account, normalize(account))];
+        if let Some(ip) = &self.ip {
+            subjects.push((LoginFailureKind::Ip, ip.clone()));
+        }
+
+        subjects
+    }
+}
+
+/// Normalize a username or email address,
+/// so the same account is counted regardless of how it is written.
+pub fn normalize(account: &str) -> String {
+    account.trim().to_lowercase()
+}
+
+/// The time in seconds attempts are blocked for after a number of failures.
+/// Returns 0 if attempts are not blocked.
+fn block_duration(config: &LockoutConfig, failures: u32, threshold: u32) -> i64 {
+    if failures >= threshold {
+        config.duration
+    } else if failures > config.delay_after {
+        2i64.saturating_pow(failures - config.delay_after - 1)
+            .min(config.max_delay)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let config = LockoutConfig::default();
+        let durations = (1..=10)
+            .map(|failures| block_duration(&config, failures, config.account_threshold))
+            .collect::<Vec<_>>();
+
+        assert_eq!(durations, [0, 0, 0, 1, 2, 4, 8, 16, 30, config.duration]);
+    }
+
+    #[test]
+    fn locked_above_threshold() {
+        let config = LockoutConfig::default();
+        assert_eq!(block_duration(&config, 50, 50), config.duration);
+        assert_eq!(block_duration(&config, 100, 50), config.duration);
+        assert_eq!(block_duration(&config, 49, 50), config.max_delay);
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!(normalize(" User@Example.com "), "user@example.com");
+    }
+}
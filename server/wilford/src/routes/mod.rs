@@ -5,7 +5,9 @@ use actix_web::web::ServiceConfig;
 mod appdata;
 mod auth;
 mod error;
+mod lockout;
 mod oauth;
+mod rate_limited;
 mod v1;
 mod well_known;
 
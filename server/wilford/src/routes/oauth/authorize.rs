@@ -1,9 +1,22 @@
+use crate::authorization::combined::CombinedAuthorizationProvider;
+use crate::authorization::{AuthorizationError, AuthorizationProvider};
+use crate::config::Config;
 use crate::response_types::{Redirect, Uncached};
 use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::auth::SessionAuth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::oauth::{pkce, prompt, redirect_uri};
 use crate::routes::oauth::{OAuth2AuthorizationResponse, OAuth2Error, OAuth2ErrorKind};
+use crate::routes::v1::auth::{authorization_info, login};
+use actix_web::cookie::time::OffsetDateTime;
 use actix_web::web;
-use database::oauth2_client::{AuthorizationType, OAuth2Client};
-use serde::Deserialize;
+use database::driver::Database;
+use database::oauth2_client::{
+    AuthorizationRequest, AuthorizationType, CodeChallenge, CodeChallengeMethod, OAuth2Client,
+    OAuth2PendingAuthorization,
+};
+use serde::{Deserialize, Serialize};
+use tap::TapFallible;
 use tracing::warn;
 
 #[derive(Deserialize)]
@@ -14,6 +27,17 @@ pub struct Query {
     scope: Option<String>,
     state: Option<String>,
     nonce: Option<String>,
+    /// PKCE code challenge, see RFC 7636
+    code_challenge: Option<String>,
+    /// PKCE code challenge method. Defaults to `plain` if a challenge is provided
+    code_challenge_method: Option<CodeChallengeMethod>,
+    /// Space separated list of OpenID Connect prompt values
+    prompt: Option<String>,
+    /// Maximum number of seconds since the user last authenticated.
+    /// If exceeded, the user must log in again
+    max_age: Option<i64>,
+    /// Hint about the identifier the user might use to log in, e.g. their email address
+    login_hint: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -33,8 +57,40 @@ pub enum ResponseType {
 pub async fn authorize(
     database: WDatabase,
     config: WConfig,
+    session: Option<SessionAuth>,
     query: web::Query<Query>,
 ) -> OAuth2AuthorizationResponse<Uncached<Redirect>> {
+    // Until the client and redirect URI are validated, errors are not returned to the redirect URI
+
+    // Get the OAuth2 client
+    let client = match OAuth2Client::get_by_client_id(&database, &query.client_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => {
+            return OAuth2AuthorizationResponse::NotRedirected(OAuth2ErrorKind::UnauthorizedClient)
+        }
+        Err(e) => {
+            warn!("{e}");
+            return OAuth2AuthorizationResponse::NotRedirected(OAuth2ErrorKind::ServerError);
+        }
+    };
+
+    let redirect_uris = match client.list_redirect_uris(&database).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("{e}");
+            return OAuth2AuthorizationResponse::NotRedirected(OAuth2ErrorKind::ServerError);
+        }
+    };
+
+    // Check redirect URI, it should be one of the URIs the OAuth client has registered
+    if !redirect_uri::is_registered(
+        &redirect_uris,
+        &query.redirect_uri,
+        client.allow_loopback_any_port,
+    ) {
+        return OAuth2AuthorizationResponse::NotRedirected(OAuth2ErrorKind::InvalidRequest);
+    }
+
     // Required for the `id_token` response type
     // OpenID Connect Core specification, section 3.2.2.1
     if query.response_type.eq(&ResponseType::IdToken) && query.nonce.is_none() {
@@ -45,28 +101,18 @@ pub async fn authorize(
         ));
     }
 
-    // Get the OAuth2 client
-    let client = match OAuth2Client::get_by_client_id(&database, &query.client_id).await {
-        Ok(Some(c)) => c,
-        Ok(None) => {
-            return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
-                OAuth2ErrorKind::UnauthorizedClient,
-                &query.redirect_uri,
-                query.state.as_deref(),
-            ))
-        }
-        Err(e) => {
-            warn!("{e}");
+    if let Some(prompt) = &query.prompt {
+        if !prompt::is_valid(prompt) {
             return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
-                OAuth2ErrorKind::ServerError,
+                OAuth2ErrorKind::InvalidRequest,
                 &query.redirect_uri,
                 query.state.as_deref(),
             ));
         }
-    };
+    }
 
-    // Check redirect URI, it should match what the OAuth client has configured
-    if client.redirect_uri.ne(&query.redirect_uri) {
+    // The client must have registered the response type
+    if !client.allows_response_type(response_to_authorization_type(&query.response_type)) {
         return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
             OAuth2ErrorKind::UnauthorizedClient,
             &query.redirect_uri,
@@ -74,15 +120,34 @@ pub async fn authorize(
         ));
     }
 
+    // Check the PKCE parameters, if provided.
+    // Public clients cannot authenticate themselves at the token endpoint,
+    // thus for them PKCE is required with the authorization code flow.
+    let code_challenge = match get_code_challenge(&query, &client) {
+        Ok(c) => c,
+        Err(kind) => {
+            return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
+                kind,
+                &query.redirect_uri,
+                query.state.as_deref(),
+            ))
+        }
+    };
+
     // Create a pending authorization. This authorization
     // will later be 'authorized' by a user logging in.
     let pending_authorization = client
         .new_pending_authorization(
             &database,
-            query.scope.clone(),
-            query.state.clone(),
-            response_to_authorization_type(&query.response_type),
-            query.nonce.clone(),
+            AuthorizationRequest {
+                scopes: query.scope.clone(),
+                state: query.state.clone(),
+                ty: response_to_authorization_type(&query.response_type),
+                nonce: query.nonce.clone(),
+                code_challenge,
+                redirect_uri: query.redirect_uri.clone(),
+                prompt: query.prompt.clone(),
+            },
         )
         .await;
 
@@ -98,14 +163,174 @@ pub async fn authorize(
         }
     };
 
+    let prompt_none = prompt::contains(query.prompt.as_deref(), prompt::NONE);
+
+    // Reuse the login session of the user, if there is one
+    if let Some(session) = reusable_session(session, &query) {
+        match resume_session(&database, &config, pending_authorization.clone(), &session).await {
+            Ok(Some(url)) => {
+                return OAuth2AuthorizationResponse::Ok(Uncached::new(Redirect::new(url)))
+            }
+            // Consent is required, which requires interaction
+            Ok(None) if prompt_none => {
+                return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
+                    OAuth2ErrorKind::ConsentRequired,
+                    &query.redirect_uri,
+                    query.state.as_deref(),
+                ))
+            }
+            // The login page forwards the user to the consent page,
+            // as the authorization is already authorized
+            Ok(None) => {}
+            // The user has to log in again, so they are told what went wrong
+            Err(e) => warn!("Cannot reuse login session: {e}"),
+        }
+    }
+
+    // Logging in requires interaction
+    if prompt_none {
+        return OAuth2AuthorizationResponse::Err(OAuth2Error::new(
+            OAuth2ErrorKind::LoginRequired,
+            &query.redirect_uri,
+            query.state.as_deref(),
+        ));
+    }
+
+    #[derive(Serialize)]
+    struct LoginQuery<'a> {
+        authorization: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        login_hint: Option<&'a str>,
+    }
+
     // Redirect to login page
     OAuth2AuthorizationResponse::Ok(Uncached::new(Redirect::new(format!(
-        "{}?authorization={}",
+        "{}?{}",
         config.http.ui_login_path,
-        pending_authorization.id(),
+        serde_qs::to_string(&LoginQuery {
+            authorization: pending_authorization.id(),
+            login_hint: query.login_hint.as_deref(),
+        })
+        .expect("Serializing query string"),
     ))))
 }
 
+/// Get the login session of the user, if it may be reused for the authorization request.
+/// It may not be reused if
+/// - `prompt=login` or `prompt=select_account` is requested
+/// - the user authenticated longer than `max_age` seconds ago
+/// - the `login_hint` refers to a different user
+fn reusable_session(session: Option<SessionAuth>, query: &Query) -> Option<SessionAuth> {
+    let session = session?;
+    let auth_time = session.session.auth_time;
+
+    if prompt::contains(query.prompt.as_deref(), prompt::LOGIN)
+        || prompt::contains(query.prompt.as_deref(), prompt::SELECT_ACCOUNT)
+    {
+        return None;
+    }
+
+    if let Some(max_age) = query.max_age {
+        if OffsetDateTime::now_utc().unix_timestamp() - auth_time > max_age {
+            return None;
+        }
+    }
+
+    if let Some(login_hint) = &query.login_hint {
+        if !login_hint.eq(&session.user.user_id)
+            && !login_hint.eq_ignore_ascii_case(&session.user.email)
+        {
+            return None;
+        }
+    }
+
+    Some(session)
+}
+
+/// Authorize the pending authorization with the login session of the user.
+/// Returns the URL at which the authorization is granted,
+/// or `None` if the user must be asked for consent first.
+///
+/// # Errors
+/// - If the authorization provider no longer allows the user to log in
+/// - If the user may not complete the authorization
+/// - If a query fails
+async fn resume_session(
+    database: &Database,
+    config: &Config,
+    pending_authorization: OAuth2PendingAuthorization,
+    session: &SessionAuth,
+) -> WebResult<Option<String>> {
+    let user = &session.user;
+
+    // The user may have been deactivated since they logged in
+    let user_information = CombinedAuthorizationProvider::new(config, database)
+        .validate_user(&user.user_id)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|e| match e {
+            AuthorizationError::InvalidCredentials => WebErrorKind::Unauthorized,
+            _ => WebErrorKind::InternalServerError,
+        })?;
+
+    login::check_user(
+        database,
+        &pending_authorization,
+        user,
+        user_information.is_admin,
+    )
+    .await?;
+
+    let pending_authorization = pending_authorization
+        .set_session(database, &session.session)
+        .await
+        .map_err(|_| WebErrorKind::InvalidInternalState)?;
+
+    session.session.touch(database).await?;
+
+    if authorization_info::is_consent_required(database, &pending_authorization, &user.user_id)
+        .await?
+    {
+        return Ok(None);
+    }
+
+    // Relative to this endpoint, so that the path prefix Wilford is served under does not matter
+    Ok(Some(format!(
+        "../v1/auth/authorize?authorization={}&grant=true",
+        pending_authorization.id()
+    )))
+}
+
+/// Get the PKCE code challenge from the query.
+///
+/// # Errors
+/// - If a `code_challenge_method` is provided without a `code_challenge`
+/// - If the `code_challenge` is malformed
+/// - If the client is public and uses the authorization code flow without a `code_challenge`
+fn get_code_challenge(
+    query: &Query,
+    client: &OAuth2Client,
+) -> Result<Option<CodeChallenge>, OAuth2ErrorKind> {
+    match (&query.code_challenge, query.code_challenge_method) {
+        (Some(challenge), method) => {
+            if !pkce::is_well_formed(challenge) {
+                return Err(OAuth2ErrorKind::InvalidRequest);
+            }
+
+            Ok(Some(CodeChallenge {
+                challenge: challenge.clone(),
+                // Defaults to `plain`, RFC 7636 Section 4.3
+                method: method.unwrap_or(CodeChallengeMethod::Plain),
+            }))
+        }
+        (None, Some(_)) => Err(OAuth2ErrorKind::InvalidRequest),
+        (None, None) if client.is_public && query.response_type.eq(&ResponseType::Code) => {
+            Err(OAuth2ErrorKind::InvalidRequest)
+        }
+        (None, None) => Ok(None),
+    }
+}
+
 fn response_to_authorization_type(rt: &ResponseType) -> AuthorizationType {
     match rt {
         ResponseType::Code => AuthorizationType::AuthorizationCode,
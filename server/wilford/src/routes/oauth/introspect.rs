@@ -1,11 +1,11 @@
 //! Introspect endpoint in accordance with RFC7662
 
+use crate::access_tokens;
 use crate::routes::appdata::WDatabase;
 use crate::routes::auth::ConstantAccessTokenAuth;
 use actix_web::body::BoxBody;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, ResponseError};
-use database::oauth2_client::AccessToken;
 use database::user::User;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -22,12 +22,15 @@ pub struct Response {
     active: bool,
     scope: String,
     client_id: String,
-    username: String,
+    /// The name of the user.
+    /// Not present if the token was issued to the client itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
     token_type: String,
     exp: i64,
     iat: i64,
     nbf: i64,
-    /// Espo user id
+    /// Espo user id, or the client id if the token was issued to the client itself
     sub: String,
 }
 
@@ -75,7 +78,7 @@ pub async fn introspect(
     _: ConstantAccessTokenAuth,
     form: web::Form<Form>,
 ) -> Result<web::Json<Response>, IntrospectError> {
-    let token = AccessToken::get_by_token(&database, &form.token)
+    let token = access_tokens::resolve(&database, &form.token)
         .await?
         .ok_or(IntrospectError::InvalidToken)?;
 
@@ -88,19 +91,27 @@ pub async fn introspect(
         }
     }
 
-    let user = User::get_by_id(&database, &token.user_id)
-        .await?
-        .ok_or(IntrospectError::Internal)?;
+    // Tokens issued with the client credentials grant have no user,
+    // the subject is then the client itself.
+    let (sub, username) = match &token.user_id {
+        Some(user_id) => {
+            let user = User::get_by_id(&database, user_id)
+                .await?
+                .ok_or(IntrospectError::Internal)?;
+            (user.user_id, Some(user.name))
+        }
+        None => (token.client_id.clone(), None),
+    };
 
     Ok(web::Json(Response {
         active: true,
         scope: token.scopes.unwrap_or_default(),
         client_id: token.client_id,
-        username: user.name,
+        username,
         token_type: "bearer".to_string(),
         exp: token.expires_at,
         iat: token.issued_at,
         nbf: token.issued_at,
-        sub: token.user_id,
+        sub,
     }))
 }
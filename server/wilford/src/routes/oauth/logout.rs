@@ -0,0 +1,484 @@
+//! RP-initiated logout.
+//!
+//! Logging out terminates the login session in the session cookie, and revokes the tokens
+//! issued for authorizations granted with that session. Other sessions of the user are not affected.
+//!
+//! # Further reading
+//! - [OpenID Connect RP-Initiated Logout 1.0](https://openid.net/specs/openid-connect-rpinitiated-1_0.html)
+
+use crate::backchannel_logout;
+use crate::response_types::{Redirect, RemoveCookie};
+use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::auth::{SessionAuth, SESSION_COOKIE};
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::oauth::redirect_uri;
+use crate::signing_keys;
+use actix_web::{web, Either};
+use database::driver::Database;
+use database::oauth2_client::OAuth2Client;
+use database::session::UserSession;
+use jwt_simple::claims::{JWTClaims, NoCustomClaims};
+use jwt_simple::common::VerificationOptions;
+use jwt_simple::reexports::coarsetime::Duration;
+use jwt_simple::token::Token;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::instrument;
+
+/// How long after expiry an ID token is still accepted as `id_token_hint`.
+/// Users often log out long after their ID token has expired.
+/// The hint only identifies the client, it is not sufficient to log the user out.
+const ID_TOKEN_HINT_MAX_AGE_DAYS: u64 = 30;
+
+#[derive(Deserialize, Serialize)]
+pub struct Params {
+    /// An ID token previously issued to the client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token_hint: Option<String>,
+    /// The client requesting the logout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    /// Where to redirect the user after logging out.
+    /// Must be registered for the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_logout_redirect_uri: Option<String>,
+    /// Passed back to the client in the redirect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    /// The ID of the session to log out of, sent by the logout confirmation page.
+    /// Other pages cannot know it, so it shows the user confirmed the logout.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirmation: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RedirectQuery<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<&'a str>,
+}
+
+/// Either the user is logged out and redirected,
+/// or the user is redirected to the logout confirmation page
+type LogoutResponse = Either<RemoveCookie<'static, RemoveCookie<'static, Redirect>>, Redirect>;
+
+pub async fn logout_get(
+    database: WDatabase,
+    config: WConfig,
+    session: Option<SessionAuth>,
+    query: web::Query<Params>,
+) -> WebResult<LogoutResponse> {
+    logout(&database, &config, session, query.into_inner()).await
+}
+
+pub async fn logout_post(
+    database: WDatabase,
+    config: WConfig,
+    session: Option<SessionAuth>,
+    form: web::Form<Params>,
+) -> WebResult<LogoutResponse> {
+    logout(&database, &config, session, form.into_inner()).await
+}
+
+/// Log the user out of the login session in the session cookie.
+/// The session is terminated, the tokens issued for authorizations granted with it are revoked,
+/// and the clients holding those tokens are notified if they have a back-channel logout URI.
+///
+/// The `id_token_hint` only identifies the client. The session is terminated right away
+/// only if the hint was issued to the user of the session. Otherwise the request may have been
+/// sent by any page, so the user is redirected to the logout confirmation page first.
+/// Afterwards, the user is redirected to the `post_logout_redirect_uri`, or to the login page.
+///
+/// # Errors
+///
+/// - If the `id_token_hint` is not valid
+/// - If the `client_id` does not match the audience of the `id_token_hint`
+/// - If the `post_logout_redirect_uri` is not registered for the client
+/// - If the operation fails
+#[instrument(skip_all)]
+async fn logout(
+    database: &Database,
+    config: &WConfig,
+    session: Option<SessionAuth>,
+    mut params: Params,
+) -> WebResult<LogoutResponse> {
+    let hint = match &params.id_token_hint {
+        Some(token) => Some(verify_id_token_hint(database, &config.oidc_issuer, token).await?),
+        None => None,
+    };
+
+    let client_id = requesting_client(hint.as_ref(), params.client_id.as_deref())?;
+
+    let redirect = match &params.post_logout_redirect_uri {
+        Some(uri) => {
+            // Without a client, the redirect URI cannot be validated
+            let client_id = client_id.as_ref().ok_or(WebErrorKind::BadRequest)?;
+            let client = OAuth2Client::get_by_client_id(database, client_id)
+                .await?
+                .ok_or(WebErrorKind::BadRequest)?;
+
+            let registered = client.list_post_logout_redirect_uris(database).await?;
+            post_logout_redirect(
+                &registered,
+                client.allow_loopback_any_port,
+                uri,
+                params.state.as_deref(),
+            )
+            .ok_or(WebErrorKind::BadRequest)?
+        }
+        None => config.http.ui_login_path.clone(),
+    };
+
+    let Some(session) = session.map(|s| s.session) else {
+        // Not logged in, so there is no session to log out of
+        return Ok(Either::Left(remove_cookies(redirect)));
+    };
+
+    if !is_confirmed(&session, hint.as_ref(), params.confirmation.as_deref()) {
+        // The confirmation page sends the request back, with the session ID as confirmation
+        params.confirmation = Some(session.session_id);
+        let query = serde_qs::to_string(&params).expect("Serializing query string");
+
+        return Ok(Either::Right(Redirect::new(format!(
+            "{}?{query}",
+            config.logout_page()
+        ))));
+    }
+
+    let user_id = session.user_id.clone();
+
+    // Collected before the tokens are revoked, as the tokens determine which clients the session is used by
+    let clients =
+        OAuth2Client::list_for_session_backchannel_logout(database, &session.session_id).await?;
+    session.logout(database).await?;
+
+    backchannel_logout::notify(
+        database.clone(),
+        config.oidc_issuer.clone(),
+        clients,
+        user_id,
+    );
+
+    Ok(Either::Left(remove_cookies(redirect)))
+}
+
+fn remove_cookies(redirect: String) -> RemoveCookie<'static, RemoveCookie<'static, Redirect>> {
+    RemoveCookie::new(
+        "Authorization",
+        RemoveCookie::new(SESSION_COOKIE, Redirect::new(redirect)),
+    )
+}
+
+/// Whether the user may be logged out of the session without asking them first.
+/// That is the case if the logout was requested with an ID token issued to the user of the session,
+/// or if the user confirmed the logout on the logout confirmation page.
+fn is_confirmed(
+    session: &UserSession,
+    hint: Option<&IdTokenHint>,
+    confirmation: Option<&str>,
+) -> bool {
+    hint.is_some_and(|hint| hint.user_id.eq(&session.user_id))
+        || confirmation.is_some_and(|confirmation| confirmation.eq(&session.session_id))
+}
+
+/// The client requesting the logout, from the `id_token_hint` or the `client_id`.
+///
+/// # Errors
+///
+/// If the `client_id` does not match the audience of the `id_token_hint`
+fn requesting_client(
+    hint: Option<&IdTokenHint>,
+    client_id: Option<&str>,
+) -> WebResult<Option<String>> {
+    match (hint, client_id) {
+        (Some(hint), Some(client_id)) if hint.client_id.ne(client_id) => {
+            Err(WebErrorKind::BadRequest.into())
+        }
+        (Some(hint), _) => Ok(Some(hint.client_id.clone())),
+        (None, client_id) => Ok(client_id.map(|c| c.to_string())),
+    }
+}
+
+/// The URI to redirect the user to after logging out, with the `state` of the logout request.
+/// Returns `None` if the URI is not registered for the client.
+fn post_logout_redirect(
+    registered: &[String],
+    allow_loopback_any_port: bool,
+    uri: &str,
+    state: Option<&str>,
+) -> Option<String> {
+    if !redirect_uri::is_registered(registered, uri, allow_loopback_any_port) {
+        return None;
+    }
+
+    let query = serde_qs::to_string(&RedirectQuery { state }).expect("Serializing query string");
+
+    Some(match (query.is_empty(), uri.contains('?')) {
+        (true, _) => uri.to_string(),
+        (false, false) => format!("{uri}?{query}"),
+        (false, true) => format!("{uri}&{query}"),
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct IdTokenHint {
+    client_id: String,
+    user_id: String,
+}
+
+impl IdTokenHint {
+    /// Get the client and user from the claims of an ID token.
+    /// Returns `None` if either is missing.
+    fn from_claims(claims: JWTClaims<NoCustomClaims>) -> Option<Self> {
+        Some(Self {
+            client_id: claims.audiences?.into_string().ok()?,
+            user_id: claims.subject?,
+        })
+    }
+}
+
+/// Verify an ID token we issued. Expired ID tokens are accepted.
+///
+/// # Errors
+///
+/// - If the token is not an ID token issued by us
+/// - If the query fails
+async fn verify_id_token_hint(
+    database: &Database,
+    issuer: &str,
+    token: &str,
+) -> WebResult<IdTokenHint> {
+    // Reject other JWTs signed with our keys, such as JWT access tokens
+    if !is_id_token(token) {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let claims = signing_keys::verify::<NoCustomClaims>(
+        database,
+        token,
+        Some(hint_verification_options(issuer)),
+    )
+    .await?
+    .ok_or(WebErrorKind::BadRequest)?;
+
+    Ok(IdTokenHint::from_claims(claims).ok_or(WebErrorKind::BadRequest)?)
+}
+
+/// Whether the token has the `typ` header of ID tokens.
+/// Other JWTs we sign have a different type, e.g. `at+jwt` for access tokens.
+fn is_id_token(token: &str) -> bool {
+    Token::decode_metadata(token).is_ok_and(|metadata| metadata.signature_type() == Some("JWT"))
+}
+
+/// The options to verify an `id_token_hint` with: it must be issued by us,
+/// and may have expired up to [ID_TOKEN_HINT_MAX_AGE_DAYS] ago.
+fn hint_verification_options(issuer: &str) -> VerificationOptions {
+    VerificationOptions {
+        allowed_issuers: Some(HashSet::from([issuer.to_string()])),
+        time_tolerance: Some(Duration::from_days(ID_TOKEN_HINT_MAX_AGE_DAYS)),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use database::oauth2_client::{create_access_token_jwt, AccessToken};
+    use database::signing_key::{JwtSigningAlgorithm, SigningKey, SigningKeyState};
+    use jwt_simple::algorithms::{ECDSAP256KeyPairLike, ES256KeyPair};
+    use jwt_simple::claims::Claims;
+    use jwt_simple::reexports::coarsetime::Clock;
+
+    const ISSUER: &str = "https://wilford.example.com";
+
+    fn hint() -> IdTokenHint {
+        IdTokenHint {
+            client_id: "client".to_string(),
+            user_id: "user".to_string(),
+        }
+    }
+
+    fn session() -> UserSession {
+        UserSession {
+            session_id: "session".to_string(),
+            token_hash: "hash".to_string(),
+            user_id: "user".to_string(),
+            auth_time: 0,
+            amr: "pwd".to_string(),
+            created_at: 0,
+            last_used_at: 0,
+            expires_at: i64::MAX,
+            user_agent: None,
+        }
+    }
+
+    fn signing_key(key: &ES256KeyPair) -> SigningKey {
+        SigningKey {
+            kid: "kid".to_string(),
+            private_key: key.to_pem().unwrap(),
+            public_key: key.public_key().to_pem().unwrap(),
+            algorithm: JwtSigningAlgorithm::ES256,
+            state: SigningKeyState::Active,
+            created_at: 0,
+            activated_at: Some(0),
+            retired_at: None,
+        }
+    }
+
+    /// Sign an ID token, which expired `expired_days` days ago
+    fn id_token(key: &ES256KeyPair, issuer: &str, expired_days: u64) -> String {
+        let mut claims = Claims::create(Duration::from_hours(1))
+            .with_issuer(issuer)
+            .with_audience("client")
+            .with_subject("user");
+
+        let expires_at = Clock::now_since_epoch() - Duration::from_days(expired_days);
+        claims.issued_at = Some(expires_at - Duration::from_hours(1));
+        claims.expires_at = Some(expires_at);
+
+        key.sign(claims).unwrap()
+    }
+
+    /// Verify the hint like [verify_id_token_hint], without looking up the key
+    fn verify_hint(key: &ES256KeyPair, token: &str) -> Option<IdTokenHint> {
+        if !is_id_token(token) {
+            return None;
+        }
+
+        signing_keys::verify_with_key::<NoCustomClaims>(
+            &signing_key(key),
+            token,
+            Some(hint_verification_options(ISSUER)),
+        )
+        .ok()
+        .and_then(IdTokenHint::from_claims)
+    }
+
+    #[test]
+    fn expired_hint() {
+        let key = ES256KeyPair::generate();
+
+        assert_eq!(verify_hint(&key, &id_token(&key, ISSUER, 0)), Some(hint()));
+        assert_eq!(verify_hint(&key, &id_token(&key, ISSUER, 29)), Some(hint()));
+        assert_eq!(verify_hint(&key, &id_token(&key, ISSUER, 31)), None);
+    }
+
+    #[test]
+    fn hint_of_other_issuer() {
+        let key = ES256KeyPair::generate();
+        let token = id_token(&key, "https://other.example.com", 0);
+
+        assert_eq!(verify_hint(&key, &token), None);
+    }
+
+    #[test]
+    fn hint_not_an_id_token() {
+        let key = ES256KeyPair::generate();
+        let access_token = AccessToken {
+            token: "token".to_string(),
+            jti: Some("jti".to_string()),
+            client_id: "client".to_string(),
+            expires_at: i64::MAX / 2,
+            issued_at: 0,
+            user_id: Some("user".to_string()),
+            scopes: Some("openid".to_string()),
+            refresh_token: None,
+            auth_time: None,
+            session_id: None,
+        };
+        let token =
+            create_access_token_jwt(ISSUER.to_string(), &signing_key(&key), &access_token).unwrap();
+
+        assert!(!is_id_token(&token));
+        assert!(!is_id_token("token"));
+    }
+
+    #[test]
+    fn hint_without_subject() {
+        let claims = Claims::create(Duration::from_hours(1)).with_audience("client");
+        assert_eq!(IdTokenHint::from_claims(claims), None);
+    }
+
+    #[test]
+    fn client_from_hint() {
+        assert_eq!(
+            requesting_client(Some(&hint()), None).unwrap(),
+            Some("client".to_string())
+        );
+        assert_eq!(
+            requesting_client(Some(&hint()), Some("client")).unwrap(),
+            Some("client".to_string())
+        );
+        assert!(requesting_client(Some(&hint()), Some("other")).is_err());
+        assert_eq!(
+            requesting_client(None, Some("other")).unwrap(),
+            Some("other".to_string())
+        );
+        assert_eq!(requesting_client(None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn confirmation() {
+        let session = session();
+
+        assert!(is_confirmed(&session, Some(&hint()), None));
+        assert!(is_confirmed(&session, None, Some("session")));
+
+        // A hint for another user, or no hint at all, requires confirmation
+        let other_user = IdTokenHint {
+            user_id: "other".to_string(),
+            ..hint()
+        };
+        assert!(!is_confirmed(&session, Some(&other_user), None));
+        assert!(!is_confirmed(&session, None, None));
+        assert!(!is_confirmed(&session, None, Some("other-session")));
+    }
+
+    #[test]
+    fn redirect() {
+        let registered = vec![
+            "https://client.example.com/logged-out".to_string(),
+            "https://client.example.com/logged-out?app=1".to_string(),
+            "http://127.0.0.1/logged-out".to_string(),
+        ];
+
+        assert_eq!(
+            post_logout_redirect(
+                &registered,
+                false,
+                "https://client.example.com/logged-out",
+                None
+            ),
+            Some("https://client.example.com/logged-out".to_string())
+        );
+        assert_eq!(
+            post_logout_redirect(
+                &registered,
+                false,
+                "https://client.example.com/logged-out",
+                Some("abc")
+            ),
+            Some("https://client.example.com/logged-out?state=abc".to_string())
+        );
+        assert_eq!(
+            post_logout_redirect(
+                &registered,
+                false,
+                "https://client.example.com/logged-out?app=1",
+                Some("abc")
+            ),
+            Some("https://client.example.com/logged-out?app=1&state=abc".to_string())
+        );
+        assert_eq!(
+            post_logout_redirect(&registered, false, "https://evil.example.com/", None),
+            None
+        );
+        assert_eq!(
+            post_logout_redirect(&registered, false, "http://127.0.0.1:8080/logged-out", None),
+            None
+        );
+        assert_eq!(
+            post_logout_redirect(&registered, true, "http://127.0.0.1:8080/logged-out", None),
+            Some("http://127.0.0.1:8080/logged-out".to_string())
+        );
+    }
+}
@@ -1,14 +1,34 @@
+use crate::client_assertion;
+use crate::config::Config;
+use crate::rate_limit::RateLimitGroup;
+use crate::routes::rate_limited::RateLimited;
 use actix_route_config::Routable;
 use actix_web::body::BoxBody;
-use actix_web::http::StatusCode;
+use actix_web::http::header::ContentType;
+use actix_web::http::{header, StatusCode};
 use actix_web::web::ServiceConfig;
 use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
-use serde::Serialize;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use database::driver::Database;
+use database::oauth2_client::{OAuth2Client, TokenEndpointAuthMethod};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt::Display;
+use tap::TapFallible;
+use tracing::{debug, warn};
 
 mod authorize;
 mod introspect;
+mod logout;
+mod pkce;
+pub mod prompt;
+pub mod redirect_uri;
+mod register;
+mod revoke;
 mod token;
+mod userinfo;
 
 pub struct Router;
 
@@ -17,15 +37,307 @@ impl Routable for Router {
         config.service(
             web::scope("/oauth")
                 .route("/authorize", web::get().to(authorize::authorize))
-                .route("/token", web::post().to(token::token))
-                .route("/introspect", web::post().to(introspect::introspect)),
+                .service(
+                    web::resource("/token")
+                        .wrap(RateLimited::new(RateLimitGroup::Client))
+                        .route(web::post().to(token::token)),
+                )
+                .service(
+                    web::resource("/introspect")
+                        .wrap(RateLimited::new(RateLimitGroup::Client))
+                        .route(web::post().to(introspect::introspect)),
+                )
+                .service(
+                    web::resource("/revoke")
+                        .wrap(RateLimited::new(RateLimitGroup::Client))
+                        .route(web::post().to(revoke::revoke)),
+                )
+                // Dynamic client registration, RFC 7591 and RFC 7592
+                .service(
+                    web::resource("/register")
+                        .wrap(RateLimited::new(RateLimitGroup::Client))
+                        .route(web::post().to(register::register)),
+                )
+                .route("/register/{client_id}", web::get().to(register::read))
+                .route("/register/{client_id}", web::put().to(register::update))
+                .route("/register/{client_id}", web::delete().to(register::delete))
+                // Both methods are required by OpenID Connect Core 1.0, Section 5.3
+                .route("/userinfo", web::get().to(userinfo::userinfo))
+                .route("/userinfo", web::post().to(userinfo::userinfo))
+                // Both methods are required by OpenID Connect RP-Initiated Logout 1.0, Section 2
+                .route("/logout", web::get().to(logout::logout_get))
+                .route("/logout", web::post().to(logout::logout_post)),
         );
     }
 }
 
+/// The credentials a client authenticates with in the request body.
+/// Flattened into the forms of the endpoints requiring client authentication.
+#[derive(Deserialize)]
+pub struct ClientCredentials {
+    /// Not required if the client uses HTTP Basic authentication or a client assertion
+    client_id: Option<String>,
+    /// For the `client_secret_post` method
+    client_secret: Option<String>,
+    /// For the `private_key_jwt` method, must be [client_assertion::ASSERTION_TYPE]
+    client_assertion_type: Option<String>,
+    /// For the `private_key_jwt` method
+    client_assertion: Option<String>,
+}
+
+/// The credentials presented by a client, by authentication method
+enum PresentedCredentials<'a> {
+    ClientSecretBasic {
+        client_id: String,
+        client_secret: String,
+    },
+    ClientSecretPost {
+        client_id: &'a str,
+        client_secret: &'a str,
+    },
+    PrivateKeyJwt {
+        client_id: String,
+        assertion: &'a str,
+    },
+    None {
+        client_id: &'a str,
+    },
+}
+
+impl PresentedCredentials<'_> {
+    fn client_id(&self) -> &str {
+        match self {
+            Self::ClientSecretBasic { client_id, .. } => client_id,
+            Self::ClientSecretPost { client_id, .. } => client_id,
+            Self::PrivateKeyJwt { client_id, .. } => client_id,
+            Self::None { client_id } => client_id,
+        }
+    }
+
+    fn method(&self) -> TokenEndpointAuthMethod {
+        match self {
+            Self::ClientSecretBasic { .. } => TokenEndpointAuthMethod::ClientSecretBasic,
+            Self::ClientSecretPost { .. } => TokenEndpointAuthMethod::ClientSecretPost,
+            Self::PrivateKeyJwt { .. } => TokenEndpointAuthMethod::PrivateKeyJwt,
+            Self::None { .. } => TokenEndpointAuthMethod::None,
+        }
+    }
+}
+
+/// Authenticate the client making a request to one of the OAuth2 endpoints.
+/// The client must use the authentication method registered for it,
+/// except that clients with a secret may use either `client_secret_basic` or `client_secret_post`.
+///
+/// Public clients do not have a secret they can keep confidential,
+/// those clients use the `none` method and only identify themselves.
+///
+/// # Errors
+///
+/// - If the client uses more than one authentication method
+/// - If the client does not exist
+/// - If the client does not use an authentication method accepted for it
+/// - If the secret or client assertion is not valid
+/// - If the query fails
+///
+/// # Further reading
+/// - [RFC 6749, Section 2.3](https://datatracker.ietf.org/doc/html/rfc6749#section-2.3)
+/// - [OpenID Connect Core 1.0, Section 9](https://openid.net/specs/openid-connect-core-1_0.html#ClientAuthentication)
+async fn authenticate_client(
+    database: &Database,
+    config: &Config,
+    req: &HttpRequest,
+    credentials: &ClientCredentials,
+) -> Result<OAuth2Client, OAuth2ErrorKind> {
+    let presented = presented_credentials(req, credentials)?;
+
+    let client = OAuth2Client::get_by_client_id(database, presented.client_id())
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?
+        .ok_or(OAuth2ErrorKind::InvalidClient)?;
+
+    if !is_method_accepted(client.token_endpoint_auth_method, presented.method()) {
+        return Err(OAuth2ErrorKind::InvalidClient);
+    }
+
+    let authenticated = match presented {
+        PresentedCredentials::ClientSecretBasic { client_secret, .. } => {
+            client.verify_secret(&client_secret)
+        }
+        PresentedCredentials::ClientSecretPost { client_secret, .. } => {
+            client.verify_secret(client_secret)
+        }
+        PresentedCredentials::PrivateKeyJwt { assertion, .. } => {
+            verify_client_assertion(database, config, &client, assertion).await?
+        }
+        PresentedCredentials::None { .. } => true,
+    };
+
+    if !authenticated {
+        return Err(OAuth2ErrorKind::InvalidClient);
+    }
+
+    Ok(client)
+}
+
+/// Whether a client registered with the `registered` method may authenticate with the `presented` method.
+/// Clients with a secret may send it either way, as libraries differ in the method they use by default.
+/// The other methods must be used exactly as registered.
+fn is_method_accepted(
+    registered: TokenEndpointAuthMethod,
+    presented: TokenEndpointAuthMethod,
+) -> bool {
+    registered == presented || (registered.uses_secret() && presented.uses_secret())
+}
+
+/// Determine which authentication method the client used.
+/// Clients must not use more than one method, RFC 6749 Section 2.3.
+fn presented_credentials<'a>(
+    req: &HttpRequest,
+    credentials: &'a ClientCredentials,
+) -> Result<PresentedCredentials<'a>, OAuth2ErrorKind> {
+    // Some libraries send an empty secret for public clients
+    let client_secret = credentials
+        .client_secret
+        .as_deref()
+        .filter(|s| !s.is_empty());
+
+    if let Some((client_id, basic_secret)) = basic_credentials(req)? {
+        let conflicting_client_id = credentials
+            .client_id
+            .as_ref()
+            .is_some_and(|id| id.ne(&client_id));
+
+        if client_secret.is_some()
+            || credentials.client_assertion.is_some()
+            || conflicting_client_id
+        {
+            return Err(OAuth2ErrorKind::InvalidRequest);
+        }
+
+        return Ok(PresentedCredentials::ClientSecretBasic {
+            client_id,
+            client_secret: basic_secret,
+        });
+    }
+
+    if let Some(assertion) = &credentials.client_assertion {
+        if client_secret.is_some()
+            || credentials.client_assertion_type.as_deref()
+                != Some(client_assertion::ASSERTION_TYPE)
+        {
+            return Err(OAuth2ErrorKind::InvalidRequest);
+        }
+
+        // The client ID is optional, it is the issuer of the assertion
+        let client_id = match &credentials.client_id {
+            Some(client_id) => client_id.clone(),
+            None => client_assertion::unverified_issuer(assertion)
+                .ok_or(OAuth2ErrorKind::InvalidClient)?,
+        };
+
+        return Ok(PresentedCredentials::PrivateKeyJwt {
+            client_id,
+            assertion,
+        });
+    }
+
+    let client_id = credentials
+        .client_id
+        .as_deref()
+        .ok_or(OAuth2ErrorKind::InvalidRequest)?;
+
+    Ok(match client_secret {
+        Some(client_secret) => PresentedCredentials::ClientSecretPost {
+            client_id,
+            client_secret,
+        },
+        None => PresentedCredentials::None { client_id },
+    })
+}
+
+/// Get the client ID and secret from the `Authorization` header, if the `Basic` scheme is used.
+/// Both are form-urlencoded before being base64 encoded, RFC 6749 Section 2.3.1.
+fn basic_credentials(req: &HttpRequest) -> Result<Option<(String, String)>, OAuth2ErrorKind> {
+    let Some(header) = req.headers().get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+
+    let (scheme, encoded) = header
+        .to_str()
+        .ok()
+        .and_then(|v| v.split_once(' '))
+        .ok_or(OAuth2ErrorKind::InvalidClient)?;
+
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return Ok(None);
+    }
+
+    let decoded = BASE64_STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|d| String::from_utf8(d).ok())
+        .ok_or(OAuth2ErrorKind::InvalidClient)?;
+
+    let (client_id, client_secret) = decoded
+        .split_once(':')
+        .ok_or(OAuth2ErrorKind::InvalidClient)?;
+
+    Ok(Some((
+        form_urldecode(client_id)?,
+        form_urldecode(client_secret)?,
+    )))
+}
+
+fn form_urldecode(value: &str) -> Result<String, OAuth2ErrorKind> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .map(|v| v.into_owned())
+        .map_err(|_| OAuth2ErrorKind::InvalidClient)
+}
+
+/// Verify a client assertion for the `private_key_jwt` method, and record it to prevent replay.
+/// The audience may be the issuer, or the URL of the token or revocation endpoint.
+async fn verify_client_assertion(
+    database: &Database,
+    config: &Config,
+    client: &OAuth2Client,
+    assertion: &str,
+) -> Result<bool, OAuth2ErrorKind> {
+    let Some(jwks) = &client.jwks else {
+        return Ok(false);
+    };
+
+    let http = &config.http;
+    let audiences = HashSet::from([
+        config.oidc_issuer.clone(),
+        config.endpoint_url(&http.token_endpoint, "/api/oauth/token"),
+        config.endpoint_url(&http.revocation_endpoint, "/api/oauth/revoke"),
+    ]);
+
+    let verified = match client_assertion::verify(jwks, assertion, &client.client_id, audiences) {
+        Ok(verified) => verified,
+        Err(e) => {
+            debug!("Rejected client assertion of {}: {e}", client.client_id);
+            return Ok(false);
+        }
+    };
+
+    client
+        .record_assertion(database, &verified.jti, verified.remember_until)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)
+}
+
 pub enum OAuth2AuthorizationResponse<T: Responder> {
     Ok(T),
     Err(OAuth2Error),
+    /// An error shown to the user instead of returned to the client,
+    /// because the client or redirect URI could not be validated.
+    /// Redirecting to an unvalidated redirect URI would make Wilford an open redirector,
+    /// RFC 6749 Section 4.1.2.1.
+    NotRedirected(OAuth2ErrorKind),
 }
 
 impl<T: Responder<Body = BoxBody>> Responder for OAuth2AuthorizationResponse<T> {
@@ -54,6 +366,16 @@ impl<T: Responder<Body = BoxBody>> Responder for OAuth2AuthorizationResponse<T>
                     .insert_header(("Location", url))
                     .finish()
             }
+            Self::NotRedirected(kind) => {
+                let status = match kind {
+                    OAuth2ErrorKind::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+                    _ => StatusCode::BAD_REQUEST,
+                };
+
+                HttpResponse::build(status)
+                    .content_type(ContentType::plaintext())
+                    .body(format!("Invalid authorization request: {kind}"))
+            }
         }
     }
 }
@@ -82,6 +404,8 @@ impl OAuth2Error {
 #[allow(unused)]
 pub enum OAuth2ErrorKind {
     InvalidRequest,
+    /// Client authentication failed, RFC 6749 Section 5.2
+    InvalidClient,
     UnauthorizedClient,
     AccessDenied,
     UnsupportedResponseType,
@@ -89,6 +413,12 @@ pub enum OAuth2ErrorKind {
     ServerError,
     InvalidGrant,
     UnsupportedGrantType,
+    /// The request required user authentication, but `prompt=none` was requested.
+    /// OpenID Connect Core Section 3.1.2.6
+    LoginRequired,
+    /// The request required user consent, but `prompt=none` was requested.
+    /// OpenID Connect Core Section 3.1.2.6
+    ConsentRequired,
 }
 
 impl Display for OAuth2ErrorKind {
@@ -98,6 +428,7 @@ impl Display for OAuth2ErrorKind {
             "{}",
             match self {
                 Self::InvalidRequest => "invalid_request",
+                Self::InvalidClient => "invalid_client",
                 Self::UnauthorizedClient => "unauthorized_client",
                 Self::AccessDenied => "access_denied",
                 Self::UnsupportedResponseType => "unsupported_response_type",
@@ -105,6 +436,8 @@ impl Display for OAuth2ErrorKind {
                 Self::ServerError => "server_error",
                 Self::InvalidGrant => "invalid_grant",
                 Self::UnsupportedGrantType => "unsupported_grant_type",
+                Self::LoginRequired => "login_required",
+                Self::ConsentRequired => "consent_required",
             }
         )
     }
@@ -112,7 +445,10 @@ impl Display for OAuth2ErrorKind {
 
 impl ResponseError for OAuth2ErrorKind {
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        match self {
+            Self::InvalidClient => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        }
     }
 
     fn error_response(&self) -> HttpResponse<BoxBody> {
@@ -121,8 +457,53 @@ impl ResponseError for OAuth2ErrorKind {
             error: String,
         }
 
-        HttpResponse::build(self.status_code()).json(&Response {
+        let mut response = HttpResponse::build(self.status_code());
+
+        // Clients may have used HTTP Basic authentication, RFC 6749 Section 5.2
+        if let Self::InvalidClient = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Basic"));
+        }
+
+        response.json(&Response {
             error: self.to_string(),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use TokenEndpointAuthMethod as Method;
+
+    #[test]
+    fn accepted_methods() {
+        assert!(is_method_accepted(
+            Method::ClientSecretBasic,
+            Method::ClientSecretBasic
+        ));
+        assert!(is_method_accepted(
+            Method::ClientSecretBasic,
+            Method::ClientSecretPost
+        ));
+        assert!(is_method_accepted(
+            Method::ClientSecretPost,
+            Method::ClientSecretBasic
+        ));
+        assert!(is_method_accepted(
+            Method::PrivateKeyJwt,
+            Method::PrivateKeyJwt
+        ));
+        assert!(is_method_accepted(Method::None, Method::None));
+
+        assert!(!is_method_accepted(
+            Method::ClientSecretBasic,
+            Method::PrivateKeyJwt
+        ));
+        assert!(!is_method_accepted(Method::ClientSecretPost, Method::None));
+        assert!(!is_method_accepted(
+            Method::PrivateKeyJwt,
+            Method::ClientSecretBasic
+        ));
+        assert!(!is_method_accepted(Method::None, Method::ClientSecretPost));
+    }
+}
@@ -0,0 +1,80 @@
+//! Proof Key for Code Exchange (PKCE) in accordance with RFC7636
+
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use database::oauth2_client::{CodeChallenge, CodeChallengeMethod};
+use sha2::{Digest, Sha256};
+
+/// Check that a code verifier or code challenge is well-formed.
+/// Both must be between 43 and 128 characters long, and consist of unreserved characters only.
+///
+/// # Further reading
+/// - [RFC 7636, Section 4.1](https://datatracker.ietf.org/doc/html/rfc7636#section-4.1)
+pub fn is_well_formed(value: &str) -> bool {
+    (43..=128).contains(&value.len())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+/// Verify the code verifier provided to the token endpoint
+/// against the code challenge provided in the authorization request.
+///
+/// # Further reading
+/// - [RFC 7636, Section 4.6](https://datatracker.ietf.org/doc/html/rfc7636#section-4.6)
+pub fn verify(challenge: &CodeChallenge, code_verifier: &str) -> bool {
+    if !is_well_formed(code_verifier) {
+        return false;
+    }
+
+    match challenge.method {
+        CodeChallengeMethod::Plain => challenge.challenge.eq(code_verifier),
+        CodeChallengeMethod::S256 => {
+            let digest = Sha256::digest(code_verifier.as_bytes());
+            BASE64_URL_SAFE_NO_PAD
+                .encode(digest)
+                .eq(&challenge.challenge)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Example from RFC 7636, Appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    #[test]
+    fn s256() {
+        let challenge = CodeChallenge {
+            challenge: CHALLENGE.to_string(),
+            method: CodeChallengeMethod::S256,
+        };
+
+        assert!(verify(&challenge, VERIFIER));
+        assert!(!verify(&challenge, CHALLENGE));
+    }
+
+    #[test]
+    fn plain() {
+        let challenge = CodeChallenge {
+            challenge: VERIFIER.to_string(),
+            method: CodeChallengeMethod::Plain,
+        };
+
+        assert!(verify(&challenge, VERIFIER));
+        assert!(!verify(&challenge, CHALLENGE));
+    }
+
+    #[test]
+    fn malformed_verifier() {
+        let challenge = CodeChallenge {
+            challenge: "too-short".to_string(),
+            method: CodeChallengeMethod::Plain,
+        };
+
+        assert!(!verify(&challenge, "too-short"));
+    }
+}
@@ -0,0 +1,54 @@
+//! The OpenID Connect `prompt` authorization request parameter
+
+/// Prompt value requesting that no user interaction takes place
+pub const NONE: &str = "none";
+/// Prompt value requesting that the user logs in again, even if they are logged in already
+pub const LOGIN: &str = "login";
+/// Prompt value requesting that the user is asked for consent, even if consent was granted before
+pub const CONSENT: &str = "consent";
+/// Prompt value requesting that the user selects an account.
+/// As Wilford has no account chooser, the user is asked to log in again instead.
+pub const SELECT_ACCOUNT: &str = "select_account";
+
+/// All supported prompt values
+pub const KNOWN: [&str; 4] = [NONE, LOGIN, CONSENT, SELECT_ACCOUNT];
+
+/// Check that the `prompt` parameter is valid.
+/// It is a space separated list of known values, and `none` may not be combined with other values.
+///
+/// # Further reading
+/// - [OpenID Connect Core, Section 3.1.2.1](https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest)
+pub fn is_valid(prompt: &str) -> bool {
+    let values = prompt.split(' ').collect::<Vec<_>>();
+
+    values.iter().all(|v| KNOWN.contains(v)) && (!values.contains(&NONE) || values.len() == 1)
+}
+
+/// Check whether the `prompt` parameter contains the value.
+pub fn contains(prompt: Option<&str>, value: &str) -> bool {
+    prompt
+        .map(|p| p.split(' ').any(|v| v.eq(value)))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        assert!(is_valid("none"));
+        assert!(is_valid("consent"));
+        assert!(is_valid("login consent"));
+        assert!(!is_valid("none consent"));
+        assert!(!is_valid("unknown"));
+        assert!(!is_valid(""));
+    }
+
+    #[test]
+    fn contains_value() {
+        assert!(contains(Some("login consent"), CONSENT));
+        assert!(!contains(Some("login"), CONSENT));
+        assert!(!contains(None, CONSENT));
+    }
+}
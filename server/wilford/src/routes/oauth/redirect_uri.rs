@@ -0,0 +1,163 @@
+//! Matching of redirect URIs against the redirect URIs registered for a client
+
+use std::collections::HashSet;
+use url::{Host, Url};
+
+/// The maximum length of a redirect URI
+const MAX_LENGTH: usize = 512;
+
+/// Check whether a URI may be registered as redirect URI:
+/// an absolute URI without fragment.
+///
+/// # Further reading
+/// - [RFC 6749, Section 3.1.2](https://datatracker.ietf.org/doc/html/rfc6749#section-3.1.2)
+pub fn is_valid(uri: &str) -> bool {
+    uri.len() <= MAX_LENGTH && Url::parse(uri).is_ok_and(|u| u.fragment().is_none())
+}
+
+/// Remove duplicate redirect URIs, keeping the first occurrence.
+pub fn dedup(uris: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    uris.retain(|uri| seen.insert(uri.clone()));
+}
+
+/// Check whether the redirect URI provided in an authorization request
+/// is one of the redirect URIs registered for the client.
+///
+/// Redirect URIs are compared exactly. If `allow_loopback_any_port` is set,
+/// a loopback redirect URI also matches a registered loopback redirect URI with a different port.
+///
+/// # Further reading
+/// - [RFC 6749, Section 3.1.2.3](https://datatracker.ietf.org/doc/html/rfc6749#section-3.1.2.3)
+/// - [RFC 8252, Section 7.3](https://datatracker.ietf.org/doc/html/rfc8252#section-7.3)
+pub fn is_registered(
+    registered: &[String],
+    redirect_uri: &str,
+    allow_loopback_any_port: bool,
+) -> bool {
+    registered.iter().any(|r| {
+        r.eq(redirect_uri) || (allow_loopback_any_port && loopback_matches(r, redirect_uri))
+    })
+}
+
+/// Check whether both URIs are loopback redirect URIs that are equal, except for the port.
+fn loopback_matches(registered: &str, redirect_uri: &str) -> bool {
+    let (Ok(mut registered), Ok(mut redirect_uri)) =
+        (Url::parse(registered), Url::parse(redirect_uri))
+    else {
+        return false;
+    };
+
+    if !is_loopback(&registered) || !is_loopback(&redirect_uri) {
+        return false;
+    }
+
+    // Cannot fail, as loopback URIs always have a host
+    let _ = registered.set_port(None);
+    let _ = redirect_uri.set_port(None);
+
+    registered.eq(&redirect_uri)
+}
+
+/// Loopback redirect URIs use the `http` scheme and a loopback IP literal.
+/// The `localhost` hostname is not considered a loopback, see RFC 8252 Section 8.3.
+fn is_loopback(uri: &Url) -> bool {
+    uri.scheme().eq("http")
+        && match uri.host() {
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            _ => false,
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registered() -> Vec<String> {
+        vec![
+            "https://example.com/callback".to_string(),
+            "http://127.0.0.1:8080/callback".to_string(),
+            "http://[::1]/callback".to_string(),
+        ]
+    }
+
+    #[test]
+    fn exact() {
+        assert!(is_registered(
+            &registered(),
+            "https://example.com/callback",
+            false
+        ));
+        assert!(is_registered(
+            &registered(),
+            "http://127.0.0.1:8080/callback",
+            false
+        ));
+        assert!(!is_registered(
+            &registered(),
+            "https://example.com/other",
+            false
+        ));
+        assert!(!is_registered(
+            &registered(),
+            "https://example.com:8443/callback",
+            true
+        ));
+    }
+
+    #[test]
+    fn valid() {
+        assert!(is_valid("https://example.com/callback"));
+        assert!(is_valid("http://127.0.0.1:8080/callback?query=1"));
+        assert!(is_valid("com.example.app:/callback"));
+        assert!(!is_valid("/callback"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("https://example.com/callback#fragment"));
+        assert!(!is_valid(&format!(
+            "https://example.com/{}",
+            "a".repeat(512)
+        )));
+    }
+
+    #[test]
+    fn dedup_keeps_order() {
+        let mut uris = vec![
+            "https://example.com/b".to_string(),
+            "https://example.com/a".to_string(),
+            "https://example.com/b".to_string(),
+        ];
+        dedup(&mut uris);
+
+        assert_eq!(uris, vec!["https://example.com/b", "https://example.com/a"]);
+    }
+
+    #[test]
+    fn loopback_any_port() {
+        assert!(is_registered(
+            &registered(),
+            "http://127.0.0.1:51234/callback",
+            true
+        ));
+        assert!(is_registered(
+            &registered(),
+            "http://[::1]:51234/callback",
+            true
+        ));
+        assert!(!is_registered(
+            &registered(),
+            "http://127.0.0.1:51234/callback",
+            false
+        ));
+        assert!(!is_registered(
+            &registered(),
+            "http://127.0.0.1:51234/other",
+            true
+        ));
+        assert!(!is_registered(
+            &registered(),
+            "http://localhost:51234/callback",
+            true
+        ));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::client_assertion;
+use crate::routes::oauth::redirect_uri;
+use crate::routes::oauth::register::RegistrationError;
+use database::oauth2_client::{
+    AuthorizationType, GrantType, OAuth2ClientOptions, TokenEndpointAuthMethod,
+};
+use database::signing_key::JwtSigningAlgorithm;
+use serde::Deserialize;
+use url::Url;
+
+/// Client metadata provided when registering or updating a client.
+/// Unknown metadata is ignored.
+///
+/// # Further reading
+/// - [RFC 7591, Section 2](https://datatracker.ietf.org/doc/html/rfc7591#section-2)
+/// - [OpenID Connect Dynamic Client Registration 1.0, Section 2](https://openid.net/specs/openid-connect-registration-1_0.html#ClientMetadata)
+#[derive(Deserialize)]
+pub struct ClientMetadata {
+    #[serde(default)]
+    redirect_uris: Vec<String>,
+    client_name: Option<String>,
+    /// Defaults to `authorization_code`
+    grant_types: Option<Vec<String>>,
+    /// Defaults to the response types matching the grant types
+    response_types: Option<Vec<String>>,
+    /// Defaults to `client_secret_basic`
+    token_endpoint_auth_method: Option<String>,
+    logo_uri: Option<String>,
+    #[serde(default)]
+    contacts: Vec<String>,
+    jwks: Option<serde_json::Value>,
+    /// Not supported, clients must provide their `jwks` instead
+    jwks_uri: Option<String>,
+    /// Defaults to `RS256`
+    id_token_signed_response_alg: Option<String>,
+    #[serde(default)]
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+    /// Required when updating a client, RFC 7592 Section 2.2
+    pub client_id: Option<String>,
+    /// Must be the current secret if provided when updating a client, RFC 7592 Section 2.2
+    pub client_secret: Option<String>,
+}
+
+/// Client metadata that was checked for validity and consistency
+pub struct ValidatedMetadata {
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub options: OAuth2ClientOptions,
+}
+
+impl ClientMetadata {
+    /// Check the metadata, and apply it to the settings of the client.
+    /// Settings which cannot be registered, e.g. the access token format, are taken from `base`.
+    ///
+    /// # Errors
+    ///
+    /// If the metadata is invalid or inconsistent
+    pub fn validate(
+        mut self,
+        base: OAuth2ClientOptions,
+    ) -> Result<ValidatedMetadata, RegistrationError> {
+        redirect_uri::dedup(&mut self.redirect_uris);
+        redirect_uri::dedup(&mut self.post_logout_redirect_uris);
+
+        let name = self
+            .client_name
+            .filter(|n| !n.trim().is_empty() && n.len() <= 64)
+            .ok_or(RegistrationError::InvalidClientMetadata(
+                "client_name is required, and may be at most 64 characters",
+            ))?;
+
+        let token_endpoint_auth_method = match &self.token_endpoint_auth_method {
+            Some(method) => TokenEndpointAuthMethod::from_name(method).ok_or(
+                RegistrationError::InvalidClientMetadata("Unsupported token_endpoint_auth_method"),
+            )?,
+            None => TokenEndpointAuthMethod::ClientSecretBasic,
+        };
+
+        let grant_types = match &self.grant_types {
+            Some(grant_types) => grant_types
+                .iter()
+                .map(|g| GrantType::from_name(g))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(RegistrationError::InvalidClientMetadata(
+                    "Unsupported grant type",
+                ))?,
+            None => vec![GrantType::AuthorizationCode],
+        };
+
+        let response_types = match &self.response_types {
+            Some(response_types) => response_types
+                .iter()
+                .map(|r| AuthorizationType::from_response_type(r))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(RegistrationError::InvalidClientMetadata(
+                    "Unsupported response type",
+                ))?,
+            None => default_response_types(&grant_types),
+        };
+
+        if !is_consistent(&grant_types, &response_types) {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "grant_types and response_types are inconsistent",
+            ));
+        }
+
+        // Public clients cannot authenticate themselves
+        let is_public = token_endpoint_auth_method == TokenEndpointAuthMethod::None;
+        if is_public && grant_types.contains(&GrantType::ClientCredentials) {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "The client_credentials grant requires client authentication",
+            ));
+        }
+
+        let redirect_uris_required = !response_types.is_empty();
+        if (redirect_uris_required && self.redirect_uris.is_empty())
+            || !self.redirect_uris.iter().all(|u| redirect_uri::is_valid(u))
+        {
+            return Err(RegistrationError::InvalidRedirectUri);
+        }
+
+        if !self
+            .post_logout_redirect_uris
+            .iter()
+            .all(|u| redirect_uri::is_valid(u))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "Invalid post_logout_redirect_uris",
+            ));
+        }
+
+        let other_uris_valid = self
+            .logo_uri
+            .iter()
+            .chain(&self.backchannel_logout_uri)
+            .all(|u| is_valid_uri(u));
+
+        if !other_uris_valid {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "Invalid logo_uri or backchannel_logout_uri",
+            ));
+        }
+
+        if self.contacts.iter().any(|c| c.trim().is_empty()) {
+            return Err(RegistrationError::InvalidClientMetadata("Invalid contacts"));
+        }
+
+        if self.jwks_uri.is_some() {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "jwks_uri is not supported, provide jwks instead",
+            ));
+        }
+
+        let jwks = self.jwks.map(|jwks| jwks.to_string());
+        let jwks_valid = match &jwks {
+            Some(jwks) => client_assertion::validate_jwks(jwks).is_ok(),
+            None => token_endpoint_auth_method != TokenEndpointAuthMethod::PrivateKeyJwt,
+        };
+
+        if !jwks_valid {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "A valid jwks is required for private_key_jwt",
+            ));
+        }
+
+        let id_token_signed_response_alg = match &self.id_token_signed_response_alg {
+            Some(alg) => JwtSigningAlgorithm::ALL
+                .into_iter()
+                .find(|a| a.name() == alg)
+                .ok_or(RegistrationError::InvalidClientMetadata(
+                    "Unsupported id_token_signed_response_alg",
+                ))?,
+            None => JwtSigningAlgorithm::RS256,
+        };
+
+        Ok(ValidatedMetadata {
+            name,
+            redirect_uris: self.redirect_uris,
+            options: OAuth2ClientOptions {
+                is_public,
+                id_token_signed_response_alg,
+                post_logout_redirect_uris: self.post_logout_redirect_uris,
+                backchannel_logout_uri: self.backchannel_logout_uri,
+                token_endpoint_auth_method,
+                jwks,
+                grant_types,
+                response_types,
+                logo_uri: self.logo_uri,
+                contacts: self.contacts,
+                ..base
+            },
+        })
+    }
+}
+
+/// The response types a client uses, if it did not register any
+fn default_response_types(grant_types: &[GrantType]) -> Vec<AuthorizationType> {
+    let mut response_types = Vec::new();
+    if grant_types.contains(&GrantType::AuthorizationCode) {
+        response_types.push(AuthorizationType::AuthorizationCode);
+    }
+
+    if grant_types.contains(&GrantType::Implicit) {
+        response_types.push(AuthorizationType::Implicit);
+    }
+
+    response_types
+}
+
+/// Whether the response types match the grant types, RFC 7591 Section 2.1.
+/// A refresh token can only be obtained with the authorization code grant.
+fn is_consistent(grant_types: &[GrantType], response_types: &[AuthorizationType]) -> bool {
+    let code_grant = grant_types.contains(&GrantType::AuthorizationCode);
+    let implicit_grant = grant_types.contains(&GrantType::Implicit);
+    let refresh_grant = grant_types.contains(&GrantType::RefreshToken);
+
+    let code_response = response_types.contains(&AuthorizationType::AuthorizationCode);
+    let implicit_response = response_types.contains(&AuthorizationType::Implicit)
+        || response_types.contains(&AuthorizationType::IdToken);
+
+    code_grant == code_response
+        && implicit_grant == implicit_response
+        && (!refresh_grant || code_grant)
+}
+
+/// Whether the URI is an absolute URI without fragment, RFC 6749 Section 3.1.2
+fn is_valid_uri(uri: &str) -> bool {
+    uri.len() <= 512 && Url::parse(uri).is_ok_and(|u| u.fragment().is_none())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata(json: serde_json::Value) -> ClientMetadata {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let validated = metadata(serde_json::json!({
+            "client_name": "Client",
+            "redirect_uris": ["https://client.example.com/callback"],
+        }))
+        .validate(OAuth2ClientOptions::default())
+        .unwrap();
+
+        assert_eq!(
+            validated.options.grant_types,
+            vec![GrantType::AuthorizationCode]
+        );
+        assert_eq!(
+            validated.options.response_types,
+            vec![AuthorizationType::AuthorizationCode]
+        );
+        assert_eq!(
+            validated.options.token_endpoint_auth_method,
+            TokenEndpointAuthMethod::ClientSecretBasic
+        );
+        assert!(!validated.options.is_public);
+    }
+
+    #[test]
+    fn inconsistent_types() {
+        let result = metadata(serde_json::json!({
+            "client_name": "Client",
+            "redirect_uris": ["https://client.example.com/callback"],
+            "grant_types": ["client_credentials"],
+            "response_types": ["code"],
+        }))
+        .validate(OAuth2ClientOptions::default());
+
+        assert!(matches!(
+            result,
+            Err(RegistrationError::InvalidClientMetadata(_))
+        ));
+    }
+
+    #[test]
+    fn client_credentials_without_redirect_uris() {
+        let validated = metadata(serde_json::json!({
+            "client_name": "Service",
+            "grant_types": ["client_credentials"],
+        }))
+        .validate(OAuth2ClientOptions::default())
+        .unwrap();
+
+        assert!(validated.options.response_types.is_empty());
+    }
+
+    #[test]
+    fn public_client() {
+        let validated = metadata(serde_json::json!({
+            "client_name": "App",
+            "redirect_uris": ["com.example.app:/callback"],
+            "grant_types": ["authorization_code", "refresh_token"],
+            "token_endpoint_auth_method": "none",
+        }))
+        .validate(OAuth2ClientOptions::default())
+        .unwrap();
+
+        assert!(validated.options.is_public);
+
+        // Public clients cannot use the client credentials grant
+        let result = metadata(serde_json::json!({
+            "client_name": "App",
+            "grant_types": ["client_credentials"],
+            "token_endpoint_auth_method": "none",
+        }))
+        .validate(OAuth2ClientOptions::default());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn duplicate_redirect_uris() {
+        let validated = metadata(serde_json::json!({
+            "client_name": "Client",
+            "redirect_uris": [
+                "https://client.example.com/callback",
+                "https://client.example.com/callback",
+            ],
+        }))
+        .validate(OAuth2ClientOptions::default())
+        .unwrap();
+
+        assert_eq!(
+            validated.redirect_uris,
+            vec!["https://client.example.com/callback"]
+        );
+    }
+
+    #[test]
+    fn invalid_redirect_uri() {
+        let result = metadata(serde_json::json!({
+            "client_name": "Client",
+            "redirect_uris": ["https://client.example.com/callback#fragment"],
+        }))
+        .validate(OAuth2ClientOptions::default());
+
+        assert!(matches!(result, Err(RegistrationError::InvalidRedirectUri)));
+    }
+}
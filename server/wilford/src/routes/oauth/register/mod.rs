@@ -0,0 +1,370 @@
+//! Dynamic client registration.
+//!
+//! Clients are registered with an initial access token, a constant access token.
+//! The registered client receives a registration access token,
+//! with which it can read, update and delete its registration.
+//!
+//! # Further reading
+//! - [RFC 7591](https://datatracker.ietf.org/doc/html/rfc7591)
+//! - [RFC 7592](https://datatracker.ietf.org/doc/html/rfc7592)
+
+use crate::response_types::Uncached;
+use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::auth::ConstantAccessTokenAuth;
+use actix_web::body::BoxBody;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, CustomizeResponder, HttpRequest, HttpResponse, Responder, ResponseError};
+use database::driver::{is_unique_violation, Database};
+use database::oauth2_client::{
+    AuthorizationType, GrantType, OAuth2Client, OAuth2ClientOptions, TokenEndpointAuthMethod,
+};
+use database::signing_key::JwtSigningAlgorithm;
+use metadata::ClientMetadata;
+use serde::Serialize;
+use std::fmt::Display;
+use tap::TapFallible;
+use tracing::warn;
+
+mod metadata;
+
+/// The client information response.
+///
+/// # Further reading
+/// - [RFC 7591, Section 3.2.1](https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.1)
+#[derive(Serialize)]
+pub struct ClientInformation {
+    client_id: String,
+    /// Only returned when the client is registered, as only its hash is stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+    /// Client secrets do not expire, `0`. Returned alongside the secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret_expires_at: Option<i64>,
+    /// Only returned when the client is registered, as only its hash is stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registration_access_token: Option<String>,
+    /// The URL at which the client can manage its registration
+    registration_client_uri: String,
+    client_name: String,
+    redirect_uris: Vec<String>,
+    grant_types: Vec<GrantType>,
+    response_types: Vec<AuthorizationType>,
+    token_endpoint_auth_method: TokenEndpointAuthMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logo_uri: Option<String>,
+    contacts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jwks: Option<serde_json::Value>,
+    id_token_signed_response_alg: JwtSigningAlgorithm,
+    post_logout_redirect_uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backchannel_logout_uri: Option<String>,
+}
+
+/// Register a new client.
+///
+/// # Errors
+///
+/// - If the initial access token is not valid
+/// - If the metadata is invalid or inconsistent
+/// - If the name is already used
+/// - If the operation fails
+pub async fn register(
+    database: WDatabase,
+    config: WConfig,
+    _: ConstantAccessTokenAuth,
+    metadata: web::Json<ClientMetadata>,
+) -> Result<Uncached<CustomizeResponder<web::Json<ClientInformation>>>, RegistrationError> {
+    let metadata = metadata
+        .into_inner()
+        .validate(OAuth2ClientOptions::default())?;
+
+    let (mut client, client_secret) = OAuth2Client::new(
+        &database,
+        metadata.name,
+        &metadata.redirect_uris,
+        false,
+        metadata.options,
+    )
+    .await
+    .map_err(name_error)?;
+
+    let registration_access_token = client
+        .new_registration_access_token(&database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?;
+
+    // Clients not authenticating with their secret have no use for it
+    let uses_secret = matches!(
+        client.token_endpoint_auth_method,
+        TokenEndpointAuthMethod::ClientSecretBasic | TokenEndpointAuthMethod::ClientSecretPost
+    );
+
+    let information = client_information(
+        &database,
+        &config,
+        &client,
+        uses_secret.then_some(client_secret),
+        Some(registration_access_token),
+    )
+    .await?;
+
+    Ok(Uncached::new(
+        web::Json(information)
+            .customize()
+            .with_status(StatusCode::CREATED),
+    ))
+}
+
+/// Read the registration of a client.
+///
+/// # Errors
+///
+/// - If the registration access token is not valid for the client
+/// - If the operation fails
+pub async fn read(
+    database: WDatabase,
+    config: WConfig,
+    req: HttpRequest,
+    client_id: web::Path<String>,
+) -> Result<Uncached<web::Json<ClientInformation>>, RegistrationError> {
+    let client = registered_client(&database, &req, &client_id).await?;
+
+    Ok(Uncached::new(web::Json(
+        client_information(&database, &config, &client, None, None).await?,
+    )))
+}
+
+/// Replace the metadata of a client.
+/// A public client cannot become confidential, nor the other way around.
+///
+/// # Errors
+///
+/// - If the registration access token is not valid for the client
+/// - If the `client_id` in the metadata is not the client's ID
+/// - If the `client_secret` in the metadata is not the client's secret
+/// - If the metadata is invalid or inconsistent
+/// - If the name is used by another client
+/// - If the operation fails
+pub async fn update(
+    database: WDatabase,
+    config: WConfig,
+    req: HttpRequest,
+    client_id: web::Path<String>,
+    metadata: web::Json<ClientMetadata>,
+) -> Result<Uncached<web::Json<ClientInformation>>, RegistrationError> {
+    let mut client = registered_client(&database, &req, &client_id).await?;
+
+    if metadata.client_id.as_ref() != Some(&client.client_id) {
+        return Err(RegistrationError::InvalidClientMetadata(
+            "client_id must be the ID of the client",
+        ));
+    }
+
+    if let Some(client_secret) = &metadata.client_secret {
+        if !client.verify_secret(client_secret) {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "client_secret must be the secret of the client",
+            ));
+        }
+    }
+
+    let options = client
+        .options(&database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?;
+
+    let metadata = metadata.into_inner().validate(options)?;
+
+    // The client would be left without a secret, or with a secret it cannot keep
+    if metadata.options.is_public != client.is_public {
+        return Err(RegistrationError::InvalidClientMetadata(
+            "token_endpoint_auth_method cannot be changed from or to none",
+        ));
+    }
+
+    client
+        .update(
+            &database,
+            metadata.name,
+            &metadata.redirect_uris,
+            metadata.options,
+        )
+        .await
+        .map_err(name_error)?;
+
+    Ok(Uncached::new(web::Json(
+        client_information(&database, &config, &client, None, None).await?,
+    )))
+}
+
+/// Delete a client.
+///
+/// # Errors
+///
+/// - If the registration access token is not valid for the client
+/// - If the operation fails
+pub async fn delete(
+    database: WDatabase,
+    req: HttpRequest,
+    client_id: web::Path<String>,
+) -> Result<HttpResponse, RegistrationError> {
+    let client = registered_client(&database, &req, &client_id).await?;
+
+    client
+        .delete(&database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Get the client with the ID, if the request contains its registration access token.
+/// Unknown clients are treated the same as an invalid token, RFC 7592 Section 2.1.
+async fn registered_client(
+    database: &Database,
+    req: &HttpRequest,
+    client_id: &str,
+) -> Result<OAuth2Client, RegistrationError> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(RegistrationError::InvalidToken)?;
+
+    let client = OAuth2Client::get_by_client_id(database, client_id)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?
+        .ok_or(RegistrationError::InvalidToken)?;
+
+    if !client.verify_registration_access_token(token) {
+        return Err(RegistrationError::InvalidToken);
+    }
+
+    Ok(client)
+}
+
+/// Map the error of creating or updating a client.
+/// Client names are unique, which is enforced by the database.
+fn name_error(e: database::driver::Error) -> RegistrationError {
+    if is_unique_violation(&e) {
+        return RegistrationError::InvalidClientMetadata("client_name is already used");
+    }
+
+    warn!("{e}");
+    RegistrationError::ServerError
+}
+
+async fn client_information(
+    database: &Database,
+    config: &WConfig,
+    client: &OAuth2Client,
+    client_secret: Option<String>,
+    registration_access_token: Option<String>,
+) -> Result<ClientInformation, RegistrationError> {
+    let registration_endpoint =
+        config.endpoint_url(&config.http.registration_endpoint, "/api/oauth/register");
+
+    let redirect_uris = client
+        .list_redirect_uris(database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?;
+
+    let post_logout_redirect_uris = client
+        .list_post_logout_redirect_uris(database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| RegistrationError::ServerError)?;
+
+    Ok(ClientInformation {
+        client_id: client.client_id.clone(),
+        client_secret_expires_at: client_secret.is_some().then_some(0),
+        client_secret,
+        registration_access_token,
+        registration_client_uri: format!("{registration_endpoint}/{}", client.client_id),
+        client_name: client.name.clone(),
+        redirect_uris,
+        grant_types: client.grant_types.to_vec(),
+        response_types: client.response_types.to_vec(),
+        token_endpoint_auth_method: client.token_endpoint_auth_method,
+        logo_uri: client.logo_uri.clone(),
+        contacts: client.contacts.to_vec(),
+        jwks: client
+            .jwks
+            .as_deref()
+            .and_then(|jwks| serde_json::from_str(jwks).ok()),
+        id_token_signed_response_alg: client.id_token_signed_response_alg,
+        post_logout_redirect_uris,
+        backchannel_logout_uri: client.backchannel_logout_uri.clone(),
+    })
+}
+
+/// An error response of the registration endpoints.
+///
+/// # Further reading
+/// - [RFC 7591, Section 3.2.2](https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.2)
+/// - [RFC 6750, Section 3.1](https://datatracker.ietf.org/doc/html/rfc6750#section-3.1)
+#[derive(Debug)]
+pub enum RegistrationError {
+    /// A redirect URI is invalid, or redirect URIs are missing
+    InvalidRedirectUri,
+    /// The metadata is invalid, with a description of the problem
+    InvalidClientMetadata(&'static str),
+    /// The registration access token is missing or invalid
+    InvalidToken,
+    ServerError,
+}
+
+impl Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::InvalidRedirectUri => "invalid_redirect_uri",
+                Self::InvalidClientMetadata(_) => "invalid_client_metadata",
+                Self::InvalidToken => "invalid_token",
+                Self::ServerError => "server_error",
+            }
+        )
+    }
+}
+
+impl ResponseError for RegistrationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        #[derive(Serialize)]
+        struct Response {
+            error: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error_description: Option<&'static str>,
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+
+        if let Self::InvalidToken = self {
+            response.insert_header((header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#));
+        }
+
+        response.json(&Response {
+            error: self.to_string(),
+            error_description: match self {
+                Self::InvalidClientMetadata(description) => Some(description),
+                _ => None,
+            },
+        })
+    }
+}
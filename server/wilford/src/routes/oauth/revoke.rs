@@ -0,0 +1,119 @@
+use crate::access_tokens;
+use crate::response_types::Empty;
+use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::oauth::{authenticate_client, ClientCredentials, OAuth2ErrorKind};
+use actix_web::{web, HttpRequest};
+use database::oauth2_client::RefreshToken;
+use serde::Deserialize;
+use tap::TapFallible;
+use tracing::warn;
+
+#[derive(Deserialize)]
+pub struct Form {
+    /// The token to revoke
+    token: String,
+    /// A hint about the type of the token, `access_token` or `refresh_token`.
+    /// Wilford uses this to determine which token type to look up first.
+    /// Unknown values are ignored.
+    token_type_hint: Option<String>,
+    #[serde(flatten)]
+    client: ClientCredentials,
+}
+
+/// Revoke an access token or refresh token.
+/// Revoking a refresh token revokes its entire family, including all access tokens derived from it.
+///
+/// Tokens that are invalid, or have already been revoked, do not result in an error.
+///
+/// # Errors
+///
+/// - If the client could not be authenticated
+/// - If the token was not issued to the client
+/// - If the operation fails
+///
+/// # Further reading
+/// - [RFC7009](https://datatracker.ietf.org/doc/html/rfc7009)
+pub async fn revoke(
+    database: WDatabase,
+    config: WConfig,
+    form: web::Form<Form>,
+    req: HttpRequest,
+) -> Result<Empty, OAuth2ErrorKind> {
+    let client = authenticate_client(&database, &config, &req, &form.client).await?;
+
+    // The hint only determines the order of the lookups,
+    // if the token is not found with the hinted type the other type is tried as well.
+    if form.token_type_hint.as_deref() == Some("refresh_token") {
+        if revoke_refresh_token(&database, &form.token, &client.client_id).await? {
+            return Ok(Empty);
+        }
+
+        revoke_access_token(&database, &form.token, &client.client_id).await?;
+    } else {
+        if revoke_access_token(&database, &form.token, &client.client_id).await? {
+            return Ok(Empty);
+        }
+
+        revoke_refresh_token(&database, &form.token, &client.client_id).await?;
+    }
+
+    Ok(Empty)
+}
+
+/// Revoke the access token if it exists.
+/// Returns whether the token was found.
+async fn revoke_access_token(
+    database: &WDatabase,
+    token: &str,
+    client_id: &str,
+) -> Result<bool, OAuth2ErrorKind> {
+    let token = match access_tokens::resolve(database, token)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?
+    {
+        Some(t) => t,
+        None => return Ok(false),
+    };
+
+    if token.client_id.ne(client_id) {
+        return Err(OAuth2ErrorKind::UnauthorizedClient);
+    }
+
+    token
+        .revoke(database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?;
+
+    Ok(true)
+}
+
+/// Revoke the refresh token's family, and all access tokens derived from it, if it exists.
+/// Returns whether the token was found.
+async fn revoke_refresh_token(
+    database: &WDatabase,
+    token: &str,
+    client_id: &str,
+) -> Result<bool, OAuth2ErrorKind> {
+    let token = match RefreshToken::get_by_token(database, token)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?
+    {
+        Some(t) => t,
+        None => return Ok(false),
+    };
+
+    if token.client_id.ne(client_id) {
+        return Err(OAuth2ErrorKind::UnauthorizedClient);
+    }
+
+    token
+        .revoke(database)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?;
+
+    Ok(true)
+}
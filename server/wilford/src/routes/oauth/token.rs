@@ -1,14 +1,18 @@
+use crate::access_tokens;
 use crate::response_types::Uncached;
 use crate::routes::appdata::{WConfig, WDatabase};
-use crate::routes::oauth::OAuth2ErrorKind;
-use crate::routes::WOidcSigningKey;
+use crate::routes::oauth::{authenticate_client, pkce, ClientCredentials, OAuth2ErrorKind};
 use actix_web::cookie::time::OffsetDateTime;
-use actix_web::web;
+use actix_web::{web, HttpRequest};
+use database::driver::Database;
 use database::oauth2_client::{
-    create_id_token, JwtSigningAlgorithm, OAuth2AuthorizationCode, OAuth2Client, RefreshToken,
+    self, create_id_token, AccessToken, OAuth2AuthorizationCode, OAuth2Client, RefreshToken,
+    UserClaims,
 };
+use database::signing_key::SigningKey;
 use database::user::User;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use tap::TapFallible;
 use tracing::warn;
 
@@ -16,10 +20,15 @@ use tracing::warn;
 pub struct Form {
     grant_type: GrantType,
     code: Option<String>,
-    redirect_uri: String,
-    client_id: String,
+    /// Only required for the authorization code grant
+    redirect_uri: Option<String>,
     refresh_token: Option<String>,
-    client_secret: String,
+    #[serde(flatten)]
+    client: ClientCredentials,
+    /// PKCE code verifier, required if a code challenge was provided in the authorization request
+    code_verifier: Option<String>,
+    /// The requested scopes, space separated. Only used for the client credentials grant
+    scope: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -28,6 +37,21 @@ pub enum GrantType {
     AuthorizationCode,
     #[serde(rename(deserialize = "refresh_token"))]
     RefreshToken,
+    /// Client Credentials Grant
+    /// [RFC6749 Section 4.4](https://datatracker.ietf.org/doc/html/rfc6749#section-4.4)
+    #[serde(rename(deserialize = "client_credentials"))]
+    ClientCredentials,
+}
+
+impl GrantType {
+    /// The grant type as registered for clients
+    fn registered(&self) -> oauth2_client::GrantType {
+        match self {
+            Self::AuthorizationCode => oauth2_client::GrantType::AuthorizationCode,
+            Self::RefreshToken => oauth2_client::GrantType::RefreshToken,
+            Self::ClientCredentials => oauth2_client::GrantType::ClientCredentials,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -35,28 +59,27 @@ pub struct Response {
     access_token: String,
     token_type: String,
     expires_in: i64,
-    refresh_token: String,
+    /// Not issued for the client credentials grant,
+    /// or if the client may not use the refresh token grant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
     scope: String,
-    id_token: String,
+    /// Not issued for the client credentials grant, as there is no user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
 }
 
 pub async fn token(
     database: WDatabase,
     form: web::Form<Form>,
     config: WConfig,
-    oidc_signing_key: WOidcSigningKey,
+    req: HttpRequest,
 ) -> Result<Uncached<web::Json<Response>>, OAuth2ErrorKind> {
-    let client = OAuth2Client::get_by_client_id(&database, &form.client_id)
-        .await
-        .tap_err(|e| warn!("{e}"))
-        .map_err(|_| OAuth2ErrorKind::ServerError)?
-        .ok_or(OAuth2ErrorKind::UnauthorizedClient)?;
+    // Public clients do not authenticate.
+    // They are instead required to use PKCE.
+    let client = authenticate_client(&database, &config, &req, &form.client).await?;
 
-    if client.client_secret.ne(&form.client_secret) {
-        return Err(OAuth2ErrorKind::UnauthorizedClient);
-    }
-
-    if client.redirect_uri.ne(&form.redirect_uri) {
+    if !client.allows_grant_type(form.grant_type.registered()) {
         return Err(OAuth2ErrorKind::UnauthorizedClient);
     }
 
@@ -77,37 +100,79 @@ pub async fn token(
                 return Err(OAuth2ErrorKind::InvalidGrant);
             }
 
+            // A code may only be used once. If it is presented again,
+            // all tokens previously issued from it are revoked, RFC6749 Section 4.1.2
+            if authorization.used {
+                return Err(revoke_replayed_code(&database, &authorization).await);
+            }
+
+            // Must be identical to the redirect URI in the authorization request,
+            // RFC6749 Section 4.1.3
+            if form.redirect_uri.as_ref() != Some(&authorization.redirect_uri) {
+                return Err(OAuth2ErrorKind::InvalidGrant);
+            }
+
             if OffsetDateTime::now_utc().unix_timestamp() > authorization.expires_at {
                 return Err(OAuth2ErrorKind::InvalidGrant);
             }
 
+            // If a code challenge was provided in the authorization request,
+            // the client must prove it is the same client that made that request.
+            // A verifier without a preceding challenge is rejected as well.
+            match (authorization.code_challenge(), &form.code_verifier) {
+                (Some(challenge), Some(verifier)) => {
+                    if !pkce::verify(&challenge, verifier) {
+                        return Err(OAuth2ErrorKind::InvalidGrant);
+                    }
+                }
+                (Some(_), None) => return Err(OAuth2ErrorKind::InvalidGrant),
+                (None, Some(_)) => return Err(OAuth2ErrorKind::InvalidGrant),
+                (None, None) => {}
+            }
+
             let authorization_nonce = authorization.nonce.clone();
 
-            let (atoken, rtoken) = client
-                .new_token_pair(&database, authorization)
+            let pair = client
+                .new_token_pair(
+                    &database,
+                    &authorization,
+                    config.refresh_token.absolute_lifetime,
+                )
                 .await
                 .tap_err(|e| warn!("{e}"))
                 .map_err(|_| OAuth2ErrorKind::ServerError)?;
 
+            // The code was exchanged concurrently
+            let (atoken, rtoken) = match pair {
+                Some(pair) => pair,
+                None => return Err(revoke_replayed_code(&database, &authorization).await),
+            };
+
+            let access_token = issue_access_token(&database, &config, &client, &atoken).await?;
+
+            // A client that may not use the refresh token grant has no use for the refresh token
+            let refresh_token = client
+                .allows_grant_type(oauth2_client::GrantType::RefreshToken)
+                .then_some(rtoken.token);
+
             Ok(Uncached::new(web::Json(Response {
-                id_token: create_id_token(
-                    config.oidc_issuer.clone(),
-                    &client,
-                    &User::get_by_id(&database, &rtoken.user_id)
-                        .await
-                        .map_err(|_| OAuth2ErrorKind::ServerError)?
-                        .ok_or(OAuth2ErrorKind::ServerError)?,
-                    &oidc_signing_key.0,
-                    &atoken,
-                    authorization_nonce,
-                    JwtSigningAlgorithm::RS256,
-                )
-                .map_err(|_| OAuth2ErrorKind::ServerError)?,
-                access_token: atoken.token,
+                id_token: Some(
+                    create_id_token(
+                        config.oidc_issuer.clone(),
+                        &client,
+                        user_claims(&database, &client, &atoken).await?,
+                        &active_signing_key(&database, &client).await?,
+                        &atoken,
+                        &access_token,
+                        authorization_nonce,
+                    )
+                    .map_err(|_| OAuth2ErrorKind::ServerError)?,
+                ),
+                access_token,
                 token_type: "bearer".to_string(),
                 scope: atoken.scopes.unwrap_or_default(),
-                expires_in: OffsetDateTime::now_utc().unix_timestamp() - atoken.expires_at,
-                refresh_token: rtoken.token,
+                expires_in: atoken.expires_at - OffsetDateTime::now_utc().unix_timestamp(),
+                refresh_token,
             })))
         }
         GrantType::RefreshToken => {
@@ -126,32 +191,186 @@ pub async fn token(
                 return Err(OAuth2ErrorKind::InvalidGrant);
             }
 
+            if rtoken.is_expired(config.refresh_token.idle_lifetime) {
+                return Err(OAuth2ErrorKind::InvalidGrant);
+            }
+
+            // A refresh token is only valid once. If it is presented again,
+            // either the client or an attacker holds a stolen token.
+            // As we cannot tell which, the entire family is revoked.
+            let rotated = if rtoken.used {
+                None
+            } else {
+                client
+                    .rotate_refresh_token(&database, &rtoken)
+                    .await
+                    .tap_err(|e| warn!("{e}"))
+                    .map_err(|_| OAuth2ErrorKind::ServerError)?
+            };
+
+            let (atoken, rtoken) = match rotated {
+                Some(pair) => pair,
+                None => {
+                    warn!(
+                        "Refresh token reuse detected for client {}, revoking token family",
+                        client.client_id
+                    );
+
+                    rtoken
+                        .revoke(&database)
+                        .await
+                        .tap_err(|e| warn!("{e}"))
+                        .map_err(|_| OAuth2ErrorKind::ServerError)?;
+
+                    return Err(OAuth2ErrorKind::InvalidGrant);
+                }
+            };
+
+            let access_token = issue_access_token(&database, &config, &client, &atoken).await?;
+
+            Ok(Uncached::new(web::Json(Response {
+                id_token: Some(
+                    create_id_token(
+                        config.oidc_issuer.clone(),
+                        &client,
+                        user_claims(&database, &client, &atoken).await?,
+                        &active_signing_key(&database, &client).await?,
+                        &atoken,
+                        &access_token,
+                        None,
+                    )
+                    .map_err(|_| OAuth2ErrorKind::ServerError)?,
+                ),
+                access_token,
+                token_type: "bearer".to_string(),
+                expires_in: atoken.expires_at - OffsetDateTime::now_utc().unix_timestamp(),
+                scope: atoken.scopes.unwrap_or_default(),
+                refresh_token: Some(rtoken.token),
+            })))
+        }
+        GrantType::ClientCredentials => {
+            // Public clients cannot authenticate themselves
+            if client.is_public {
+                return Err(OAuth2ErrorKind::UnauthorizedClient);
+            }
+
+            let permitted_scopes = client
+                .list_permitted_scopes(&database)
+                .await
+                .tap_err(|e| warn!("{e}"))
+                .map_err(|_| OAuth2ErrorKind::ServerError)?
+                .into_iter()
+                .collect::<HashSet<_>>();
+
+            // If no scopes are requested, all scopes permitted to the client are granted.
+            let scopes = match &form.scope {
+                Some(scope) => {
+                    let requested = scope
+                        .split(" ")
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string())
+                        .collect::<HashSet<_>>();
+
+                    if !requested.is_subset(&permitted_scopes) {
+                        return Err(OAuth2ErrorKind::InvalidScope);
+                    }
+
+                    requested
+                }
+                None => permitted_scopes,
+            };
+
+            let scopes =
+                (!scopes.is_empty()).then(|| scopes.into_iter().collect::<Vec<_>>().join(" "));
+
             let atoken = client
-                .refresh_access_token(&database, &rtoken)
+                .new_client_access_token(&database, scopes)
                 .await
                 .tap_err(|e| warn!("{e}"))
                 .map_err(|_| OAuth2ErrorKind::ServerError)?;
 
             Ok(Uncached::new(web::Json(Response {
-                id_token: create_id_token(
-                    config.oidc_issuer.clone(),
-                    &client,
-                    &User::get_by_id(&database, &rtoken.user_id)
-                        .await
-                        .map_err(|_| OAuth2ErrorKind::ServerError)?
-                        .ok_or(OAuth2ErrorKind::ServerError)?,
-                    &oidc_signing_key.0,
-                    &atoken,
-                    None,
-                    JwtSigningAlgorithm::RS256,
-                )
-                .map_err(|_| OAuth2ErrorKind::ServerError)?,
-                access_token: atoken.token,
+                id_token: None,
+                access_token: issue_access_token(&database, &config, &client, &atoken).await?,
                 token_type: "bearer".to_string(),
                 expires_in: atoken.expires_at - OffsetDateTime::now_utc().unix_timestamp(),
                 scope: atoken.scopes.unwrap_or_default(),
-                refresh_token: rtoken.token,
+                refresh_token: None,
             })))
         }
     }
 }
+
+/// Revoke all tokens issued from an authorization code that was presented more than once.
+/// Returns the error to respond with.
+async fn revoke_replayed_code(
+    database: &Database,
+    authorization: &OAuth2AuthorizationCode,
+) -> OAuth2ErrorKind {
+    warn!(
+        "Authorization code replay detected for client {}, revoking issued tokens",
+        authorization.client_id
+    );
+
+    match authorization.revoke_issued_tokens(database).await {
+        Ok(_) => OAuth2ErrorKind::InvalidGrant,
+        Err(e) => {
+            warn!("{e}");
+            OAuth2ErrorKind::ServerError
+        }
+    }
+}
+
+/// Collect the claims about the user the access token was issued to, for the ID token.
+async fn user_claims(
+    database: &Database,
+    client: &OAuth2Client,
+    access_token: &AccessToken,
+) -> Result<UserClaims, OAuth2ErrorKind> {
+    let user = User::get_by_id(
+        database,
+        access_token
+            .user_id
+            .as_deref()
+            .ok_or(OAuth2ErrorKind::ServerError)?,
+    )
+    .await
+    .tap_err(|e| warn!("{e}"))
+    .map_err(|_| OAuth2ErrorKind::ServerError)?
+    .ok_or(OAuth2ErrorKind::ServerError)?;
+
+    UserClaims::new(
+        database,
+        &user,
+        &access_token.scopes(),
+        client.legacy_id_token_claims,
+    )
+    .await
+    .tap_err(|e| warn!("{e}"))
+    .map_err(|_| OAuth2ErrorKind::ServerError)
+}
+
+/// Get the access token in the format the client expects.
+async fn issue_access_token(
+    database: &Database,
+    config: &WConfig,
+    client: &OAuth2Client,
+    access_token: &AccessToken,
+) -> Result<String, OAuth2ErrorKind> {
+    access_tokens::issue(database, &config.oidc_issuer, client, access_token)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)
+}
+
+/// Get the key to sign ID tokens for the client with.
+async fn active_signing_key(
+    database: &Database,
+    client: &OAuth2Client,
+) -> Result<SigningKey, OAuth2ErrorKind> {
+    SigningKey::get_active(database, client.id_token_signed_response_alg)
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| OAuth2ErrorKind::ServerError)?
+        .ok_or(OAuth2ErrorKind::ServerError)
+}
@@ -0,0 +1,26 @@
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use actix_web::web;
+use database::oauth2_client::UserClaims;
+
+/// Get the claims about the user the access token was issued to.
+/// Which claims are present depends on the scopes of the access token.
+///
+/// # Errors
+///
+/// - If the access token is invalid or was not issued to a user
+/// - If the access token does not have the `openid` scope
+/// - If the operation fails
+///
+/// # Further reading
+/// - [OpenID Connect Core 1.0, Section 5.3](https://openid.net/specs/openid-connect-core-1_0.html#UserInfo)
+pub async fn userinfo(auth: Auth, database: WDatabase) -> WebResult<web::Json<UserClaims>> {
+    if !auth.has_scope("openid") {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let claims = UserClaims::new(&database, &auth.user, &auth.scopes(), false).await?;
+
+    Ok(web::Json(claims))
+}
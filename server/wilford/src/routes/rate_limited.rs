@@ -0,0 +1,91 @@
+use crate::client_ip::client_ip;
+use crate::rate_limit::{RateLimitGroup, RateLimitKey};
+use crate::routes::appdata::{WConfig, WRateLimiter};
+use crate::routes::error::{WebError, WebErrorKind};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use tracing::warn;
+
+/// Middleware limiting the requests per client IP address to a group of endpoints.
+/// Requests over the limit are rejected with `429 Too Many Requests`,
+/// with a `Retry-After` header.
+pub struct RateLimited {
+    group: RateLimitGroup,
+}
+
+impl RateLimited {
+    pub fn new(group: RateLimitGroup) -> Self {
+        Self { group }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimited
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitedMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitedMiddleware {
+            service,
+            group: self.group,
+        }))
+    }
+}
+
+pub struct RateLimitedMiddleware<S> {
+    service: S,
+    group: RateLimitGroup,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitedMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Err(retry_after) = self.take(&req) {
+            return Box::pin(ready(Err(WebError::from(WebErrorKind::TooManyRequests(
+                retry_after,
+            ))
+            .into())));
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
+
+impl<S> RateLimitedMiddleware<S> {
+    /// Take a token for the request.
+    /// Requests of which the client IP address is unknown are not limited.
+    fn take(&self, req: &ServiceRequest) -> Result<(), i64> {
+        let (Some(config), Some(limiter)) =
+            (req.app_data::<WConfig>(), req.app_data::<WRateLimiter>())
+        else {
+            return Ok(());
+        };
+
+        let Some(ip) = client_ip(req.request(), &config.http.trusted_proxies) else {
+            return Ok(());
+        };
+
+        limiter
+            .take(
+                RateLimitKey::ip(self.group, ip),
+                self.group.limit(&config.rate_limit),
+            )
+            .inspect_err(|_| warn!("Rate limited {ip} for {:?}", self.group))
+    }
+}
@@ -1,10 +1,14 @@
 use actix_web::web;
 use serde::{Deserialize, Serialize};
 
+use database::consent::UserConsent;
+use database::driver::Database;
 use database::oauth2_client::{OAuth2Client, OAuth2PendingAuthorization};
+use std::collections::HashSet;
 
-use crate::routes::appdata::WDatabase;
+use crate::routes::appdata::{WConfig, WDatabase};
 use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::oauth::prompt;
 
 #[derive(Deserialize)]
 pub struct Query {
@@ -15,29 +19,70 @@ pub struct Query {
 pub struct Response {
     client_name: String,
     scopes: Option<String>,
+    /// Whether the user must be asked for consent.
+    /// If not, the user has already granted the requested scopes to the client.
+    consent_required: bool,
 }
 
 pub async fn authorization_info(
     database: WDatabase,
+    config: WConfig,
     query: web::Query<Query>,
 ) -> WebResult<web::Json<Response>> {
-    let authorization = OAuth2PendingAuthorization::get_by_id(&database, &query.authorization)
-        .await?
-        .ok_or(WebErrorKind::NotFound)?;
+    let authorization = OAuth2PendingAuthorization::get_by_id(
+        &database,
+        &query.authorization,
+        config.cleanup.pending_authorization_max_age,
+    )
+    .await?
+    .ok_or(WebErrorKind::NotFound)?;
 
-    match &authorization {
-        OAuth2PendingAuthorization::Authorized(_) => {}
+    let user_id = match &authorization {
+        OAuth2PendingAuthorization::Authorized(v) => v.user_id(),
         OAuth2PendingAuthorization::Unauthorized(_) => {
             return Err(WebErrorKind::Unauthorized.into())
         }
-    }
+    };
 
     let client = OAuth2Client::get_by_client_id(&database, authorization.client_id())
         .await?
         .ok_or(WebErrorKind::NotFound)?;
 
+    let consent_required = is_consent_required(&database, &authorization, user_id).await?;
+
     Ok(web::Json(Response {
         client_name: client.name,
         scopes: authorization.scopes().clone(),
+        consent_required,
     }))
 }
+
+/// Check whether the user must be asked for consent.
+/// This is the case if `prompt=consent` was requested,
+/// or if the user has not yet granted all requested scopes to the client.
+///
+/// # Errors
+///
+/// If the query fails
+pub async fn is_consent_required(
+    database: &Database,
+    authorization: &OAuth2PendingAuthorization,
+    user_id: &str,
+) -> WebResult<bool> {
+    if prompt::contains(authorization.prompt().as_deref(), prompt::CONSENT) {
+        return Ok(true);
+    }
+
+    let requested = authorization
+        .scopes()
+        .as_ref()
+        .map(|s| s.split(" ").map(|c| c.to_string()).collect::<HashSet<_>>())
+        .unwrap_or_default();
+
+    Ok(
+        !UserConsent::get(database, user_id, authorization.client_id())
+            .await?
+            .map(|c| c.covers(&requested))
+            .unwrap_or(false),
+    )
+}
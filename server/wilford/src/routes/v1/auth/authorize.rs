@@ -1,21 +1,24 @@
 use actix_web::cookie::time::OffsetDateTime;
 use actix_web::web;
+use database::consent::UserConsent;
 use database::driver::Database;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use tap::TapFallible;
 use tracing::{instrument, warn};
 
 use database::oauth2_client::{
-    create_id_token, AccessToken, AuthorizationType, JwtSigningAlgorithm,
-    OAuth2AuthorizationCodeCreationError, OAuth2Client, OAuth2PendingAuthorization,
+    create_id_token, AccessToken, AuthorizationType, OAuth2AuthorizationCodeCreationError,
+    OAuth2Client, OAuth2PendingAuthorization, UserClaims,
 };
+use database::signing_key::SigningKey;
 use database::user::User;
 
+use crate::access_tokens;
 use crate::response_types::{MaybeCookie, Redirect, SetCookie};
 use crate::routes::appdata::{WConfig, WDatabase};
 use crate::routes::error::{WebErrorKind, WebResult};
 use crate::routes::oauth::{OAuth2AuthorizationResponse, OAuth2Error, OAuth2ErrorKind};
-use crate::routes::WOidcSigningKey;
 
 #[derive(Deserialize)]
 pub struct Query {
@@ -26,29 +29,45 @@ pub struct Query {
 #[instrument(skip_all)]
 pub async fn authorize(
     database: WDatabase,
-    oidc_signing_key: WOidcSigningKey,
     config: WConfig,
     query: web::Query<Query>,
 ) -> WebResult<MaybeCookie<'static, OAuth2AuthorizationResponse<Redirect>>> {
-    let pending_authorization =
-        OAuth2PendingAuthorization::get_by_id(&database, &query.authorization)
-            .await?
-            .ok_or(WebErrorKind::NotFound)?;
+    let pending_authorization = OAuth2PendingAuthorization::get_by_id(
+        &database,
+        &query.authorization,
+        config.cleanup.pending_authorization_max_age,
+    )
+    .await?
+    .ok_or(WebErrorKind::NotFound)?;
 
     let client = OAuth2Client::get_by_client_id(&database, pending_authorization.client_id())
         .await?
         .ok_or(WebErrorKind::NotFound)?;
 
+    // The redirect URI was validated in the authorization request
+    let redirect_uri = pending_authorization.redirect_uri().clone();
+
     if !query.grant {
         return Ok(MaybeCookie::none(OAuth2AuthorizationResponse::Err(
             OAuth2Error::new(
                 OAuth2ErrorKind::AccessDenied,
-                &client.redirect_uri,
+                &redirect_uri,
                 pending_authorization.state().as_deref(),
             ),
         )));
     }
 
+    // Remember the consent, so the user is not asked again for the same scopes
+    if let OAuth2PendingAuthorization::Authorized(v) = &pending_authorization {
+        let scopes = pending_authorization
+            .scopes()
+            .as_ref()
+            .map(|s| s.split(" ").map(|c| c.to_string()).collect::<HashSet<_>>())
+            .unwrap_or_default();
+
+        UserConsent::grant(&database, v.user_id(), &client.client_id, &scopes).await?;
+    }
+
     let state = pending_authorization.state().clone();
     let res = match pending_authorization.ty() {
         AuthorizationType::AuthorizationCode => {
@@ -71,7 +90,7 @@ pub async fn authorize(
 
             let url = format!(
                 "{}?{}",
-                client.redirect_uri,
+                redirect_uri,
                 serde_qs::to_string(&RedirectQuery {
                     code: authorization.code,
                     state,
@@ -83,50 +102,69 @@ pub async fn authorize(
         }
         AuthorizationType::Implicit => {
             let access_token = new_access_token(&client, pending_authorization, &database).await?;
+            let issued_access_token =
+                issue_access_token(&database, &config, &client, &access_token).await?;
 
             let url = format!(
                 "{}#{}",
-                client.redirect_uri,
-                create_implicit_fragment(None, access_token.clone(), state),
+                redirect_uri,
+                create_implicit_fragment(None, &access_token, issued_access_token.clone(), state),
             );
 
             MaybeCookie::some(SetCookie::new(
                 "Authorization",
-                format!("Bearer {}", access_token.token),
+                format!("Bearer {issued_access_token}"),
                 OAuth2AuthorizationResponse::Ok(Redirect::new(url)),
             ))
         }
         AuthorizationType::IdToken => {
             let nonce = pending_authorization.nonce().clone();
             let access_token = new_access_token(&client, pending_authorization, &database).await?;
+            let issued_access_token =
+                issue_access_token(&database, &config, &client, &access_token).await?;
 
             let url = format!(
                 "{}#{}",
-                client.redirect_uri,
+                redirect_uri,
                 create_implicit_fragment(
                     Some(
                         create_id_token(
                             config.oidc_issuer.clone(),
                             &client,
-                            &User::get_by_id(&database, &access_token.user_id)
+                            UserClaims::new(
+                                &database,
+                                &User::get_by_id(
+                                    &database,
+                                    access_token
+                                        .user_id
+                                        .as_deref()
+                                        .ok_or(WebErrorKind::InvalidInternalState)?
+                                )
+                                .await?
+                                .ok_or(WebErrorKind::InternalServerError)?,
+                                &access_token.scopes(),
+                                client.legacy_id_token_claims,
+                            )
+                            .await?,
+                            &SigningKey::get_active(&database, client.id_token_signed_response_alg)
                                 .await?
                                 .ok_or(WebErrorKind::InternalServerError)?,
-                            &oidc_signing_key.0,
                             &access_token,
+                            &issued_access_token,
                             nonce,
-                            JwtSigningAlgorithm::RS256,
                         )
                         .tap_err(|e| warn!("Failed to create ID token: {e}"))
                         .map_err(|_| WebErrorKind::InternalServerError)?
                     ),
-                    access_token.clone(),
+                    &access_token,
+                    issued_access_token.clone(),
                     state
                 )
             );
 
             MaybeCookie::some(SetCookie::new(
                 "Authorization",
-                format!("Bearer {}", access_token.token),
+                format!("Bearer {issued_access_token}"),
                 OAuth2AuthorizationResponse::Ok(Redirect::new(url)),
             ))
         }
@@ -153,6 +191,21 @@ async fn new_access_token(
         })?)
 }
 
+/// Get the access token in the format the client expects.
+async fn issue_access_token(
+    database: &Database,
+    config: &WConfig,
+    client: &OAuth2Client,
+    access_token: &AccessToken,
+) -> WebResult<String> {
+    Ok(
+        access_tokens::issue(database, &config.oidc_issuer, client, access_token)
+            .await
+            .tap_err(|e| warn!("Failed to issue access token: {e}"))
+            .map_err(|_| WebErrorKind::InternalServerError)?,
+    )
+}
+
 #[derive(Serialize)]
 struct RedirectFragment {
     access_token: String,
@@ -166,13 +219,15 @@ struct RedirectFragment {
 
 /// Create the fragment string used for the OAuth2 implicit flow and the OpenID Connect IdToken flow.
 /// The `id_token` string should only be supplied for the OpenID Connect IdToken flow.
+/// `issued_access_token` is the access token in the format the client expects.
 fn create_implicit_fragment(
     id_token: Option<String>,
-    access_token: AccessToken,
+    access_token: &AccessToken,
+    issued_access_token: String,
     state: Option<String>,
 ) -> String {
     serde_qs::to_string(&RedirectFragment {
-        access_token: access_token.token,
+        access_token: issued_access_token,
         token_type: "bearer",
         expires_in: access_token.expires_at - OffsetDateTime::now_utc().unix_timestamp(),
         state,
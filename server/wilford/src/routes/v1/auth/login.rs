@@ -3,12 +3,21 @@ use crate::authorization::combined::{
 };
 use crate::authorization::espo::EspoAuthorizationProviderError;
 use crate::authorization::local_provider::LocalAuthorizationProviderError;
-use crate::authorization::{AuthorizationError, AuthorizationProvider};
+use crate::authorization::{AuthorizationError, AuthorizationProvider, SecondFactor};
+use crate::config::Config;
+use crate::response_types::{MaybeCookie, SetCookie};
 use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::auth::{SessionAuth, SESSION_COOKIE};
 use crate::routes::error::{WebErrorKind, WebResult};
-use actix_web::web;
+use crate::routes::lockout::LoginThrottle;
+use crate::routes::v1::auth::passkey;
+use crate::webauthn::AuthenticationCredential;
+use actix_web::cookie::time::OffsetDateTime;
+use actix_web::http::header::USER_AGENT;
+use actix_web::{web, HttpRequest};
 use database::driver::Database;
 use database::oauth2_client::OAuth2PendingAuthorization;
+use database::session::{amr, UserSession};
 use database::user::{SetEmailAddressError, User};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -21,6 +30,8 @@ pub struct Request {
     username: String,
     password: String,
     totp_code: Option<String>,
+    /// A passkey of the user, used as second factor instead of a TOTP code
+    passkey: Option<AuthenticationCredential>,
 }
 
 #[derive(Serialize)]
@@ -29,44 +40,91 @@ pub struct Response {
     totp_required: bool,
 }
 
+impl Response {
+    /// The response if the credentials are invalid
+    pub fn failed() -> Self {
+        Self {
+            status: false,
+            totp_required: false,
+        }
+    }
+}
+
+/// Log in to complete a pending authorization.
+/// On success, a login session is started and set as cookie,
+/// so the user does not have to log in again for other clients.
+/// The session replaces the session the user was logged in with, if any.
+///
+/// Failed attempts are counted per account and client IP address,
+/// too many failed attempts delay or lock further attempts.
 #[instrument(skip_all)]
 pub async fn login(
     database: WDatabase,
     config: WConfig,
+    req: HttpRequest,
+    current_session: Option<SessionAuth>,
     payload: web::Json<Request>,
-) -> WebResult<web::Json<Response>> {
+) -> WebResult<MaybeCookie<'static, web::Json<Response>>> {
     // Get the authorization assocated with the provided token
-    let authorization = OAuth2PendingAuthorization::get_by_id(&database, &payload.authorization)
-        .instrument(warn_span!("OAuth2PendingAuthorization::get_by_id"))
-        .await
-        .tap_err(|e| warn!("{e}"))?
-        .ok_or(WebErrorKind::NotFound)?;
+    let authorization = OAuth2PendingAuthorization::get_by_id(
+        &database,
+        &payload.authorization,
+        config.cleanup.pending_authorization_max_age,
+    )
+    .instrument(warn_span!("OAuth2PendingAuthorization::get_by_id"))
+    .await
+    .tap_err(|e| warn!("{e}"))?
+    .ok_or(WebErrorKind::NotFound)?;
 
     // Get the provider backend
     let auth_provider = CombinedAuthorizationProvider::new(&config, &database);
 
+    let throttle = LoginThrottle::new(&database, &config, &req);
+    throttle.check(&payload.username).await?;
+
+    // The passkey is verified here, the provider checks whether it belongs to the user
+    let passkey = match &payload.passkey {
+        Some(credential) => {
+            if !auth_provider.supports_passkey_login() {
+                return Err(WebErrorKind::Unsupported.into());
+            }
+
+            match passkey::verify_assertion(&database, &config, credential).await? {
+                Some((passkey, _)) => Some(passkey),
+                None => {
+                    throttle.record_failure(&payload.username).await?;
+                    return Ok(MaybeCookie::none(web::Json(Response::failed())));
+                }
+            }
+        }
+        None => None,
+    };
+
+    let second_factor = match (&passkey, &payload.totp_code) {
+        (Some(passkey), _) => Some(SecondFactor::Passkey(&passkey.user_id)),
+        (None, Some(code)) => Some(SecondFactor::Totp(code)),
+        (None, None) => None,
+    };
+
     // Check the credentials and handle results
-    let validation_result = match auth_provider
-        .validate_credentials(
-            &payload.username,
-            &payload.password,
-            payload.totp_code.as_deref(),
-        )
+    let validation_result = auth_provider
+        .validate_credentials(&payload.username, &payload.password, second_factor)
         .instrument(warn_span!("auth_provider::validate_credentials"))
         .await
-        .tap_err(|e| warn!("{e}"))
-    {
+        .tap_err(|e| warn!("{e}"));
+    throttle
+        .record(&payload.username, &validation_result)
+        .await?;
+
+    let validation_result = match validation_result {
         Err(AuthorizationError::InvalidCredentials) => {
-            return Ok(web::Json(Response {
-                status: false,
-                totp_required: false,
-            }))
+            return Ok(MaybeCookie::none(web::Json(Response::failed())))
         }
         Err(AuthorizationError::TotpNeeded) => {
-            return Ok(web::Json(Response {
+            return Ok(MaybeCookie::none(web::Json(Response {
                 status: false,
                 totp_required: true,
-            }))
+            })))
         }
         // Should not happen here, but handle anyway.
         Err(AuthorizationError::UnsupportedOperation) => {
@@ -105,36 +163,118 @@ pub async fn login(
         .await?
         .ok_or(WebErrorKind::InternalServerError)?;
 
+    check_user(
+        &database,
+        &authorization,
+        &user,
+        validation_result.user_information.is_admin,
+    )
+    .await?;
+
+    let amr = match (validation_result.second_factor_verified, passkey) {
+        (true, Some(_)) => vec![amr::PASSWORD, amr::HARDWARE_KEY, amr::MFA],
+        (true, None) => vec![amr::PASSWORD, amr::OTP, amr::MFA],
+        (false, _) => vec![amr::PASSWORD],
+    };
+
+    start_session(
+        &database,
+        &config,
+        &req,
+        current_session,
+        authorization,
+        &user.user_id,
+        &amr,
+    )
+    .await
+}
+
+/// Start a login session for the user who logged in, and complete the pending authorization.
+/// The session replaces the session the user was logged in with, if any.
+///
+/// # Errors
+///
+/// If a query fails
+#[instrument(skip_all)]
+pub async fn start_session(
+    database: &Database,
+    config: &Config,
+    req: &HttpRequest,
+    current_session: Option<SessionAuth>,
+    authorization: OAuth2PendingAuthorization,
+    user_id: &str,
+    amr: &[&str],
+) -> WebResult<MaybeCookie<'static, web::Json<Response>>> {
+    if let Some(current_session) = current_session {
+        current_session.session.terminate(database).await?;
+    }
+
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let (session, token) =
+        UserSession::new(database, user_id, amr, config.session.lifetime, user_agent).await?;
+
+    // Mark the authorization as authorized.
+    authorization
+        .set_session(database, &session)
+        .instrument(warn_span!("authorization::set_session"))
+        .await
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| WebErrorKind::BadRequest)?;
+
+    Ok(MaybeCookie::some(
+        SetCookie::new(
+            SESSION_COOKIE,
+            token,
+            web::Json(Response {
+                status: true,
+                totp_required: false,
+            }),
+        )
+        .expires(
+            OffsetDateTime::from_unix_timestamp(session.expires_at)
+                .map_err(|_| WebErrorKind::InternalServerError)?,
+        )
+        .http_only(),
+    ))
+}
+
+/// Check whether the user may complete the authorization.
+/// Admins may request any scope.
+///
+/// # Errors
+/// - If the user requested scopes they are not permitted to access
+/// - If the user's email address is not verified
+/// - If a query fails
+#[instrument(skip_all)]
+pub async fn check_user(
+    database: &Database,
+    authorization: &OAuth2PendingAuthorization,
+    user: &User,
+    is_admin: bool,
+) -> WebResult<()> {
     // Check if any scopes were requested that the user should not be allowed to access
     // This check is skipped for admins.
     // For optimizations, we evaluate the is_admin check first, followed by the scope check. Due to
     // short-circuiting behaviour, the scope check is only evaluated if the user is _not_ an admin.
     // We use the lambda function to reduce the complecity of the if statement.
-    let scope_check = || {
-        are_scopes_allowed(&database, &authorization, &user).instrument(warn_span!("scope_check"))
-    };
+    let scope_check =
+        || are_scopes_allowed(database, authorization, user).instrument(warn_span!("scope_check"));
 
-    if !validation_result.user_information.is_admin && !scope_check().await? {
+    if !is_admin && !scope_check().await? {
         return Err(WebErrorKind::Forbidden.into());
     }
 
     // Check if the email address is verified
-    if !user.is_email_verified(&database).await? {
+    if !user.is_email_verified(database).await? {
         return Err(WebErrorKind::EmailNotVerified.into());
     }
 
-    // Mark the authorization as authorized.
-    authorization
-        .set_user_id(&database, &validation_result.user_information.id)
-        .instrument(warn_span!("authorization::set_user_id"))
-        .await
-        .tap_err(|e| warn!("{e}"))
-        .map_err(|_| WebErrorKind::BadRequest)?;
-
-    Ok(web::Json(Response {
-        status: true,
-        totp_required: false,
-    }))
+    Ok(())
 }
 
 #[instrument(skip_all)]
@@ -1,10 +1,13 @@
+use crate::rate_limit::RateLimitGroup;
+use crate::routes::rate_limited::RateLimited;
 use actix_route_config::Routable;
 use actix_web::web;
 use actix_web::web::ServiceConfig;
 
-mod authorization_info;
+pub mod authorization_info;
 mod authorize;
-mod login;
+pub mod login;
+mod passkey;
 mod token_info;
 
 pub struct Router;
@@ -13,7 +16,21 @@ impl Routable for Router {
     fn configure(config: &mut ServiceConfig) {
         config.service(
             web::scope("/auth")
-                .route("/login", web::post().to(login::login))
+                .service(
+                    web::resource("/login")
+                        .wrap(RateLimited::new(RateLimitGroup::Login))
+                        .route(web::post().to(login::login)),
+                )
+                .service(
+                    web::resource("/passkey/challenge")
+                        .wrap(RateLimited::new(RateLimitGroup::Login))
+                        .route(web::post().to(passkey::challenge)),
+                )
+                .service(
+                    web::resource("/passkey/login")
+                        .wrap(RateLimited::new(RateLimitGroup::Login))
+                        .route(web::post().to(passkey::login)),
+                )
                 .route("/authorize", web::get().to(authorize::authorize))
                 .route(
                     "/authorization-info",
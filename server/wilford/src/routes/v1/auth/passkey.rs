@@ -0,0 +1,196 @@
+use crate::authorization::combined::CombinedAuthorizationProvider;
+use crate::authorization::{AuthorizationError, AuthorizationProvider};
+use crate::config::Config;
+use crate::response_types::MaybeCookie;
+use crate::routes::appdata::{WConfig, WDatabase};
+use crate::routes::auth::SessionAuth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::auth::login::{check_user, start_session, Response};
+use crate::webauthn::{AuthenticationCredential, RelyingParty, RequestOptions, VerifiedAssertion};
+use actix_web::{web, HttpRequest};
+use database::driver::Database;
+use database::oauth2_client::OAuth2PendingAuthorization;
+use database::passkey::{Ceremony, UserPasskey, WebauthnChallenge};
+use database::session::amr;
+use database::user::User;
+use serde::Deserialize;
+use tap::TapFallible;
+use tracing::{instrument, warn};
+
+#[derive(Deserialize)]
+pub struct ChallengeRequest {
+    authorization: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    authorization: String,
+    /// The credential returned by `navigator.credentials.get()`
+    credential: AuthenticationCredential,
+}
+
+/// Start logging in with a passkey, for a pending authorization.
+/// Returns the options to pass to `navigator.credentials.get()`.
+/// The resulting credential can be used to log in without password,
+/// or as second factor when logging in with a password.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the authorization does not exist
+/// - If a query fails
+#[instrument(skip_all)]
+pub async fn challenge(
+    database: WDatabase,
+    config: WConfig,
+    payload: web::Json<ChallengeRequest>,
+) -> WebResult<web::Json<RequestOptions>> {
+    if !CombinedAuthorizationProvider::new(&config, &database).supports_passkey_login() {
+        return Err(WebErrorKind::Unsupported.into());
+    }
+
+    OAuth2PendingAuthorization::get_by_id(
+        &database,
+        &payload.authorization,
+        config.cleanup.pending_authorization_max_age,
+    )
+    .await?
+    .ok_or(WebErrorKind::NotFound)?;
+
+    let challenge = WebauthnChallenge::new(&database, Ceremony::Authentication, None).await?;
+
+    Ok(web::Json(RequestOptions::new(
+        &RelyingParty::from_config(&config),
+        challenge.challenge,
+    )))
+}
+
+/// Log in with a passkey, without password, to complete a pending authorization.
+/// The authenticator must have verified the user, e.g. with a PIN or biometrics.
+/// On success, a login session is started, like logging in with a password.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the authorization does not exist
+/// - If the user may not complete the authorization
+/// - If a query fails
+#[instrument(skip_all)]
+pub async fn login(
+    database: WDatabase,
+    config: WConfig,
+    req: HttpRequest,
+    current_session: Option<SessionAuth>,
+    payload: web::Json<LoginRequest>,
+) -> WebResult<MaybeCookie<'static, web::Json<Response>>> {
+    let provider = CombinedAuthorizationProvider::new(&config, &database);
+    if !provider.supports_passkey_login() {
+        return Err(WebErrorKind::Unsupported.into());
+    }
+
+    let authorization = OAuth2PendingAuthorization::get_by_id(
+        &database,
+        &payload.authorization,
+        config.cleanup.pending_authorization_max_age,
+    )
+    .await?
+    .ok_or(WebErrorKind::NotFound)?;
+
+    let passkey = match verify_assertion(&database, &config, &payload.credential).await? {
+        // Without user verification, the passkey is only something the user has
+        Some((passkey, assertion)) if assertion.user_verified => passkey,
+        _ => return Ok(MaybeCookie::none(web::Json(Response::failed()))),
+    };
+
+    let user_information = match provider
+        .validate_user(&passkey.user_id)
+        .await
+        .tap_err(|e| warn!("{e}"))
+    {
+        Ok(user_information) => user_information,
+        Err(AuthorizationError::InvalidCredentials) => {
+            return Ok(MaybeCookie::none(web::Json(Response::failed())))
+        }
+        Err(_) => return Err(WebErrorKind::InternalServerError.into()),
+    };
+
+    let user = User::get_by_id(&database, &user_information.id)
+        .await?
+        .ok_or(WebErrorKind::InternalServerError)?;
+
+    check_user(&database, &authorization, &user, user_information.is_admin).await?;
+
+    // The passkey proves possession, user verification a PIN or biometrics
+    start_session(
+        &database,
+        &config,
+        &req,
+        current_session,
+        authorization,
+        &user.user_id,
+        &[amr::HARDWARE_KEY, amr::MFA],
+    )
+    .await
+}
+
+/// Verify a passkey a user logs in with.
+/// Returns `None` if the credential is invalid, was not created for a challenge we issued,
+/// or if its signature counter indicates the passkey was cloned.
+///
+/// # Errors
+///
+/// If a query fails
+#[instrument(skip_all)]
+pub async fn verify_assertion(
+    database: &Database,
+    config: &Config,
+    credential: &AuthenticationCredential,
+) -> WebResult<Option<(UserPasskey, VerifiedAssertion)>> {
+    let Ok(challenge) = credential.challenge().tap_err(|e| warn!("{e}")) else {
+        return Ok(None);
+    };
+
+    // The challenge is removed, so the assertion cannot be replayed
+    if WebauthnChallenge::take(database, &challenge, Ceremony::Authentication)
+        .await?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let Some(mut passkey) = UserPasskey::get_by_credential_id(database, &credential.id).await?
+    else {
+        return Ok(None);
+    };
+
+    if credential
+        .user_handle()
+        .is_some_and(|handle| handle != passkey.user_id.as_bytes())
+    {
+        return Ok(None);
+    }
+
+    let Ok(assertion) = credential
+        .verify(
+            &RelyingParty::from_config(config),
+            &challenge,
+            &passkey.public_key,
+        )
+        .tap_err(|e| warn!("{e}"))
+    else {
+        return Ok(None);
+    };
+
+    if !passkey
+        .record_use(database, assertion.sign_count as i64)
+        .await?
+    {
+        warn!(
+            "Signature counter of passkey {} did not increase, it may have been cloned",
+            passkey.credential_id
+        );
+        return Ok(None);
+    }
+
+    Ok(Some((passkey, assertion)))
+}
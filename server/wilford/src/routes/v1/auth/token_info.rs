@@ -1,4 +1,4 @@
-use crate::routes::auth::Auth;
+use crate::routes::auth::TokenAuth;
 use crate::routes::error::WebResult;
 use actix_web::web;
 use serde::Serialize;
@@ -7,6 +7,12 @@ use serde::Serialize;
 pub struct Response {
     /// The scopes allowed for the authorization, space separated.
     scope: String,
+    /// The client the token was issued to.
+    client_id: String,
+    /// The user the token was issued to.
+    /// Not present if the token was issued to the client itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,
 }
 
 /// Get information about the OAuth2 token used for authorizations
@@ -14,8 +20,10 @@ pub struct Response {
 /// # Errors
 ///
 /// If the operation fails
-pub async fn token_info(auth: Auth) -> WebResult<web::Json<Response>> {
+pub async fn token_info(auth: TokenAuth) -> WebResult<web::Json<Response>> {
     Ok(web::Json(Response {
         scope: auth.scopes().into_iter().collect::<Vec<_>>().join(" "),
+        client_id: auth.client_id().to_string(),
+        user_id: auth.user_id().map(str::to_string),
     }))
 }
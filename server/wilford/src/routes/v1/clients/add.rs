@@ -1,31 +1,83 @@
 use actix_web::web;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use url::Url;
 
-use database::oauth2_client::OAuth2Client;
+use database::driver::is_unique_violation;
+use database::oauth2_client::{
+    AccessTokenFormat, OAuth2Client, OAuth2ClientOptions, TokenEndpointAuthMethod,
+};
+use database::signing_key::JwtSigningAlgorithm;
 
-use crate::response_types::Empty;
+use crate::client_assertion;
 use crate::routes::appdata::WDatabase;
 use crate::routes::auth::Auth;
 use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::oauth::redirect_uri;
 use crate::routes::v1::MANAGE_SCOPE;
 
 #[derive(Deserialize)]
 pub struct Request {
     /// The name of an OAuth client
     name: String,
-    /// The redirect URI of the client
-    redirect_uri: String,
+    /// The redirect URIs of the client. At least one is required
+    redirect_uris: Vec<String>,
+    /// Whether the client is a public client, e.g. a SPA or mobile app.
+    /// Public clients are not required to provide their secret, but must use PKCE.
+    #[serde(default)]
+    is_public: bool,
+    /// Whether a loopback redirect URI may be used with any port, for native apps.
+    #[serde(default)]
+    allow_loopback_any_port: bool,
+    /// Whether ID tokens contain the non-standard `sub_email`, `sub_name` and `sub_is_admin` claims.
+    #[serde(default)]
+    legacy_id_token_claims: bool,
+    /// The algorithm ID tokens are signed with. Defaults to `RS256`.
+    #[serde(default)]
+    id_token_signed_response_alg: JwtSigningAlgorithm,
+    /// The format of access tokens issued to the client. Defaults to `Opaque`.
+    #[serde(default)]
+    access_token_format: AccessTokenFormat,
+    /// The URIs the user may be redirected to after logging out.
+    #[serde(default)]
+    post_logout_redirect_uris: Vec<String>,
+    /// The URI logout tokens are sent to when a user logs out.
+    backchannel_logout_uri: Option<String>,
+    /// How the client authenticates at the token endpoint.
+    /// Defaults to `none` for public clients and `client_secret_basic` for other clients.
+    token_endpoint_auth_method: Option<TokenEndpointAuthMethod>,
+    /// The JWK Set with the keys the client signs its assertions with.
+    /// Required for the `private_key_jwt` method.
+    jwks: Option<serde_json::Value>,
 }
 
-/// Add a new OAuth2 client
+#[derive(Serialize)]
+pub struct Response {
+    /// The OAuth2 `client_id`
+    client_id: String,
+    /// The OAuth2 `client_secret`.
+    /// Only returned here, it cannot be retrieved later.
+    client_secret: String,
+}
+
+/// Add a new OAuth2 client.
+/// The client's secret is returned only once.
 ///
 /// # Errors
 ///
 /// - If the user does not have sufficient scopes
 /// - If the name is too long; >64
 /// - If the name is already used
+/// - If no redirect URIs are provided
+/// - If a redirect URI or post logout redirect URI is not an absolute URI, has a fragment or is too long; >512
+/// - If the back-channel logout URI is not a valid URL
+/// - If the authentication method is `none` for a confidential client, or not `none` for a public client
+/// - If the `private_key_jwt` method is used without a valid JWK Set
 /// - If the operation fails
-pub async fn add(database: WDatabase, auth: Auth, payload: web::Json<Request>) -> WebResult<Empty> {
+pub async fn add(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<web::Json<Response>> {
     if !auth.has_scope(MANAGE_SCOPE) {
         return Err(WebErrorKind::Forbidden.into());
     }
@@ -34,22 +86,81 @@ pub async fn add(database: WDatabase, auth: Auth, payload: web::Json<Request>) -
         return Err(WebErrorKind::BadRequest.into());
     }
 
-    let exists = OAuth2Client::list(&database)
-        .await?
-        .into_iter()
-        .any(|c| c.name.eq(&payload.name));
+    let mut payload = payload.into_inner();
+    redirect_uri::dedup(&mut payload.redirect_uris);
+    redirect_uri::dedup(&mut payload.post_logout_redirect_uris);
+
+    let invalid_redirect_uri = payload
+        .redirect_uris
+        .iter()
+        .chain(&payload.post_logout_redirect_uris)
+        .any(|r| !redirect_uri::is_valid(r));
+
+    if payload.redirect_uris.is_empty() || invalid_redirect_uri {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let invalid_backchannel_logout_uri = payload
+        .backchannel_logout_uri
+        .as_ref()
+        .is_some_and(|u| Url::parse(u).is_err());
 
-    if exists {
+    if invalid_backchannel_logout_uri {
         return Err(WebErrorKind::BadRequest.into());
     }
 
-    OAuth2Client::new(
+    let token_endpoint_auth_method = match payload.token_endpoint_auth_method {
+        Some(method) => method,
+        None if payload.is_public => TokenEndpointAuthMethod::None,
+        None => TokenEndpointAuthMethod::ClientSecretBasic,
+    };
+
+    // Public clients cannot authenticate, confidential clients must
+    if payload.is_public != (token_endpoint_auth_method == TokenEndpointAuthMethod::None) {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let jwks = payload.jwks.as_ref().map(|jwks| jwks.to_string());
+    if token_endpoint_auth_method == TokenEndpointAuthMethod::PrivateKeyJwt {
+        let valid_jwks = jwks
+            .as_deref()
+            .is_some_and(|jwks| client_assertion::validate_jwks(jwks).is_ok());
+
+        if !valid_jwks {
+            return Err(WebErrorKind::BadRequest.into());
+        }
+    }
+
+    let (client, client_secret) = OAuth2Client::new(
         &database,
-        payload.name.clone(),
-        payload.redirect_uri.clone(),
+        payload.name,
+        &payload.redirect_uris,
         false,
+        OAuth2ClientOptions {
+            is_public: payload.is_public,
+            allow_loopback_any_port: payload.allow_loopback_any_port,
+            legacy_id_token_claims: payload.legacy_id_token_claims,
+            id_token_signed_response_alg: payload.id_token_signed_response_alg,
+            access_token_format: payload.access_token_format,
+            post_logout_redirect_uris: payload.post_logout_redirect_uris,
+            backchannel_logout_uri: payload.backchannel_logout_uri,
+            token_endpoint_auth_method,
+            jwks,
+            ..Default::default()
+        },
     )
-    .await?;
+    .await
+    .map_err(|e| {
+        // Another client has the name
+        if is_unique_violation(&e) {
+            WebErrorKind::BadRequest
+        } else {
+            WebErrorKind::Database(e)
+        }
+    })?;
 
-    Ok(Empty)
+    Ok(web::Json(Response {
+        client_id: client.client_id,
+        client_secret,
+    }))
 }
@@ -28,9 +28,17 @@ pub async fn internal(database: WDatabase) -> WebResult<web::Json<Response>> {
         .find(|c| c.is_internal)
         .ok_or(WebErrorKind::InvalidInternalState)?;
 
+    // The internal client is created with exactly one redirect URI
+    let redirect_uri = client
+        .list_redirect_uris(&database)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(WebErrorKind::InvalidInternalState)?;
+
     Ok(web::Json(Response {
         name: client.name,
         client_id: client.client_id,
-        redirect_uri: client.redirect_uri,
+        redirect_uri,
     }))
 }
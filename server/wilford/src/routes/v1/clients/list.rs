@@ -1,7 +1,10 @@
 use actix_web::web;
 use serde::Serialize;
 
-use database::oauth2_client::OAuth2Client;
+use database::oauth2_client::{
+    AccessTokenFormat, AuthorizationType, GrantType, OAuth2Client, TokenEndpointAuthMethod,
+};
+use database::signing_key::JwtSigningAlgorithm;
 
 use crate::routes::appdata::WDatabase;
 use crate::routes::auth::Auth;
@@ -18,12 +21,40 @@ pub struct Response {
 pub struct Client {
     /// The name of the client
     name: String,
-    /// The redirect URI of the client
-    redirect_uri: String,
+    /// The redirect URIs of the client
+    redirect_uris: Vec<String>,
     /// The OAuth2 `client_id`
     client_id: String,
-    /// The OAuth2 `client_secret`
-    client_secret: String,
+    /// UNIX timestamp until which the previous secret remains valid, after the secret was rotated
+    previous_client_secret_expires_at: Option<i64>,
+    /// Whether the client is a public client
+    is_public: bool,
+    /// Whether a loopback redirect URI may be used with any port
+    allow_loopback_any_port: bool,
+    /// Whether ID tokens contain the non-standard claims
+    legacy_id_token_claims: bool,
+    /// The algorithm ID tokens are signed with
+    id_token_signed_response_alg: JwtSigningAlgorithm,
+    /// The format of access tokens issued to the client
+    access_token_format: AccessTokenFormat,
+    /// The URIs the user may be redirected to after logging out
+    post_logout_redirect_uris: Vec<String>,
+    /// The URI logout tokens are sent to
+    backchannel_logout_uri: Option<String>,
+    /// How the client authenticates at the token endpoint
+    token_endpoint_auth_method: TokenEndpointAuthMethod,
+    /// The JWK Set with the keys the client signs its assertions with
+    jwks: Option<serde_json::Value>,
+    /// The grant types the client may use
+    grant_types: Vec<GrantType>,
+    /// The response types the client may use
+    response_types: Vec<AuthorizationType>,
+    /// The URL of the client's logo
+    logo_uri: Option<String>,
+    /// The email addresses of the people responsible for the client
+    contacts: Vec<String>,
+    /// Whether the client was registered dynamically, and can manage its own registration
+    dynamically_registered: bool,
 }
 
 /// List all configured OAuth2 clients
@@ -37,18 +68,34 @@ pub async fn list(database: WDatabase, auth: Auth) -> WebResult<web::Json<Respon
         return Err(WebErrorKind::Forbidden.into());
     }
 
-    let clients = OAuth2Client::list(&database)
+    let mut clients = Vec::new();
+    // Don't show the internal client in this list
+    for c in OAuth2Client::list(&database)
         .await?
         .into_iter()
-        // Don't show the internal client in this list
         .filter(|f| !f.is_internal)
-        .map(|c| Client {
+    {
+        clients.push(Client {
+            redirect_uris: c.list_redirect_uris(&database).await?,
+            post_logout_redirect_uris: c.list_post_logout_redirect_uris(&database).await?,
             name: c.name,
-            redirect_uri: c.redirect_uri,
             client_id: c.client_id,
-            client_secret: c.client_secret,
-        })
-        .collect();
+            previous_client_secret_expires_at: c.previous_client_secret_expires_at,
+            is_public: c.is_public,
+            allow_loopback_any_port: c.allow_loopback_any_port,
+            legacy_id_token_claims: c.legacy_id_token_claims,
+            id_token_signed_response_alg: c.id_token_signed_response_alg,
+            access_token_format: c.access_token_format,
+            backchannel_logout_uri: c.backchannel_logout_uri,
+            token_endpoint_auth_method: c.token_endpoint_auth_method,
+            jwks: c.jwks.and_then(|jwks| serde_json::from_str(&jwks).ok()),
+            grant_types: c.grant_types.to_vec(),
+            response_types: c.response_types.to_vec(),
+            logo_uri: c.logo_uri,
+            contacts: c.contacts.to_vec(),
+            dynamically_registered: c.registration_access_token_hash.is_some(),
+        });
+    }
 
     Ok(web::Json(Response { clients }))
 }
@@ -5,7 +5,10 @@ use actix_web::web::ServiceConfig;
 mod add;
 mod internal;
 mod list;
+mod permitted_scopes;
 mod remove;
+mod rotate_secret;
+mod update_redirect_uris;
 
 pub struct Router;
 
@@ -13,10 +16,19 @@ impl Routable for Router {
     fn configure(config: &mut ServiceConfig) {
         config.service(
             web::scope("/clients")
+                .configure(permitted_scopes::Router::configure)
                 .route("/internal", web::get().to(internal::internal))
                 .route("/add", web::post().to(add::add))
                 .route("/list", web::get().to(list::list))
-                .route("/remove", web::delete().to(remove::remove)),
+                .route("/remove", web::delete().to(remove::remove))
+                .route(
+                    "/rotate-secret",
+                    web::post().to(rotate_secret::rotate_secret),
+                )
+                .route(
+                    "/update-redirect-uris",
+                    web::post().to(update_redirect_uris::update_redirect_uris),
+                ),
         );
     }
 }
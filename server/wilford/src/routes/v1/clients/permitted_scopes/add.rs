@@ -0,0 +1,47 @@
+use actix_web::web;
+use serde::Deserialize;
+
+use database::oauth2_client::OAuth2Client;
+
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+
+#[derive(Deserialize)]
+pub struct Payload {
+    /// The ID of the OAuth2 client
+    client_id: String,
+    /// Scope to add
+    scope: String,
+}
+
+/// Permit a client to request a scope using the client credentials grant
+///
+/// # Errors
+///
+/// - If the user does not have sufficient scopes
+/// - If the client does not exist
+/// - If the client is already permitted the scope
+/// - If the operation fails
+pub async fn add(database: WDatabase, auth: Auth, payload: web::Json<Payload>) -> WebResult<Empty> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let client = OAuth2Client::get_by_client_id(&database, &payload.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    let current_scopes = client.list_permitted_scopes(&database).await?;
+    if current_scopes.contains(&payload.scope) {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    client
+        .grant_permitted_scope(&database, &payload.scope)
+        .await?;
+
+    Ok(Empty)
+}
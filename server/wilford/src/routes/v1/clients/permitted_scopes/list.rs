@@ -0,0 +1,37 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use database::oauth2_client::OAuth2Client;
+
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+
+#[derive(Serialize)]
+pub struct Response {
+    scopes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Query {
+    /// The ID of the OAuth2 client
+    client_id: String,
+}
+
+pub async fn list(
+    database: WDatabase,
+    auth: Auth,
+    query: web::Query<Query>,
+) -> WebResult<web::Json<Response>> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let client = OAuth2Client::get_by_client_id(&database, &query.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+    let scopes = client.list_permitted_scopes(&database).await?;
+
+    Ok(web::Json(Response { scopes }))
+}
@@ -0,0 +1,43 @@
+use actix_web::web;
+use serde::Deserialize;
+
+use database::oauth2_client::OAuth2Client;
+
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The ID of the OAuth2 client
+    client_id: String,
+    /// The scope to remove
+    scope: String,
+}
+
+pub async fn remove(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let client = OAuth2Client::get_by_client_id(&database, &payload.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+    let current_scopes = client.list_permitted_scopes(&database).await?;
+
+    if !current_scopes.contains(&payload.scope) {
+        return Err(WebErrorKind::NotFound.into());
+    }
+
+    client
+        .remove_permitted_scope(&database, &payload.scope)
+        .await?;
+
+    Ok(Empty)
+}
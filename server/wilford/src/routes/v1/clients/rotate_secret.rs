@@ -0,0 +1,72 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use database::oauth2_client::OAuth2Client;
+
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+
+/// The longest grace period allowed, in seconds: 30 days
+const MAX_GRACE_PERIOD: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct Request {
+    client_id: String,
+    /// The number of seconds the current secret remains valid.
+    /// If absent, the current secret is invalidated immediately.
+    grace_period: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    /// The new OAuth2 `client_secret`.
+    /// Only returned here, it cannot be retrieved later.
+    client_secret: String,
+    /// UNIX timestamp until which the previous secret remains valid
+    previous_client_secret_expires_at: Option<i64>,
+}
+
+/// Replace the secret of an OAuth2 client with a newly generated one.
+///
+/// # Errors
+///
+/// - If the user has insufficient permissions
+/// - If the client does not exist
+/// - If the client is public, as public clients do not use their secret
+/// - If the grace period is negative or longer than 30 days
+/// - If the operation fails
+pub async fn rotate_secret(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<web::Json<Response>> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    if payload
+        .grace_period
+        .is_some_and(|g| !(0..=MAX_GRACE_PERIOD).contains(&g))
+    {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let mut client = OAuth2Client::get_by_client_id(&database, &payload.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    if client.is_public {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let client_secret = client
+        .rotate_secret(&database, payload.grace_period)
+        .await?;
+
+    Ok(web::Json(Response {
+        client_secret,
+        previous_client_secret_expires_at: client.previous_client_secret_expires_at,
+    }))
+}
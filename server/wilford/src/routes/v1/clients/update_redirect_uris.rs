@@ -0,0 +1,73 @@
+use actix_web::web;
+use serde::Deserialize;
+
+use database::oauth2_client::OAuth2Client;
+
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::oauth::redirect_uri;
+use crate::routes::v1::MANAGE_SCOPE;
+
+#[derive(Deserialize)]
+pub struct Request {
+    client_id: String,
+    /// The new redirect URIs of the client. At least one is required
+    redirect_uris: Vec<String>,
+    /// The new post logout redirect URIs of the client.
+    #[serde(default)]
+    post_logout_redirect_uris: Vec<String>,
+}
+
+/// Replace the redirect URIs and post logout redirect URIs of an OAuth2 client.
+///
+/// # Errors
+///
+/// - If the user has insufficient permissions
+/// - If the client does not exist
+/// - If the client is the internal client, its redirect URI is configured instead
+/// - If no redirect URIs are provided
+/// - If a redirect URI or post logout redirect URI is not an absolute URI, has a fragment or is too long; >512
+/// - If the operation fails
+pub async fn update_redirect_uris(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let mut payload = payload.into_inner();
+    redirect_uri::dedup(&mut payload.redirect_uris);
+    redirect_uri::dedup(&mut payload.post_logout_redirect_uris);
+
+    let invalid_redirect_uri = payload
+        .redirect_uris
+        .iter()
+        .chain(&payload.post_logout_redirect_uris)
+        .any(|r| !redirect_uri::is_valid(r));
+
+    if payload.redirect_uris.is_empty() || invalid_redirect_uri {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let mut client = OAuth2Client::get_by_client_id(&database, &payload.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    if client.is_internal {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let mut options = client.options(&database).await?;
+    options.post_logout_redirect_uris = payload.post_logout_redirect_uris;
+
+    let name = client.name.clone();
+    client
+        .update(&database, name, &payload.redirect_uris, options)
+        .await?;
+
+    Ok(Empty)
+}
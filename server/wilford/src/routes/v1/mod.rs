@@ -2,9 +2,10 @@ use actix_route_config::Routable;
 use actix_web::web;
 use actix_web::web::ServiceConfig;
 
-mod auth;
+pub mod auth;
 mod cat;
 mod clients;
+mod signing_keys;
 mod user;
 
 pub const MANAGE_SCOPE: &str = "wilford.manage";
@@ -18,7 +19,8 @@ impl Routable for Router {
                 .configure(clients::Router::configure)
                 .configure(auth::Router::configure)
                 .configure(user::Router::configure)
-                .configure(cat::Router::configure),
+                .configure(cat::Router::configure)
+                .configure(signing_keys::Router::configure),
         );
     }
 }
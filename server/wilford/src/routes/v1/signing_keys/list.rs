@@ -0,0 +1,47 @@
+use actix_web::web;
+use serde::Serialize;
+
+use database::signing_key::{JwtSigningAlgorithm, SigningKey, SigningKeyState};
+
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+
+#[derive(Serialize)]
+pub struct Response {
+    keys: Vec<Key>,
+}
+
+#[derive(Serialize)]
+pub struct Key {
+    kid: String,
+    algorithm: JwtSigningAlgorithm,
+    state: SigningKeyState,
+    created_at: i64,
+    activated_at: Option<i64>,
+    retired_at: Option<i64>,
+}
+
+/// List the keys used to sign ID tokens.
+/// The private keys are not included.
+pub async fn list(database: WDatabase, auth: Auth) -> WebResult<web::Json<Response>> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let keys = SigningKey::list(&database)
+        .await?
+        .into_iter()
+        .map(|k| Key {
+            kid: k.kid,
+            algorithm: k.algorithm,
+            state: k.state,
+            created_at: k.created_at,
+            activated_at: k.activated_at,
+            retired_at: k.retired_at,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(web::Json(Response { keys }))
+}
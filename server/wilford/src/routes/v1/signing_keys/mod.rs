@@ -0,0 +1,18 @@
+use actix_route_config::Routable;
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+mod list;
+mod rotate;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/signing-keys")
+                .route("/list", web::get().to(list::list))
+                .route("/rotate", web::post().to(rotate::rotate)),
+        );
+    }
+}
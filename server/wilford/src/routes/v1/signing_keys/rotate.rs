@@ -0,0 +1,19 @@
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+use crate::signing_keys;
+
+/// Rotate the keys used to sign ID tokens.
+/// The next key becomes active, the active key is retired and a new next key is generated.
+/// ID tokens signed with the retired key remain valid.
+pub async fn rotate(database: WDatabase, auth: Auth) -> WebResult<Empty> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    signing_keys::rotate(&database).await?;
+
+    Ok(Empty)
+}
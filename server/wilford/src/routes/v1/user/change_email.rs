@@ -4,9 +4,10 @@ use crate::mail::WilfordMailer;
 use crate::response_types::Empty;
 use crate::routes::auth::Auth;
 use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::LoginThrottle;
 use crate::routes::v1::user::email_verify_link;
 use crate::routes::{auth_error_to_web_error, WConfig, WDatabase};
-use actix_web::web;
+use actix_web::{web, HttpRequest};
 use mailer::{VerifyEmailData, VerifyEmailEmail};
 use serde::Deserialize;
 use tracing::info;
@@ -26,12 +27,14 @@ pub struct Request {
 /// # Errors
 /// - The operation is not supported
 /// - The provided password is invalid
+/// - Too many invalid passwords were provided
 /// - The operation fails
 pub async fn change_email(
     auth: Auth,
     payload: web::Json<Request>,
     config: WConfig,
     database: WDatabase,
+    req: HttpRequest,
 ) -> WebResult<Empty> {
     // Check for support
     let provider = CombinedAuthorizationProvider::new(&config, &database);
@@ -42,11 +45,14 @@ pub async fn change_email(
     let payload = payload.into_inner();
 
     // Validate password
-    auth_error_to_web_error(
-        provider
-            .validate_credentials(&auth.user.email, &payload.password, None)
-            .await,
-    )?;
+    let throttle = LoginThrottle::new(&database, &config, &req);
+    throttle.check(&auth.user.email).await?;
+
+    let validation = provider
+        .validate_credentials(&auth.user.email, &payload.password, None)
+        .await;
+    throttle.record(&auth.user.email, &validation).await?;
+    auth_error_to_web_error(validation)?;
 
     let verification = auth.user.update_email(&database, payload.new_email).await?;
 
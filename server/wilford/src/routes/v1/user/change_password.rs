@@ -4,8 +4,9 @@ use crate::mail::WilfordMailer;
 use crate::response_types::Empty;
 use crate::routes::auth::Auth;
 use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::LoginThrottle;
 use crate::routes::{auth_error_to_web_error, WConfig, WDatabase};
-use actix_web::web;
+use actix_web::{web, HttpRequest};
 use database::user::Locale;
 use mailer::{PasswordChangedData, PasswordChangedMail};
 use serde::Deserialize;
@@ -24,12 +25,14 @@ pub struct Request {
 ///
 /// - If the provider does not support changing passwords
 /// - If the provided `old_password` does not match the stored password
+/// - If too many invalid passwords were provided
 /// - If the change operation fails
 pub async fn change_password(
     auth: Auth,
     payload: web::Json<Request>,
     config: WConfig,
     database: WDatabase,
+    req: HttpRequest,
 ) -> WebResult<Empty> {
     let provider = CombinedAuthorizationProvider::new(&config, &database);
     if !provider.supports_password_change() {
@@ -37,11 +40,14 @@ pub async fn change_password(
     }
 
     // Check the old password is correct
-    auth_error_to_web_error(
-        provider
-            .validate_credentials(&auth.user.email, &payload.old_password, None)
-            .await,
-    )?;
+    let throttle = LoginThrottle::new(&database, &config, &req);
+    throttle.check(&auth.user.email).await?;
+
+    let validation = provider
+        .validate_credentials(&auth.user.email, &payload.old_password, None)
+        .await;
+    throttle.record(&auth.user.email, &validation).await?;
+    auth_error_to_web_error(validation)?;
 
     // Set new password
     auth_error_to_web_error(
@@ -0,0 +1,46 @@
+use actix_web::web;
+use serde::Serialize;
+
+use database::consent::UserConsent;
+use database::oauth2_client::OAuth2Client;
+
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::WebResult;
+
+#[derive(Serialize)]
+pub struct Response {
+    consents: Vec<Consent>,
+}
+
+#[derive(Serialize)]
+pub struct Consent {
+    client_id: String,
+    client_name: String,
+    /// The granted scopes, space separated
+    scopes: Option<String>,
+    /// UNIX timestamp at which consent was last granted
+    granted_at: i64,
+}
+
+pub async fn list(database: WDatabase, auth: Auth) -> WebResult<web::Json<Response>> {
+    let consents = UserConsent::list_for_user(&database, &auth.user_id).await?;
+
+    let mut response = Vec::with_capacity(consents.len());
+    for consent in consents {
+        // The client may have been removed in the meantime
+        let Some(client) = OAuth2Client::get_by_client_id(&database, &consent.client_id).await?
+        else {
+            continue;
+        };
+
+        response.push(Consent {
+            client_id: consent.client_id,
+            client_name: client.name,
+            scopes: consent.scopes,
+            granted_at: consent.granted_at,
+        });
+    }
+
+    Ok(web::Json(Response { consents: response }))
+}
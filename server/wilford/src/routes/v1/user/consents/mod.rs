@@ -0,0 +1,18 @@
+use actix_route_config::Routable;
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+mod list;
+mod remove;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("consents")
+                .route("/list", web::get().to(list::list))
+                .route("/remove", web::delete().to(remove::remove)),
+        );
+    }
+}
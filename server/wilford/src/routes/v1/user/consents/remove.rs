@@ -0,0 +1,31 @@
+use actix_web::web;
+use serde::Deserialize;
+
+use database::consent::UserConsent;
+
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The client to withdraw consent from
+    client_id: String,
+}
+
+/// Withdraw the consent granted to a client.
+/// All tokens issued to the client for the user are revoked.
+pub async fn remove(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    let consent = UserConsent::get(&database, &auth.user_id, &payload.client_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    consent.withdraw(&database).await?;
+
+    Ok(Empty)
+}
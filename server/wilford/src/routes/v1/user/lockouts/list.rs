@@ -0,0 +1,52 @@
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::MANAGE_SCOPE;
+use actix_web::web;
+use database::login_failure::{LoginFailureKind, LoginFailures};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Response {
+    lockouts: Vec<Lockout>,
+}
+
+#[derive(Serialize)]
+pub struct Lockout {
+    kind: LoginFailureKind,
+    /// The normalized username or email address, or the IP address
+    subject: String,
+    /// The number of failed attempts
+    failures: i32,
+    /// UNIX timestamp of the last failed attempt
+    last_failure_at: i64,
+    /// UNIX timestamp until which attempts are rejected
+    blocked_until: i64,
+}
+
+/// List the accounts and IP addresses for which login attempts are currently rejected,
+/// because of too many failed attempts.
+///
+/// # Errors
+///
+/// - If the caller is not permitted to manage users
+/// - If a query fails
+pub async fn list(auth: Auth, database: WDatabase) -> WebResult<web::Json<Response>> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let lockouts = LoginFailures::list_blocked(&database)
+        .await?
+        .into_iter()
+        .map(|f| Lockout {
+            kind: f.kind,
+            subject: f.subject,
+            failures: f.failures,
+            last_failure_at: f.last_failure_at,
+            blocked_until: f.blocked_until,
+        })
+        .collect();
+
+    Ok(web::Json(Response { lockouts }))
+}
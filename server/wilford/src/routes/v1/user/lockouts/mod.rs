@@ -0,0 +1,18 @@
+use actix_route_config::Routable;
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+mod list;
+mod unlock;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("lockouts")
+                .route("/list", web::get().to(list::list))
+                .route("/unlock", web::post().to(unlock::unlock)),
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::normalize;
+use crate::routes::v1::MANAGE_SCOPE;
+use actix_web::web;
+use database::login_failure::{LoginFailureKind, LoginFailures};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Request {
+    kind: LoginFailureKind,
+    /// The username or email address, or the IP address
+    subject: String,
+}
+
+/// Unlock an account or IP address, and forget its failed login attempts.
+///
+/// # Errors
+///
+/// - If the caller is not permitted to manage users
+/// - If there were no failed attempts for the account or IP address
+/// - If a query fails
+pub async fn unlock(
+    auth: Auth,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    let subject = match payload.kind {
+        LoginFailureKind::Account => normalize(&payload.subject),
+        LoginFailureKind::Ip => payload.subject.trim().to_string(),
+    };
+
+    if !LoginFailures::clear(&database, payload.kind, &subject).await? {
+        return Err(WebErrorKind::NotFound.into());
+    }
+
+    Ok(Empty)
+}
@@ -1,4 +1,6 @@
 use crate::config::Config;
+use crate::rate_limit::RateLimitGroup;
+use crate::routes::rate_limited::RateLimited;
 use actix_route_config::Routable;
 use actix_web::web;
 use actix_web::web::ServiceConfig;
@@ -7,13 +9,19 @@ use database::user::UserEmailVerification;
 mod change_email;
 mod change_name;
 mod change_password;
+mod consents;
 mod info;
 mod list;
+mod lockouts;
+mod passkeys;
 mod password_forgotten;
 mod permitted_scopes;
 mod register;
 mod registration_required;
+mod reset_password;
+mod sessions;
 mod supports_password_change;
+mod totp;
 mod verify_email;
 
 pub struct Router;
@@ -23,15 +31,26 @@ impl Routable for Router {
         config.service(
             web::scope("/user")
                 .configure(permitted_scopes::Router::configure)
+                .configure(consents::Router::configure)
+                .configure(sessions::Router::configure)
+                .configure(totp::Router::configure)
+                .configure(passkeys::Router::configure)
+                .configure(lockouts::Router::configure)
                 .route("/info", web::get().to(info::info))
                 .route("/list", web::get().to(list::list))
                 .route(
                     "/registration-required",
                     web::get().to(registration_required::registration_required),
                 )
-                .route(
-                    "password-forgotten",
-                    web::post().to(password_forgotten::password_forgotten),
+                .service(
+                    web::resource("password-forgotten")
+                        .wrap(RateLimited::new(RateLimitGroup::Email))
+                        .route(web::post().to(password_forgotten::password_forgotten)),
+                )
+                .service(
+                    web::resource("/reset-password")
+                        .wrap(RateLimited::new(RateLimitGroup::Login))
+                        .route(web::post().to(reset_password::reset_password)),
                 )
                 .route(
                     "/change-password",
@@ -42,7 +61,11 @@ impl Routable for Router {
                     "/supports-password-change",
                     web::get().to(supports_password_change::supports_password_change),
                 )
-                .route("/register", web::post().to(register::register))
+                .service(
+                    web::resource("/register")
+                        .wrap(RateLimited::new(RateLimitGroup::Email))
+                        .route(web::post().to(register::register)),
+                )
                 .route("/change-name", web::post().to(change_name::change_name))
                 .route("/verify-email", web::post().to(verify_email::verify_email)),
         );
@@ -0,0 +1,34 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::WebResult;
+use crate::routes::v1::user::passkeys::{require_passkey_support, Passkey};
+use crate::routes::{WConfig, WDatabase};
+use actix_web::web;
+use database::passkey::UserPasskey;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Response {
+    passkeys: Vec<Passkey>,
+}
+
+/// List the passkeys of the user.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If a query fails
+pub async fn list(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+) -> WebResult<web::Json<Response>> {
+    require_passkey_support(&config, &database)?;
+
+    let passkeys = UserPasskey::list_for_user(&database, &auth.user_id)
+        .await?
+        .into_iter()
+        .map(Passkey::from)
+        .collect();
+
+    Ok(web::Json(Response { passkeys }))
+}
@@ -0,0 +1,118 @@
+use crate::authorization::combined::CombinedAuthorizationProvider;
+use crate::authorization::{AuthorizationProvider, SecondFactor};
+use crate::config::Config;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::LoginThrottle;
+use crate::routes::{auth_error_to_web_error, Either};
+use actix_route_config::Routable;
+use actix_web::web::ServiceConfig;
+use actix_web::{web, HttpRequest};
+use database::driver::Database;
+use database::passkey::UserPasskey;
+use serde::{Deserialize, Serialize};
+
+mod list;
+mod register;
+mod remove;
+mod rename;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("passkeys")
+                .route("/list", web::get().to(list::list))
+                .route("/register/start", web::post().to(register::start))
+                .route("/register/finish", web::post().to(register::finish))
+                .route("/rename", web::post().to(rename::rename))
+                .route("/remove", web::delete().to(remove::remove)),
+        );
+    }
+}
+
+#[derive(Serialize)]
+pub struct Passkey {
+    /// The base64url encoded credential ID
+    credential_id: String,
+    name: String,
+    created_at: i64,
+    /// UNIX timestamp at which the passkey was last used to log in
+    last_used_at: Option<i64>,
+}
+
+impl From<UserPasskey> for Passkey {
+    fn from(value: UserPasskey) -> Self {
+        Self {
+            credential_id: value.credential_id,
+            name: value.name,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+/// Passkeys can only be used if the authorization provider supports logging in with them.
+///
+/// # Errors
+///
+/// If the authorization provider does not support passkeys
+fn require_passkey_support(config: &Config, database: &Database) -> WebResult<()> {
+    if CombinedAuthorizationProvider::new(config, database).supports_passkey_login() {
+        Ok(())
+    } else {
+        Err(WebErrorKind::Unsupported.into())
+    }
+}
+
+/// The credentials the user re-enters to manage their passkeys
+#[derive(Deserialize)]
+pub struct Credentials {
+    /// The password of the user
+    password: String,
+    /// A TOTP code or recovery code, required if the user enabled TOTP
+    code: Option<String>,
+}
+
+/// Check the password of the user, and their second factor if they enabled TOTP.
+/// A passkey logs the user in without password, so a stolen access token alone
+/// must not be enough to register one, nor to remove the user's own passkeys.
+///
+/// # Errors
+///
+/// - If the password or code is invalid, or a required code is missing
+/// - If too many invalid passwords or codes were provided
+/// - If a query fails
+async fn verify_credentials(
+    auth: &Auth,
+    config: &Config,
+    database: &Database,
+    req: &HttpRequest,
+    credentials: &Credentials,
+) -> WebResult<()> {
+    let provider = CombinedAuthorizationProvider::new(config, database);
+    let throttle = LoginThrottle::new(database, config, req);
+    throttle.check(&auth.user.email).await?;
+
+    let validation = provider
+        .validate_credentials(
+            &auth.user.email,
+            &credentials.password,
+            credentials.code.as_deref().map(SecondFactor::Totp),
+        )
+        .await;
+    throttle.record(&auth.user.email, &validation).await?;
+
+    // Only returned if the user enabled TOTP, but no code was provided
+    if let Either::Right(()) = auth_error_to_web_error(validation)? {
+        return Err(WebErrorKind::Unauthorized.into());
+    }
+
+    Ok(())
+}
+
+/// Check the name of a passkey
+fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= 64
+}
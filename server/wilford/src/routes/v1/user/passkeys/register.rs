@@ -0,0 +1,124 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::passkeys::{
+    is_valid_name, require_passkey_support, verify_credentials, Credentials, Passkey,
+};
+use crate::routes::{WConfig, WDatabase};
+use crate::webauthn::{CreationOptions, RegistrationCredential, RelyingParty};
+use actix_web::{web, HttpRequest};
+use database::passkey::{Ceremony, UserPasskey, WebauthnChallenge};
+use serde::Deserialize;
+use tap::TapFallible;
+use tracing::warn;
+
+#[derive(Deserialize)]
+pub struct FinishRequest {
+    /// The name of the passkey, e.g. the device it is stored on
+    name: String,
+    /// The credential returned by `navigator.credentials.create()`
+    credential: RegistrationCredential,
+}
+
+/// Start registering a new passkey for the user.
+/// The user must enter their password, and their second factor if they enabled TOTP.
+/// Returns the options to pass to `navigator.credentials.create()`.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the password or code is invalid
+/// - If too many invalid passwords or codes were provided
+/// - If a query fails
+pub async fn start(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    req: HttpRequest,
+    payload: web::Json<Credentials>,
+) -> WebResult<web::Json<CreationOptions>> {
+    require_passkey_support(&config, &database)?;
+    verify_credentials(&auth, &config, &database, &req, &payload).await?;
+
+    let existing = UserPasskey::list_for_user(&database, &auth.user_id)
+        .await?
+        .into_iter()
+        .map(|p| p.credential_id)
+        .collect();
+
+    let challenge = WebauthnChallenge::new(
+        &database,
+        Ceremony::Registration,
+        Some(auth.user_id.clone()),
+    )
+    .await?;
+
+    Ok(web::Json(CreationOptions::new(
+        &RelyingParty::from_config(&config),
+        challenge.challenge,
+        &auth.user_id,
+        &auth.user.email,
+        &auth.user.name,
+        existing,
+    )))
+}
+
+/// Finish registering a passkey.
+/// The passkey can be used to log in from now on.
+/// The credentials of the user were checked when the registration was started.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the name is invalid
+/// - If the credential is invalid, or was not created for a registration the user started
+/// - If the passkey is already registered
+/// - If a query fails
+pub async fn finish(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<FinishRequest>,
+) -> WebResult<web::Json<Passkey>> {
+    require_passkey_support(&config, &database)?;
+
+    if !is_valid_name(&payload.name) {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let challenge = payload
+        .credential
+        .challenge()
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| WebErrorKind::BadRequest)?;
+
+    // The challenge is removed, so it cannot be used to register another passkey
+    WebauthnChallenge::take(&database, &challenge, Ceremony::Registration)
+        .await?
+        .filter(|c| c.user_id.as_deref() == Some(auth.user_id.as_str()))
+        .ok_or(WebErrorKind::BadRequest)?;
+
+    let registered = payload
+        .credential
+        .verify(&RelyingParty::from_config(&config), &challenge)
+        .tap_err(|e| warn!("{e}"))
+        .map_err(|_| WebErrorKind::BadRequest)?;
+
+    if UserPasskey::get_by_credential_id(&database, &registered.credential_id)
+        .await?
+        .is_some()
+    {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let passkey = UserPasskey::new(
+        &database,
+        registered.credential_id,
+        auth.user_id,
+        payload.name.trim().to_string(),
+        registered.public_key,
+        registered.sign_count as i64,
+    )
+    .await?;
+
+    Ok(web::Json(passkey.into()))
+}
@@ -0,0 +1,47 @@
+use crate::response_types::Empty;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::passkeys::{require_passkey_support, verify_credentials, Credentials};
+use crate::routes::{WConfig, WDatabase};
+use actix_web::{web, HttpRequest};
+use database::passkey::UserPasskey;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The passkey to remove
+    credential_id: String,
+    #[serde(flatten)]
+    credentials: Credentials,
+}
+
+/// Remove a passkey of the user. It can no longer be used to log in.
+/// The passkey itself remains stored on the user's device.
+/// The user must enter their password, and their second factor if they enabled TOTP.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the password or code is invalid
+/// - If too many invalid passwords or codes were provided
+/// - If the user has no such passkey
+/// - If a query fails
+pub async fn remove(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    req: HttpRequest,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    require_passkey_support(&config, &database)?;
+    verify_credentials(&auth, &config, &database, &req, &payload.credentials).await?;
+
+    let passkey = UserPasskey::get_by_credential_id(&database, &payload.credential_id)
+        .await?
+        .filter(|p| p.user_id.eq(&auth.user_id))
+        .ok_or(WebErrorKind::NotFound)?;
+
+    passkey.delete(&database).await?;
+
+    Ok(Empty)
+}
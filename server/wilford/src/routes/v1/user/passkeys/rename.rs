@@ -0,0 +1,45 @@
+use crate::response_types::Empty;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::passkeys::{is_valid_name, require_passkey_support};
+use crate::routes::{WConfig, WDatabase};
+use actix_web::web;
+use database::passkey::UserPasskey;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The passkey to rename
+    credential_id: String,
+    name: String,
+}
+
+/// Rename a passkey of the user.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support passkeys
+/// - If the name is invalid
+/// - If the user has no such passkey
+/// - If a query fails
+pub async fn rename(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    require_passkey_support(&config, &database)?;
+
+    if !is_valid_name(&payload.name) {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let mut passkey = UserPasskey::get_by_credential_id(&database, &payload.credential_id)
+        .await?
+        .filter(|p| p.user_id.eq(&auth.user_id))
+        .ok_or(WebErrorKind::NotFound)?;
+
+    passkey.set_name(&database, payload.name.trim()).await?;
+
+    Ok(Empty)
+}
@@ -3,12 +3,13 @@ use crate::authorization::AuthorizationProvider;
 use crate::mail::WilfordMailer;
 use crate::response_types::Empty;
 use crate::routes::error::{WebErrorKind, WebResult};
-use crate::routes::{auth_error_to_web_error, WConfig, WDatabase};
+use crate::routes::{WConfig, WDatabase, WRateLimiter};
 use actix_web::web;
-use database::user::{Locale, User};
+use database::password_reset::PasswordResetToken;
+use database::user::User;
 use mailer::{PasswordForgottenData, PasswordForgottenMail};
-use rand::Rng;
 use serde::Deserialize;
+use tracing::warn;
 
 #[derive(Deserialize)]
 pub struct Request {
@@ -16,39 +17,46 @@ pub struct Request {
     email: String,
 }
 
-/// Request a temporary password for the user email provided.
-/// This will send an email to the configured email address with a temporary password.
+/// Request a password reset link for the user email provided.
+/// This will send an email to the configured email address with a single-use link,
+/// with which the user can choose a new password.
+/// The current password remains valid until the link is used.
+///
+/// Succeeds if the email address does not belong to any user,
+/// so the endpoint does not reveal which email addresses are in use.
 ///
 /// # Errors
 ///
-/// - If the email address does not belong to any user
 /// - If the operation is not supported
+/// - If too many emails were sent to the email address
 /// - If the operation fails
 pub async fn password_forgotten(
     config: WConfig,
     database: WDatabase,
+    rate_limiter: WRateLimiter,
     payload: web::Json<Request>,
 ) -> WebResult<Empty> {
+    let provider = CombinedAuthorizationProvider::new(&config, &database);
+    if !provider.supports_password_change() {
+        return Err(WebErrorKind::Unsupported.into());
+    }
+
+    // Limited whether the user exists or not, so the limit does not reveal it
+    rate_limiter
+        .take_email_address(&payload.email, &config.rate_limit)
+        .map_err(WebErrorKind::TooManyRequests)?;
+
     // Fetch the user
     let user = match User::get_by_email(&database, &payload.email).await? {
         Some(user) => user,
         None => return Ok(Empty),
     };
 
-    let provider = CombinedAuthorizationProvider::new(&config, &database);
-    if !provider.supports_password_change() {
-        return Err(WebErrorKind::Unsupported.into());
-    }
+    // Replaces any link sent before
+    let token = PasswordResetToken::new(&database, &user.user_id).await?;
+    let reset_link = format!("{}?token={token}", config.password_reset_page());
 
-    // Update the password in the database
-    let tmp_password = tmp_password();
-    auth_error_to_web_error(
-        provider
-            .set_password(&user.user_id, &tmp_password, true)
-            .await,
-    )?;
-
-    // Email the user with their temporary password
+    // Email the user with the reset link
     if let Some(email_cfg) = &config.email {
         WilfordMailer::new(email_cfg)
             .send_email(
@@ -56,21 +64,18 @@ pub async fn password_forgotten(
                 PasswordForgottenMail,
                 &PasswordForgottenData {
                     name: user.name,
-                    temporary_password: tmp_password,
+                    reset_link,
                 },
-                Locale::Nl,
+                user.locale,
             )
             .await?;
+    } else {
+        // The link grants access to the account, so it is not logged
+        warn!(
+            "No email configured, cannot send a password reset link to {}",
+            user.user_id
+        );
     }
 
     Ok(Empty)
 }
-
-/// Generate a temporary password
-fn tmp_password() -> String {
-    rand::thread_rng()
-        .sample_iter(rand::distributions::Alphanumeric)
-        .take(16)
-        .map(char::from)
-        .collect()
-}
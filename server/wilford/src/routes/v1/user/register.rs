@@ -3,7 +3,7 @@ use crate::authorization::AuthorizationProvider;
 use crate::mail::WilfordMailer;
 use crate::routes::error::{WebErrorKind, WebResult};
 use crate::routes::v1::user::email_verify_link;
-use crate::routes::{auth_error_to_web_error, WConfig, WDatabase};
+use crate::routes::{auth_error_to_web_error, WConfig, WDatabase, WRateLimiter};
 use actix_web::web;
 use database::user::{Locale, User};
 use mailer::{VerifyEmailData, VerifyEmailEmail};
@@ -34,17 +34,23 @@ pub struct Response {
 ///
 /// - If the provider does not support registering new users
 /// - If the provided e-mail address already exists
+/// - If too many emails were sent to the e-mail address
 /// - If the underlying operation fails
 pub async fn register(
     payload: web::Json<Request>,
     config: WConfig,
     database: WDatabase,
+    rate_limiter: WRateLimiter,
 ) -> WebResult<web::Json<Response>> {
     let provider = CombinedAuthorizationProvider::new(&config, &database);
     if !provider.supports_registration() {
         return Err(WebErrorKind::Unsupported.into());
     }
 
+    rate_limiter
+        .take_email_address(&payload.email, &config.rate_limit)
+        .map_err(WebErrorKind::TooManyRequests)?;
+
     let payload = payload.into_inner();
 
     let first_user = User::count(&database).await? == 0;
@@ -0,0 +1,100 @@
+use crate::authorization::combined::CombinedAuthorizationProvider;
+use crate::authorization::AuthorizationProvider;
+use crate::backchannel_logout;
+use crate::mail::WilfordMailer;
+use crate::response_types::Empty;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::normalize;
+use crate::routes::{auth_error_to_web_error, WConfig, WDatabase};
+use actix_web::web;
+use database::login_failure::{LoginFailureKind, LoginFailures};
+use database::oauth2_client::OAuth2Client;
+use database::password_reset::PasswordResetToken;
+use database::session::UserSession;
+use database::user::User;
+use mailer::{PasswordChangedData, PasswordChangedMail};
+use serde::Deserialize;
+use tracing::instrument;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The token from the password reset link
+    token: String,
+    /// The new password
+    new_password: String,
+}
+
+/// Set a new password with the token from a password reset link.
+/// The token can be used once. On success, the user is logged out everywhere:
+/// all login sessions are terminated and all tokens issued to the user are revoked.
+///
+/// # Errors
+///
+/// - If the provider does not support changing passwords
+/// - If the token does not exist, was used before or has expired
+/// - If the change operation fails
+#[instrument(skip_all)]
+pub async fn reset_password(
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    let provider = CombinedAuthorizationProvider::new(&config, &database);
+    if !provider.supports_password_change() {
+        return Err(WebErrorKind::Unsupported.into());
+    }
+
+    let reset = PasswordResetToken::take(&database, &payload.token)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+    let user = User::get_by_id(&database, &reset.user_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    // Taking the token first makes sure concurrent requests cannot both use it
+    let changed = auth_error_to_web_error(
+        provider
+            .set_password(&user.user_id, &payload.new_password, false)
+            .await,
+    );
+    if let Err(e) = changed {
+        // The password was not changed, so the link may be used again
+        reset.restore(&database).await?;
+        return Err(e);
+    }
+
+    // Whoever knew the old password should no longer have access.
+    // Collected before the tokens are revoked, as the tokens determine which clients the user is logged in to
+    let clients = OAuth2Client::list_for_backchannel_logout(&database, &user.user_id).await?;
+    User::revoke_tokens(&database, &user.user_id).await?;
+    UserSession::terminate_all(&database, &user.user_id).await?;
+
+    backchannel_logout::notify(
+        database.get_ref().clone(),
+        config.oidc_issuer.clone(),
+        clients,
+        user.user_id.clone(),
+    );
+
+    // The user proved access to their email address, so a lockout no longer applies
+    LoginFailures::clear(
+        &database,
+        LoginFailureKind::Account,
+        &normalize(&user.email),
+    )
+    .await?;
+
+    // Inform user of password change via email
+    if let Some(email_cfg) = &config.email {
+        WilfordMailer::new(email_cfg)
+            .send_email(
+                &user.email,
+                PasswordChangedMail,
+                &PasswordChangedData { name: user.name },
+                user.locale,
+            )
+            .await?;
+    }
+
+    Ok(Empty)
+}
@@ -0,0 +1,55 @@
+use actix_web::web;
+use serde::Serialize;
+
+use database::session::UserSession;
+
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::{Auth, SessionAuth};
+use crate::routes::error::WebResult;
+
+#[derive(Serialize)]
+pub struct Response {
+    sessions: Vec<Session>,
+}
+
+#[derive(Serialize)]
+pub struct Session {
+    session_id: String,
+    /// UNIX timestamp at which the user logged in
+    auth_time: i64,
+    /// The authentication methods used to log in
+    amr: Vec<String>,
+    /// UNIX timestamp at which the session was last used to authorize a client
+    last_used_at: i64,
+    /// UNIX timestamp at which the session expires
+    expires_at: i64,
+    /// The user agent the user logged in with
+    user_agent: Option<String>,
+    /// Whether this is the session of the browser making the request
+    current: bool,
+}
+
+/// List the active login sessions of the user.
+pub async fn list(
+    database: WDatabase,
+    auth: Auth,
+    current: Option<SessionAuth>,
+) -> WebResult<web::Json<Response>> {
+    let current = current.map(|c| c.session.session_id);
+
+    let sessions = UserSession::list_for_user(&database, &auth.user_id)
+        .await?
+        .into_iter()
+        .map(|s| Session {
+            amr: s.amr(),
+            current: current.as_ref() == Some(&s.session_id),
+            session_id: s.session_id,
+            auth_time: s.auth_time,
+            last_used_at: s.last_used_at,
+            expires_at: s.expires_at,
+            user_agent: s.user_agent,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(web::Json(Response { sessions }))
+}
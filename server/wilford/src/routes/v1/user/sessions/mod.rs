@@ -0,0 +1,18 @@
+use actix_route_config::Routable;
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+mod list;
+mod terminate;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("sessions")
+                .route("/list", web::get().to(list::list))
+                .route("/terminate", web::delete().to(terminate::terminate)),
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use actix_web::web;
+use serde::Deserialize;
+
+use database::session::UserSession;
+
+use crate::response_types::Empty;
+use crate::routes::appdata::WDatabase;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The session to terminate
+    session_id: String,
+}
+
+/// Terminate a login session of the user.
+/// The user has to log in again in the browser the session belongs to.
+/// Tokens already issued to clients are not revoked.
+pub async fn terminate(
+    database: WDatabase,
+    auth: Auth,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    let session = UserSession::list_for_user(&database, &auth.user_id)
+        .await?
+        .into_iter()
+        .find(|s| s.session_id.eq(&payload.session_id))
+        .ok_or(WebErrorKind::NotFound)?;
+
+    session.terminate(&database).await?;
+
+    Ok(Empty)
+}
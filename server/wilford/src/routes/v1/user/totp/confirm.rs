@@ -0,0 +1,56 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::totp::require_local_provider;
+use crate::routes::{WConfig, WDatabase};
+use crate::totp;
+use actix_web::cookie::time::OffsetDateTime;
+use actix_web::web;
+use database::totp::UserTotp;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// A code generated from the secret returned by the enroll endpoint
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    /// Single-use codes the user can log in with if they lose access to their authenticator app.
+    /// These cannot be retrieved later.
+    recovery_codes: Vec<String>,
+}
+
+/// Confirm the TOTP enrollment of the user with a first code, enabling TOTP.
+/// From now on, a code is required when logging in.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If the user did not start enrolling, or already enabled TOTP
+/// - If the code is invalid
+/// - If a query fails
+pub async fn confirm(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<web::Json<Response>> {
+    require_local_provider(&config)?;
+
+    let mut totp = UserTotp::get(&database, &auth.user_id)
+        .await?
+        .filter(|t| !t.enabled)
+        .ok_or(WebErrorKind::NotFound)?;
+
+    let step = totp::verify(
+        &totp.secret,
+        &payload.code,
+        OffsetDateTime::now_utc().unix_timestamp(),
+    )
+    .ok_or(WebErrorKind::BadRequest)?;
+
+    let recovery_codes = totp.enable(&database, step).await?;
+
+    Ok(web::Json(Response { recovery_codes }))
+}
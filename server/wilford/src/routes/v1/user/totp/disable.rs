@@ -0,0 +1,70 @@
+use crate::authorization::combined::CombinedAuthorizationProvider;
+use crate::authorization::{AuthorizationProvider, SecondFactor};
+use crate::response_types::Empty;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::lockout::LoginThrottle;
+use crate::routes::v1::user::totp::require_local_provider;
+use crate::routes::{auth_error_to_web_error, Either, WConfig, WDatabase};
+use actix_web::{web, HttpRequest};
+use database::totp::UserTotp;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The password of the user
+    password: String,
+    /// A TOTP code or recovery code
+    code: String,
+}
+
+/// Disable TOTP two-factor authentication for the user.
+/// The recovery codes of the user are removed.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If the password or code is invalid
+/// - If too many invalid passwords or codes were provided
+/// - If the user did not enable TOTP
+/// - If a query fails
+pub async fn disable(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    req: HttpRequest,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    require_local_provider(&config)?;
+
+    if !UserTotp::get(&database, &auth.user_id)
+        .await?
+        .is_some_and(|t| t.enabled)
+    {
+        return Err(WebErrorKind::NotFound.into());
+    }
+
+    // Both factors are required, so a stolen access token alone cannot disable TOTP
+    let provider = CombinedAuthorizationProvider::new(&config, &database);
+    let throttle = LoginThrottle::new(&database, &config, &req);
+    throttle.check(&auth.user.email).await?;
+
+    let validation = provider
+        .validate_credentials(
+            &auth.user.email,
+            &payload.password,
+            Some(SecondFactor::Totp(&payload.code)),
+        )
+        .await;
+    throttle.record(&auth.user.email, &validation).await?;
+    let validation = auth_error_to_web_error(validation)?;
+
+    // Only returned if the code is empty
+    if let Either::Right(()) = validation {
+        return Err(WebErrorKind::Unauthorized.into());
+    }
+
+    UserTotp::delete(&database, &auth.user_id).await?;
+
+    Ok(Empty)
+}
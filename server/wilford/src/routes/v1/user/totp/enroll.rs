@@ -0,0 +1,54 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::totp::require_local_provider;
+use crate::routes::{WConfig, WDatabase};
+use crate::totp;
+use actix_web::web;
+use database::totp::UserTotp;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Serialize)]
+pub struct Response {
+    /// The base32 encoded secret, for entering into an authenticator app manually
+    secret: String,
+    /// The `otpauth://` URI of the secret, to be shown as QR code
+    otpauth_uri: String,
+}
+
+/// Start enrolling the user in TOTP two-factor authentication.
+/// A new secret is generated, replacing the secret of an earlier enrollment that was not confirmed.
+/// TOTP is enabled once the enrollment is confirmed with a code generated from the secret.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If the user already enabled TOTP
+/// - If a query fails
+pub async fn enroll(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+) -> WebResult<web::Json<Response>> {
+    require_local_provider(&config)?;
+
+    if UserTotp::get(&database, &auth.user_id)
+        .await?
+        .is_some_and(|t| t.enabled)
+    {
+        return Err(WebErrorKind::BadRequest.into());
+    }
+
+    let totp = UserTotp::new_pending(&database, &auth.user_id, totp::generate_secret()).await?;
+
+    // Authenticator apps show the issuer with the account, use the host users know the server by
+    let issuer = Url::parse(&config.oidc_issuer)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_else(|| "Wilford".to_string());
+
+    Ok(web::Json(Response {
+        otpauth_uri: totp::otpauth_uri(&issuer, &auth.user.email, &totp.secret),
+        secret: totp.secret,
+    }))
+}
@@ -0,0 +1,72 @@
+use crate::authorization::local_provider::LocalAuthorizationProvider;
+use crate::authorization::{AuthorizationError, SecondFactor};
+use crate::config::{AuthorizationProviderType, Config};
+use crate::routes::error::{WebErrorKind, WebResult};
+use actix_route_config::Routable;
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+use database::driver::Database;
+use database::user::User;
+use tap::TapFallible;
+use tracing::warn;
+
+mod confirm;
+mod disable;
+mod enroll;
+mod recovery_codes;
+mod reset;
+mod status;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("totp")
+                .route("/status", web::get().to(status::status))
+                .route("/enroll", web::post().to(enroll::enroll))
+                .route("/confirm", web::post().to(confirm::confirm))
+                .route("/disable", web::post().to(disable::disable))
+                .route(
+                    "/recovery-codes",
+                    web::post().to(recovery_codes::recovery_codes),
+                )
+                .route("/reset", web::post().to(reset::reset)),
+        );
+    }
+}
+
+/// TOTP is managed by Wilford only for the local authorization provider.
+/// EspoCRM users configure two-factor authentication in EspoCRM.
+///
+/// # Errors
+///
+/// If another authorization provider is configured
+fn require_local_provider(config: &Config) -> WebResult<()> {
+    match config.authorization_provider {
+        AuthorizationProviderType::Local => Ok(()),
+        _ => Err(WebErrorKind::Unsupported.into()),
+    }
+}
+
+/// Check a TOTP or recovery code of a user with TOTP enabled.
+///
+/// # Errors
+///
+/// - If the code is invalid or was used before
+/// - If the user did not enable TOTP
+/// - If a query fails
+async fn verify_code(database: &Database, user: &User, code: &str) -> WebResult<()> {
+    match LocalAuthorizationProvider::new(database)
+        .verify_second_factor(user, Some(SecondFactor::Totp(code)))
+        .await
+        .tap_err(|e| warn!("{e}"))
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(WebErrorKind::BadRequest.into()),
+        Err(AuthorizationError::InvalidCredentials | AuthorizationError::TotpNeeded) => {
+            Err(WebErrorKind::Unauthorized.into())
+        }
+        Err(_) => Err(WebErrorKind::InternalServerError.into()),
+    }
+}
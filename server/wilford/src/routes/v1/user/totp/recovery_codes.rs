@@ -0,0 +1,48 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::totp::{require_local_provider, verify_code};
+use crate::routes::{WConfig, WDatabase};
+use actix_web::web;
+use database::totp::UserTotp;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// A TOTP code or recovery code
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    /// The new recovery codes. These cannot be retrieved later.
+    recovery_codes: Vec<String>,
+}
+
+/// Generate new recovery codes for the user.
+/// The previous recovery codes can no longer be used.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If the user did not enable TOTP
+/// - If the code is invalid
+/// - If a query fails
+pub async fn recovery_codes(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<web::Json<Response>> {
+    require_local_provider(&config)?;
+
+    let totp = UserTotp::get(&database, &auth.user_id)
+        .await?
+        .filter(|t| t.enabled)
+        .ok_or(WebErrorKind::NotFound)?;
+
+    verify_code(&database, &auth.user, &payload.code).await?;
+
+    Ok(web::Json(Response {
+        recovery_codes: totp.regenerate_recovery_codes(&database).await?,
+    }))
+}
@@ -0,0 +1,47 @@
+use crate::response_types::Empty;
+use crate::routes::auth::Auth;
+use crate::routes::error::{WebErrorKind, WebResult};
+use crate::routes::v1::user::totp::require_local_provider;
+use crate::routes::v1::MANAGE_SCOPE;
+use crate::routes::{WConfig, WDatabase};
+use actix_web::web;
+use database::totp::UserTotp;
+use database::user::User;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Request {
+    /// The user to reset TOTP for
+    user_id: String,
+}
+
+/// Disable TOTP two-factor authentication for another user,
+/// e.g. when they lost both their authenticator app and their recovery codes.
+/// The user can enroll again after logging in with only their password.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If the caller is not permitted to manage users
+/// - If the user does not exist
+/// - If a query fails
+pub async fn reset(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+    payload: web::Json<Request>,
+) -> WebResult<Empty> {
+    require_local_provider(&config)?;
+
+    if !auth.has_scope(MANAGE_SCOPE) {
+        return Err(WebErrorKind::Forbidden.into());
+    }
+
+    User::get_by_id(&database, &payload.user_id)
+        .await?
+        .ok_or(WebErrorKind::NotFound)?;
+
+    UserTotp::delete(&database, &payload.user_id).await?;
+
+    Ok(Empty)
+}
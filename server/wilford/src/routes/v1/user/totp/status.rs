@@ -0,0 +1,42 @@
+use crate::routes::auth::Auth;
+use crate::routes::error::WebResult;
+use crate::routes::v1::user::totp::require_local_provider;
+use crate::routes::{WConfig, WDatabase};
+use actix_web::web;
+use database::totp::UserTotp;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Response {
+    /// Whether TOTP is required when logging in
+    enabled: bool,
+    /// The number of unused recovery codes
+    recovery_codes_remaining: i64,
+}
+
+/// Get whether the user enabled TOTP two-factor authentication.
+///
+/// # Errors
+///
+/// - If the authorization provider does not support TOTP
+/// - If a query fails
+pub async fn status(
+    auth: Auth,
+    config: WConfig,
+    database: WDatabase,
+) -> WebResult<web::Json<Response>> {
+    require_local_provider(&config)?;
+
+    let response = match UserTotp::get(&database, &auth.user_id).await? {
+        Some(totp) if totp.enabled => Response {
+            enabled: true,
+            recovery_codes_remaining: totp.count_recovery_codes(&database).await?,
+        },
+        _ => Response {
+            enabled: false,
+            recovery_codes_remaining: 0,
+        },
+    };
+
+    Ok(web::Json(response))
+}
@@ -1,9 +1,13 @@
 use crate::routes::appdata::WConfig;
+use crate::routes::oauth::prompt;
 use actix_web::web;
+use database::oauth2_client::TokenEndpointAuthMethod;
+use database::signing_key::JwtSigningAlgorithm;
 use serde::Serialize;
 
 /// # Further reading
-/// - [OpenID Connect Discovery 1.0, Section 4.1](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderConfig)
+/// - [OpenID Connect Discovery 1.0, Section 3](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata)
+/// - [RFC 8414, Section 2](https://datatracker.ietf.org/doc/html/rfc8414#section-2)
 #[derive(Serialize)]
 pub struct OpenidConfiguration {
     /// The isser of all JWT tokens
@@ -12,32 +16,123 @@ pub struct OpenidConfiguration {
     authorization_endpoint: String,
     /// The server's endpoint for exchanging a code or refresh token for an access token
     token_endpoint: String,
+    /// The server's endpoint for retrieving claims about the authenticated user
+    userinfo_endpoint: String,
+    /// The endpoint for where the JWKS document can found
+    jwks_uri: String,
+    /// The server's endpoint for revoking access and refresh tokens
+    revocation_endpoint: String,
+    /// The server's endpoint for introspecting access tokens
+    introspection_endpoint: String,
+    /// The server's endpoint for logging the user out, RP-initiated logout
+    end_session_endpoint: String,
+    /// The server's endpoint for registering clients, which requires an initial access token
+    registration_endpoint: String,
+    /// Whether clients can be notified of logouts with a logout token
+    backchannel_logout_supported: bool,
+    /// Whether logout tokens contain the `sid` claim
+    backchannel_logout_session_supported: bool,
+    /// The scopes with a meaning defined by OpenID Connect.
+    /// Other scopes can be granted to users and clients as well.
+    scopes_supported: Vec<&'static str>,
     /// The response types we support in the authorization endpoint
-    response_types_supported: Vec<String>,
+    response_types_supported: Vec<&'static str>,
+    /// How the authorization response is returned to the client
+    response_modes_supported: Vec<&'static str>,
     /// The grant types we support in the token endpoint
-    grant_types_supported: Vec<String>,
+    grant_types_supported: Vec<&'static str>,
+    /// The subject identifier types we support.
+    /// The subject is the same for every client.
+    subject_types_supported: Vec<&'static str>,
     /// The algorithms we support for signing JWTs
-    id_token_signing_alg_values_supported: Vec<String>,
-    /// The endpoint for where the JWKS document can found
-    jwks_uri: String,
+    id_token_signing_alg_values_supported: Vec<&'static str>,
+    /// How clients can authenticate to the token endpoint
+    token_endpoint_auth_methods_supported: Vec<&'static str>,
+    /// The algorithms we support for client assertions, with the `private_key_jwt` method
+    token_endpoint_auth_signing_alg_values_supported: Vec<&'static str>,
+    /// How clients can authenticate to the revocation endpoint
+    revocation_endpoint_auth_methods_supported: Vec<&'static str>,
+    /// The algorithms we support for client assertions at the revocation endpoint
+    revocation_endpoint_auth_signing_alg_values_supported: Vec<&'static str>,
+    /// The PKCE code challenge methods we support
+    code_challenge_methods_supported: Vec<&'static str>,
+    /// The values of the `prompt` authorization request parameter we support
+    prompt_values_supported: Vec<&'static str>,
+    /// The claims we can provide in ID tokens and from the UserInfo endpoint
+    claims_supported: Vec<&'static str>,
 }
 
-/// Get the OpenID configuration for this server
+/// Get the OpenID configuration for this server.
+/// Endpoint URLs are derived from the issuer, unless configured otherwise.
 ///
 /// # Further reading
 /// - [OpenID Connect Discovery 1.0, Section 4](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderConfig)
 pub async fn openid_configuration(config: WConfig) -> web::Json<OpenidConfiguration> {
+    let http = &config.http;
+
     web::Json(OpenidConfiguration {
         issuer: config.oidc_issuer.clone(),
-        authorization_endpoint: config.http.authorization_endpoint.clone(),
-        token_endpoint: config.http.token_endpoint.clone(),
-        response_types_supported: vec![
-            "code".to_string(),
-            "id_token token".to_string(),
-            "token".to_string(),
+        authorization_endpoint: config
+            .endpoint_url(&http.authorization_endpoint, "/api/oauth/authorize"),
+        token_endpoint: config.endpoint_url(&http.token_endpoint, "/api/oauth/token"),
+        userinfo_endpoint: config.endpoint_url(&http.userinfo_endpoint, "/api/oauth/userinfo"),
+        jwks_uri: config.endpoint_url(&http.jwks_uri_endpoint, "/.well-known/jwks.json"),
+        revocation_endpoint: config.endpoint_url(&http.revocation_endpoint, "/api/oauth/revoke"),
+        introspection_endpoint: config
+            .endpoint_url(&http.introspection_endpoint, "/api/oauth/introspect"),
+        end_session_endpoint: config.endpoint_url(&http.end_session_endpoint, "/api/oauth/logout"),
+        registration_endpoint: config
+            .endpoint_url(&http.registration_endpoint, "/api/oauth/register"),
+        backchannel_logout_supported: true,
+        backchannel_logout_session_supported: false,
+        scopes_supported: vec!["openid", "profile", "email"],
+        response_types_supported: vec!["code", "id_token token", "token"],
+        response_modes_supported: vec!["query", "fragment"],
+        grant_types_supported: vec![
+            "authorization_code",
+            "implicit",
+            "refresh_token",
+            "client_credentials",
+        ],
+        subject_types_supported: vec!["public"],
+        id_token_signing_alg_values_supported: signing_algs(),
+        // Public clients don't authenticate, they use `none`
+        token_endpoint_auth_methods_supported: auth_methods(),
+        token_endpoint_auth_signing_alg_values_supported: signing_algs(),
+        revocation_endpoint_auth_methods_supported: auth_methods(),
+        revocation_endpoint_auth_signing_alg_values_supported: signing_algs(),
+        code_challenge_methods_supported: vec!["S256", "plain"],
+        prompt_values_supported: prompt::KNOWN.to_vec(),
+        claims_supported: vec![
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "azp",
+            "at_hash",
+            "name",
+            "preferred_username",
+            "locale",
+            "updated_at",
+            "email",
+            "email_verified",
         ],
-        grant_types_supported: vec!["authorization_code".to_string(), "implicit".to_string()],
-        id_token_signing_alg_values_supported: vec!["RS256".to_string()],
-        jwks_uri: config.http.jwks_uri_endpoint.clone(),
     })
 }
+
+fn auth_methods() -> Vec<&'static str> {
+    TokenEndpointAuthMethod::ALL
+        .iter()
+        .map(|method| method.name())
+        .collect()
+}
+
+fn signing_algs() -> Vec<&'static str> {
+    JwtSigningAlgorithm::ALL
+        .iter()
+        .map(|alg| alg.name())
+        .collect()
+}
@@ -0,0 +1,436 @@
+//! Rotation of the keys used to sign OpenID Connect ID tokens.
+//!
+//! Keys go through the states `Next`, `Active` and `Retired`.
+//! A next key is published before it is used for signing, so clients caching the JWKS document
+//! know it by the time it becomes active. A retired key remains published until the ID tokens
+//! signed with it have expired.
+//!
+//! Every signing algorithm has its own set of keys, which are rotated independently.
+
+use crate::config::Config;
+use actix_web::cookie::time::OffsetDateTime;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use database::driver::Database;
+use database::signing_key::{JwtSigningAlgorithm, SigningKey, SigningKeyState};
+use jwt_simple::algorithms::{
+    ECDSAP256PublicKeyLike, ES256KeyPair, ES256PublicKey, Ed25519KeyPair, Ed25519PublicKey,
+    EdDSAPublicKeyLike, Edwards25519PublicKey, P256PublicKey, RS256PublicKey, RSAPublicKeyLike,
+};
+use jwt_simple::claims::JWTClaims;
+use jwt_simple::common::VerificationOptions;
+use jwt_simple::token::Token;
+use rsa::pkcs8::{DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, RsaPrivateKey, RsaPublicKey};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// How often to check whether the active key should be rotated
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The size of generated RSA keys, in bits
+const RSA_KEY_SIZE: usize = 2048;
+
+#[derive(Debug, Error)]
+pub enum SigningKeyError {
+    #[error("{0}")]
+    Database(#[from] database::driver::Error),
+    #[error("Failed to generate key: {0}")]
+    Rsa(#[from] rsa::Error),
+    #[error("Failed to encode private key: {0}")]
+    Pkcs8(#[from] rsa::pkcs8::Error),
+    #[error("Failed to parse or encode public key: {0}")]
+    Spki(#[from] rsa::pkcs8::spki::Error),
+    #[error("Key generation task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("Invalid key: {0}")]
+    Key(String),
+}
+
+/// The public key members of a JWK, with the key type as the `kty` member.
+///
+/// # Further reading
+/// - RSA keys: [RFC 7518, Section 6.3.1](https://datatracker.ietf.org/doc/html/rfc7518#section-6.3.1)
+/// - EC keys: [RFC 7518, Section 6.2.1](https://datatracker.ietf.org/doc/html/rfc7518#section-6.2.1)
+/// - OKP keys: [RFC 8037, Section 2](https://datatracker.ietf.org/doc/html/rfc8037#section-2)
+#[derive(Debug, Serialize)]
+#[serde(tag = "kty")]
+pub enum PublicKeyParams {
+    #[serde(rename = "RSA")]
+    Rsa { n: String, e: String },
+    #[serde(rename = "EC")]
+    Ec {
+        crv: &'static str,
+        x: String,
+        y: String,
+    },
+    #[serde(rename = "OKP")]
+    Okp { crv: &'static str, x: String },
+}
+
+impl PublicKeyParams {
+    /// Get the public key members of a PEM encoded public key used with the algorithm.
+    ///
+    /// # Errors
+    ///
+    /// If the key cannot be parsed
+    pub fn from_pem(
+        algorithm: JwtSigningAlgorithm,
+        public_key_pem: &str,
+    ) -> Result<Self, SigningKeyError> {
+        Ok(match algorithm {
+            JwtSigningAlgorithm::RS256 => {
+                let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)?;
+                Self::Rsa {
+                    n: encode_uint(public_key.n()),
+                    e: encode_uint(public_key.e()),
+                }
+            }
+            JwtSigningAlgorithm::ES256 => {
+                // SEC1 uncompressed point: 0x04 || x || y
+                let point = P256PublicKey::from_pem(public_key_pem)
+                    .map_err(key_error)?
+                    .to_bytes_uncompressed();
+                let (x, y) = point[1..].split_at(32);
+
+                Self::Ec {
+                    crv: "P-256",
+                    x: BASE64_URL_SAFE_NO_PAD.encode(x),
+                    y: BASE64_URL_SAFE_NO_PAD.encode(y),
+                }
+            }
+            JwtSigningAlgorithm::EdDSA => Self::Okp {
+                crv: "Ed25519",
+                x: BASE64_URL_SAFE_NO_PAD.encode(
+                    Edwards25519PublicKey::from_pem(public_key_pem)
+                        .map_err(key_error)?
+                        .to_bytes(),
+                ),
+            },
+        })
+    }
+
+    /// Compute the JWK thumbprint of the key.
+    ///
+    /// # Further reading
+    /// - [RFC 7638, Section 3](https://datatracker.ietf.org/doc/html/rfc7638#section-3)
+    /// - [RFC 8037, Section 2](https://datatracker.ietf.org/doc/html/rfc8037#section-2)
+    pub fn thumbprint(&self) -> String {
+        // The required members, in lexicographic order and without whitespace
+        let jwk = match self {
+            Self::Rsa { n, e } => format!(r#"{{"e":"{e}","kty":"RSA","n":"{n}"}}"#),
+            Self::Ec { crv, x, y } => {
+                format!(r#"{{"crv":"{crv}","kty":"EC","x":"{x}","y":"{y}"}}"#)
+            }
+            Self::Okp { crv, x } => format!(r#"{{"crv":"{crv}","kty":"OKP","x":"{x}"}}"#),
+        };
+
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(jwk.as_bytes()))
+    }
+}
+
+/// Make sure there is an active and a next key for every algorithm.
+/// If no RS256 key exists yet, the key pair from the configuration is imported as the active RS256 key.
+///
+/// # Errors
+///
+/// If a key cannot be read, generated or stored
+pub async fn init(database: &Database, config: &Config) -> color_eyre::Result<()> {
+    for algorithm in JwtSigningAlgorithm::ALL {
+        if SigningKey::get_active(database, algorithm).await?.is_none() {
+            let has_keys = SigningKey::list(database)
+                .await?
+                .iter()
+                .any(|k| k.algorithm == algorithm);
+
+            if algorithm == JwtSigningAlgorithm::RS256 && !has_keys {
+                let public_key = config.read_oidc_public_key().await?;
+                let kid = kid(algorithm, &public_key)?;
+
+                SigningKey::new(
+                    database,
+                    kid.clone(),
+                    config.read_oidc_signing_key().await?,
+                    public_key,
+                    algorithm,
+                    SigningKeyState::Active,
+                )
+                .await?;
+
+                info!("Imported the configured OIDC signing key with kid {kid}");
+            } else {
+                rotate_algorithm(database, algorithm).await?;
+            }
+        }
+
+        if SigningKey::get_next(database, algorithm).await?.is_none() {
+            new_key(database, algorithm, SigningKeyState::Next).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rotate the signing keys of all algorithms.
+/// The next key becomes the active key, the active key is retired and a new next key is generated.
+///
+/// # Errors
+///
+/// If a key cannot be generated or stored
+pub async fn rotate(database: &Database) -> Result<(), SigningKeyError> {
+    for algorithm in JwtSigningAlgorithm::ALL {
+        rotate_algorithm(database, algorithm).await?;
+    }
+
+    Ok(())
+}
+
+/// Rotate the signing keys of a single algorithm.
+async fn rotate_algorithm(
+    database: &Database,
+    algorithm: JwtSigningAlgorithm,
+) -> Result<(), SigningKeyError> {
+    if !SigningKey::promote_next(database, algorithm).await? {
+        // Without a next key, the new key is used immediately
+        new_key(database, algorithm, SigningKeyState::Next).await?;
+        SigningKey::promote_next(database, algorithm).await?;
+    }
+
+    let next = new_key(database, algorithm, SigningKeyState::Next).await?;
+    info!(
+        "Rotated {} OIDC signing keys, next key has kid {}",
+        algorithm.name(),
+        next.kid
+    );
+
+    Ok(())
+}
+
+/// Spawn the rotation task in the background.
+pub fn spawn(database: Database, config: &Config) {
+    tokio::spawn(run(
+        database,
+        config.signing_keys.rotation_interval,
+        config.signing_keys.retired_key_lifetime,
+    ));
+}
+
+/// Periodically rotate the signing keys and delete expired retired keys.
+/// Runs until the process exits.
+async fn run(database: Database, rotation_interval: i64, retired_key_lifetime: i64) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = rotate_if_due(&database, rotation_interval, retired_key_lifetime).await {
+            warn!("Failed to rotate signing keys: {e}");
+        }
+    }
+}
+
+async fn rotate_if_due(
+    database: &Database,
+    rotation_interval: i64,
+    retired_key_lifetime: i64,
+) -> Result<(), SigningKeyError> {
+    for algorithm in JwtSigningAlgorithm::ALL {
+        let activated_at = SigningKey::get_active(database, algorithm)
+            .await?
+            .and_then(|k| k.activated_at)
+            .unwrap_or_default();
+
+        if activated_at <= OffsetDateTime::now_utc().unix_timestamp() - rotation_interval {
+            rotate_algorithm(database, algorithm).await?;
+        }
+    }
+
+    let deleted = SigningKey::delete_expired(database, retired_key_lifetime).await?;
+    debug!("Deleted {deleted} retired signing keys");
+
+    Ok(())
+}
+
+/// Generate and store a new key for the algorithm.
+async fn new_key(
+    database: &Database,
+    algorithm: JwtSigningAlgorithm,
+    state: SigningKeyState,
+) -> Result<SigningKey, SigningKeyError> {
+    let (private_key, public_key) = match algorithm {
+        JwtSigningAlgorithm::RS256 => {
+            // Generating an RSA key takes a while, don't block the runtime
+            let private_key = tokio::task::spawn_blocking(|| {
+                RsaPrivateKey::new(&mut rand::thread_rng(), RSA_KEY_SIZE)
+            })
+            .await??;
+            let public_key = RsaPublicKey::from(&private_key);
+
+            (
+                private_key.to_pkcs8_pem(LineEnding::LF)?.to_string(),
+                public_key.to_public_key_pem(LineEnding::LF)?,
+            )
+        }
+        JwtSigningAlgorithm::ES256 => {
+            let key = ES256KeyPair::generate();
+            (
+                key.to_pem().map_err(key_error)?,
+                key.public_key().to_pem().map_err(key_error)?,
+            )
+        }
+        JwtSigningAlgorithm::EdDSA => {
+            let key = Ed25519KeyPair::generate();
+            (key.to_pem(), key.public_key().to_pem())
+        }
+    };
+
+    Ok(SigningKey::new(
+        database,
+        kid(algorithm, &public_key)?,
+        private_key,
+        public_key,
+        algorithm,
+        state,
+    )
+    .await?)
+}
+
+/// Get the key ID of a PEM encoded public key used with the algorithm.
+///
+/// # Errors
+///
+/// If the key cannot be parsed
+pub fn kid(
+    algorithm: JwtSigningAlgorithm,
+    public_key_pem: &str,
+) -> Result<String, SigningKeyError> {
+    Ok(PublicKeyParams::from_pem(algorithm, public_key_pem)?.thumbprint())
+}
+
+/// Verify a JWT signed with one of our keys.
+/// The key is looked up by the `kid` in the token header,
+/// and the `alg` in the header must match the key's algorithm.
+/// Returns `None` if the key is unknown or the token is not valid.
+///
+/// # Errors
+///
+/// If the query fails
+pub async fn verify<C: Serialize + DeserializeOwned>(
+    database: &Database,
+    token: &str,
+    options: Option<VerificationOptions>,
+) -> Result<Option<JWTClaims<C>>, database::driver::Error> {
+    let Ok(metadata) = Token::decode_metadata(token) else {
+        return Ok(None);
+    };
+
+    let Some(kid) = metadata.key_id() else {
+        return Ok(None);
+    };
+
+    let Some(signing_key) = SigningKey::get_by_kid(database, kid).await? else {
+        return Ok(None);
+    };
+
+    if metadata.algorithm() != signing_key.algorithm.name() {
+        return Ok(None);
+    }
+
+    match verify_with_key(&signing_key, token, options) {
+        Ok(claims) => Ok(Some(claims)),
+        Err(e) => {
+            debug!("Rejected JWT: {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Verify the signature and validity period of a JWT signed with the key.
+///
+/// # Errors
+///
+/// If the key cannot be parsed or the token is not valid
+pub fn verify_with_key<C: Serialize + DeserializeOwned>(
+    signing_key: &SigningKey,
+    token: &str,
+    options: Option<VerificationOptions>,
+) -> Result<JWTClaims<C>, jwt_simple::Error> {
+    match signing_key.algorithm {
+        JwtSigningAlgorithm::RS256 => {
+            RS256PublicKey::from_pem(&signing_key.public_key)?.verify_token(token, options)
+        }
+        JwtSigningAlgorithm::ES256 => {
+            ES256PublicKey::from_pem(&signing_key.public_key)?.verify_token(token, options)
+        }
+        JwtSigningAlgorithm::EdDSA => {
+            Ed25519PublicKey::from_pem(&signing_key.public_key)?.verify_token(token, options)
+        }
+    }
+}
+
+fn key_error(e: jwt_simple::Error) -> SigningKeyError {
+    SigningKeyError::Key(e.to_string())
+}
+
+/// Encode an unsigned integer as base64url, without padding.
+///
+/// # Further reading
+/// - [RFC 7518, Section 6.3.1.1](https://datatracker.ietf.org/doc/html/rfc7518#section-6.3.1.1)
+fn encode_uint(value: &BigUint) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(value.to_bytes_be())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rfc7638_thumbprint() {
+        // Example from RFC 7638, Section 3.1
+        let params = PublicKeyParams::Rsa {
+            n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".to_string(),
+            e: "AQAB".to_string(),
+        };
+
+        assert_eq!(
+            params.thumbprint(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn rfc8037_thumbprint() {
+        // Example from RFC 8037, Appendix A.3
+        let params = PublicKeyParams::Okp {
+            crv: "Ed25519",
+            x: "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo".to_string(),
+        };
+
+        assert_eq!(
+            params.thumbprint(),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn ec_params() {
+        let key = ES256KeyPair::generate();
+        let params = PublicKeyParams::from_pem(
+            JwtSigningAlgorithm::ES256,
+            &key.public_key().to_pem().unwrap(),
+        )
+        .unwrap();
+
+        let PublicKeyParams::Ec { x, y, .. } = params else {
+            panic!("Expected EC parameters");
+        };
+
+        assert_eq!(BASE64_URL_SAFE_NO_PAD.decode(x).unwrap().len(), 32);
+        assert_eq!(BASE64_URL_SAFE_NO_PAD.decode(y).unwrap().len(), 32);
+    }
+}
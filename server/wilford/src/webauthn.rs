@@ -0,0 +1,809 @@
+//! Verification of WebAuthn ceremonies, used to register passkeys and to log in with them.
+//!
+//! Attestation is not requested, the authenticator a passkey is stored on is not verified.
+//! Supported algorithms are ES256, EdDSA and RS256.
+//!
+//! # Further reading
+//! - [Web Authentication Level 2](https://www.w3.org/TR/webauthn-2/)
+//! - [RFC 9053, COSE algorithms](https://datatracker.ietf.org/doc/html/rfc9053)
+
+use crate::config::Config;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use ciborium::Value;
+use p256::ecdsa::signature::Verifier;
+use rsa::{BigUint, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::Url;
+
+/// The time in milliseconds the user has to complete a ceremony
+const TIMEOUT: u64 = 5 * 60 * 1000;
+
+/// The maximum length of credential IDs in bytes.
+/// WebAuthn allows up to 1023 bytes, but authenticators use far shorter IDs.
+const MAX_CREDENTIAL_ID_LENGTH: usize = 255;
+
+/// COSE algorithm identifiers
+const ES256: i64 = -7;
+const EDDSA: i64 = -8;
+const RS256: i64 = -257;
+
+/// Authenticator data flags, WebAuthn Section 6.1
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+#[derive(Debug, Error)]
+pub enum WebauthnError {
+    #[error("Invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Invalid client data: {0}")]
+    ClientData(#[from] serde_json::Error),
+    #[error("Invalid CBOR: {0}")]
+    Cbor(String),
+    #[error("Client data of type {0}, expected {1}")]
+    Type(String, &'static str),
+    #[error("The challenge does not match")]
+    Challenge,
+    #[error("Origin {0} is not allowed")]
+    Origin(String),
+    #[error("Invalid authenticator data")]
+    AuthenticatorData,
+    #[error("The credential is for another relying party")]
+    RpId,
+    #[error("The user was not present")]
+    UserNotPresent,
+    #[error("The credential ID does not match, or is too long")]
+    CredentialId,
+    #[error("Invalid or unsupported public key")]
+    PublicKey,
+    #[error("Invalid signature")]
+    Signature,
+}
+
+/// The relying party passkeys are registered with, i.e. Wilford's frontend
+pub struct RelyingParty {
+    /// The domain passkeys are scoped to
+    pub id: String,
+    pub name: String,
+    /// The origin ceremonies must be performed on
+    pub origin: String,
+}
+
+impl RelyingParty {
+    /// The relying party of the configured frontend.
+    /// The origin is that of the login page, the ID its host unless configured otherwise.
+    pub fn from_config(config: &Config) -> Self {
+        let login_page = Url::parse(&config.http.ui_login_path).ok();
+        let host = login_page
+            .as_ref()
+            .and_then(|u| u.host_str())
+            .unwrap_or_default()
+            .to_string();
+
+        Self {
+            id: config.webauthn.rp_id.clone().unwrap_or(host),
+            name: config.webauthn.rp_name.clone(),
+            origin: login_page
+                .map(|u| u.origin().ascii_serialization())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// `PublicKeyCredentialCreationOptions`, passed to `navigator.credentials.create()`.
+/// WebAuthn Section 5.4
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    challenge: String,
+    rp: RpEntity,
+    user: UserEntity,
+    pub_key_cred_params: Vec<CredentialParameters>,
+    timeout: u64,
+    exclude_credentials: Vec<CredentialDescriptor>,
+    authenticator_selection: AuthenticatorSelection,
+    attestation: &'static str,
+}
+
+/// `PublicKeyCredentialRequestOptions`, passed to `navigator.credentials.get()`.
+/// WebAuthn Section 5.5
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    challenge: String,
+    rp_id: String,
+    timeout: u64,
+    /// Empty, so the user can choose any of their passkeys
+    allow_credentials: Vec<CredentialDescriptor>,
+    user_verification: &'static str,
+}
+
+#[derive(Serialize)]
+struct RpEntity {
+    id: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserEntity {
+    /// The base64url encoded user handle
+    id: String,
+    name: String,
+    display_name: String,
+}
+
+#[derive(Serialize)]
+struct CredentialParameters {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    alg: i64,
+}
+
+#[derive(Serialize)]
+struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthenticatorSelection {
+    resident_key: &'static str,
+    user_verification: &'static str,
+}
+
+impl CreationOptions {
+    /// Options to register a new passkey for the user.
+    /// The user's ID is used as user handle, it does not contain personal information.
+    /// The passkeys the user already registered are excluded, so they are not registered twice.
+    pub fn new(
+        rp: &RelyingParty,
+        challenge: String,
+        user_id: &str,
+        email: &str,
+        name: &str,
+        existing_credentials: Vec<String>,
+    ) -> Self {
+        Self {
+            challenge,
+            rp: RpEntity {
+                id: rp.id.clone(),
+                name: rp.name.clone(),
+            },
+            user: UserEntity {
+                id: BASE64_URL_SAFE_NO_PAD.encode(user_id),
+                name: email.to_string(),
+                display_name: name.to_string(),
+            },
+            pub_key_cred_params: [ES256, EDDSA, RS256]
+                .into_iter()
+                .map(|alg| CredentialParameters {
+                    type_: "public-key",
+                    alg,
+                })
+                .collect(),
+            timeout: TIMEOUT,
+            exclude_credentials: existing_credentials
+                .into_iter()
+                .map(|id| CredentialDescriptor {
+                    type_: "public-key",
+                    id,
+                })
+                .collect(),
+            authenticator_selection: AuthenticatorSelection {
+                // Discoverable credentials allow logging in without entering a username
+                resident_key: "preferred",
+                user_verification: "preferred",
+            },
+            attestation: "none",
+        }
+    }
+}
+
+impl RequestOptions {
+    /// Options to log in with any passkey registered with the relying party.
+    pub fn new(rp: &RelyingParty, challenge: String) -> Self {
+        Self {
+            challenge,
+            rp_id: rp.id.clone(),
+            timeout: TIMEOUT,
+            allow_credentials: Vec::new(),
+            user_verification: "preferred",
+        }
+    }
+}
+
+/// A `PublicKeyCredential` created by `navigator.credentials.create()`, in its JSON representation
+#[derive(Deserialize)]
+pub struct RegistrationCredential {
+    /// The base64url encoded credential ID
+    pub id: String,
+    response: AttestationResponse,
+}
+
+/// `AuthenticatorAttestationResponse`, WebAuthn Section 5.2.1
+#[derive(Deserialize)]
+struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    attestation_object: String,
+}
+
+/// A `PublicKeyCredential` returned by `navigator.credentials.get()`, in its JSON representation
+#[derive(Deserialize)]
+pub struct AuthenticationCredential {
+    /// The base64url encoded credential ID
+    pub id: String,
+    response: AssertionResponse,
+}
+
+/// `AuthenticatorAssertionResponse`, WebAuthn Section 5.2.2
+#[derive(Deserialize)]
+struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    authenticator_data: String,
+    signature: String,
+    #[serde(rename = "userHandle")]
+    user_handle: Option<String>,
+}
+
+/// `CollectedClientData`, WebAuthn Section 5.8.1
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientData {
+    #[serde(rename = "type")]
+    type_: String,
+    challenge: String,
+    origin: String,
+    #[serde(default)]
+    cross_origin: bool,
+}
+
+/// A passkey created in a successful registration ceremony
+#[derive(Debug)]
+pub struct RegisteredCredential {
+    /// The base64url encoded credential ID
+    pub credential_id: String,
+    /// The base64url encoded COSE public key
+    pub public_key: String,
+    pub sign_count: u32,
+}
+
+/// The result of a successful authentication ceremony
+#[derive(Debug)]
+pub struct VerifiedAssertion {
+    /// The signature counter reported by the authenticator
+    pub sign_count: u32,
+    /// Whether the authenticator verified the user, e.g. with a PIN or biometrics
+    pub user_verified: bool,
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    /// The credential ID and COSE public key, only present when registering
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+enum PublicKey {
+    Es256(p256::ecdsa::VerifyingKey),
+    EdDSA(ed25519_compact::PublicKey),
+    Rs256(Box<rsa::pkcs1v15::VerifyingKey<Sha256>>),
+}
+
+impl RegistrationCredential {
+    /// The challenge the client signed.
+    /// Must be checked to be a challenge issued for registration before calling [Self::verify].
+    ///
+    /// # Errors
+    ///
+    /// If the client data is invalid
+    pub fn challenge(&self) -> Result<String, WebauthnError> {
+        Ok(parse_client_data(&self.response.client_data_json)?
+            .1
+            .challenge)
+    }
+
+    /// Verify the registration ceremony, WebAuthn Section 7.1.
+    ///
+    /// # Errors
+    ///
+    /// If the credential is invalid, or was not created for the challenge and relying party
+    pub fn verify(
+        &self,
+        rp: &RelyingParty,
+        challenge: &str,
+    ) -> Result<RegisteredCredential, WebauthnError> {
+        let (_, client_data) = parse_client_data(&self.response.client_data_json)?;
+        client_data.check(rp, "webauthn.create", challenge)?;
+
+        let attestation_object = decode(&self.response.attestation_object)?;
+        let attestation_object: Value = ciborium::from_reader(attestation_object.as_slice())
+            .map_err(|e| WebauthnError::Cbor(e.to_string()))?;
+
+        // The attestation statement is not verified, as attestation is not requested
+        let authenticator_data = map_get(&attestation_object, &Value::Text("authData".into()))
+            .and_then(Value::as_bytes)
+            .ok_or(WebauthnError::AuthenticatorData)?;
+
+        let authenticator_data = AuthenticatorData::parse(authenticator_data)?;
+        authenticator_data.check(rp)?;
+
+        let (credential_id, public_key) = authenticator_data
+            .attested_credential
+            .ok_or(WebauthnError::AuthenticatorData)?;
+
+        if credential_id.len() > MAX_CREDENTIAL_ID_LENGTH || decode(&self.id)? != credential_id {
+            return Err(WebauthnError::CredentialId);
+        }
+
+        // Reject keys we would not be able to verify signatures with
+        PublicKey::parse(&public_key)?;
+
+        Ok(RegisteredCredential {
+            credential_id: BASE64_URL_SAFE_NO_PAD.encode(credential_id),
+            public_key: BASE64_URL_SAFE_NO_PAD.encode(public_key),
+            sign_count: authenticator_data.sign_count,
+        })
+    }
+}
+
+impl AuthenticationCredential {
+    /// The challenge the client signed.
+    /// Must be checked to be a challenge issued for authentication before calling [Self::verify].
+    ///
+    /// # Errors
+    ///
+    /// If the client data is invalid
+    pub fn challenge(&self) -> Result<String, WebauthnError> {
+        Ok(parse_client_data(&self.response.client_data_json)?
+            .1
+            .challenge)
+    }
+
+    /// The user handle returned by the authenticator, the ID of the user the passkey was registered for.
+    /// Only returned by discoverable credentials.
+    pub fn user_handle(&self) -> Option<Vec<u8>> {
+        self.response
+            .user_handle
+            .as_deref()
+            .filter(|h| !h.is_empty())
+            .and_then(|h| decode(h).ok())
+    }
+
+    /// Verify the authentication ceremony with the public key of the passkey, WebAuthn Section 7.2.
+    /// The caller is responsible for checking the signature counter.
+    ///
+    /// # Errors
+    ///
+    /// If the assertion is invalid, or was not created for the challenge and relying party
+    pub fn verify(
+        &self,
+        rp: &RelyingParty,
+        challenge: &str,
+        public_key: &str,
+    ) -> Result<VerifiedAssertion, WebauthnError> {
+        let (client_data_json, client_data) = parse_client_data(&self.response.client_data_json)?;
+        client_data.check(rp, "webauthn.get", challenge)?;
+
+        let raw_authenticator_data = decode(&self.response.authenticator_data)?;
+        let authenticator_data = AuthenticatorData::parse(&raw_authenticator_data)?;
+        authenticator_data.check(rp)?;
+
+        // The signature is over the authenticator data and the hash of the client data
+        let mut signed = raw_authenticator_data;
+        signed.extend_from_slice(&Sha256::digest(&client_data_json));
+
+        PublicKey::parse(&decode(public_key)?)?
+            .verify(&signed, &decode(&self.response.signature)?)?;
+
+        Ok(VerifiedAssertion {
+            sign_count: authenticator_data.sign_count,
+            user_verified: authenticator_data.flags & FLAG_USER_VERIFIED != 0,
+        })
+    }
+}
+
+impl ClientData {
+    fn check(
+        &self,
+        rp: &RelyingParty,
+        type_: &'static str,
+        challenge: &str,
+    ) -> Result<(), WebauthnError> {
+        if self.type_ != type_ {
+            return Err(WebauthnError::Type(self.type_.clone(), type_));
+        }
+
+        if self.challenge != challenge {
+            return Err(WebauthnError::Challenge);
+        }
+
+        // The login page is never embedded in another origin
+        if self.origin != rp.origin || self.cross_origin {
+            return Err(WebauthnError::Origin(self.origin.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+impl AuthenticatorData {
+    /// Parse authenticator data, WebAuthn Section 6.1
+    fn parse(data: &[u8]) -> Result<Self, WebauthnError> {
+        if data.len() < 37 {
+            return Err(WebauthnError::AuthenticatorData);
+        }
+
+        let rp_id_hash = data[..32].try_into().expect("Slice has length 32");
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes(data[33..37].try_into().expect("Slice has length 4"));
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+            // AAGUID, followed by the length of the credential ID, WebAuthn Section 6.5.1
+            let rest = data
+                .get(37 + 16..)
+                .ok_or(WebauthnError::AuthenticatorData)?;
+            let id_length = u16::from_be_bytes(
+                rest.get(..2)
+                    .ok_or(WebauthnError::AuthenticatorData)?
+                    .try_into()
+                    .expect("Slice has length 2"),
+            ) as usize;
+
+            let credential_id = rest
+                .get(2..2 + id_length)
+                .ok_or(WebauthnError::AuthenticatorData)?;
+
+            // The public key is followed by extensions, if any.
+            // Decoding advances the reader past the key only.
+            let key_start = &rest[2 + id_length..];
+            let mut reader = key_start;
+            let _: Value = ciborium::from_reader(&mut reader)
+                .map_err(|e| WebauthnError::Cbor(e.to_string()))?;
+            let public_key = &key_start[..key_start.len() - reader.len()];
+
+            Some((credential_id.to_vec(), public_key.to_vec()))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    fn check(&self, rp: &RelyingParty) -> Result<(), WebauthnError> {
+        if self.rp_id_hash[..] != Sha256::digest(rp.id.as_bytes())[..] {
+            return Err(WebauthnError::RpId);
+        }
+
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebauthnError::UserNotPresent);
+        }
+
+        Ok(())
+    }
+}
+
+impl PublicKey {
+    /// Parse a COSE key, RFC 9053 Section 7
+    fn parse(cose_key: &[u8]) -> Result<Self, WebauthnError> {
+        let key: Value =
+            ciborium::from_reader(cose_key).map_err(|e| WebauthnError::Cbor(e.to_string()))?;
+
+        let int = |label: i64| {
+            map_get(&key, &Value::Integer(label.into()))
+                .and_then(Value::as_integer)
+                .and_then(|i| i64::try_from(i).ok())
+        };
+        let bytes = |label: i64| {
+            map_get(&key, &Value::Integer(label.into()))
+                .and_then(Value::as_bytes)
+                .ok_or(WebauthnError::PublicKey)
+        };
+
+        // Key type, algorithm and curve
+        match (int(1), int(3), int(-1)) {
+            // EC2 on P-256
+            (Some(2), Some(ES256), Some(1)) => {
+                let mut point = vec![0x04];
+                point.extend_from_slice(bytes(-2)?);
+                point.extend_from_slice(bytes(-3)?);
+
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(&point)
+                    .map(Self::Es256)
+                    .map_err(|_| WebauthnError::PublicKey)
+            }
+            // OKP on Ed25519
+            (Some(1), Some(EDDSA), Some(6)) => ed25519_compact::PublicKey::from_slice(bytes(-2)?)
+                .map(Self::EdDSA)
+                .map_err(|_| WebauthnError::PublicKey),
+            // RSA, where -1 is the modulus rather than a curve
+            (Some(3), Some(RS256), _) => RsaPublicKey::new(
+                BigUint::from_bytes_be(bytes(-1)?),
+                BigUint::from_bytes_be(bytes(-2)?),
+            )
+            .map(|key| Self::Rs256(Box::new(rsa::pkcs1v15::VerifyingKey::new(key))))
+            .map_err(|_| WebauthnError::PublicKey),
+            _ => Err(WebauthnError::PublicKey),
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), WebauthnError> {
+        let valid = match self {
+            // ECDSA signatures are DER encoded, WebAuthn Section 6.5.6
+            Self::Es256(key) => p256::ecdsa::Signature::from_der(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::EdDSA(key) => ed25519_compact::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::Rs256(key) => rsa::pkcs1v15::Signature::try_from(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(WebauthnError::Signature)
+        }
+    }
+}
+
+/// Decode the client data, returning its raw bytes as well
+fn parse_client_data(client_data_json: &str) -> Result<(Vec<u8>, ClientData), WebauthnError> {
+    let raw = decode(client_data_json)?;
+    let client_data = serde_json::from_slice(&raw)?;
+    Ok((raw, client_data))
+}
+
+/// Decode base64url, with or without padding
+fn decode(value: &str) -> Result<Vec<u8>, WebauthnError> {
+    Ok(BASE64_URL_SAFE_NO_PAD.decode(value.trim_end_matches('='))?)
+}
+
+/// Get a value from a CBOR map
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+
+    fn relying_party() -> RelyingParty {
+        RelyingParty {
+            id: "auth.example.com".to_string(),
+            name: "Wilford".to_string(),
+            origin: "https://auth.example.com".to_string(),
+        }
+    }
+
+    fn cbor(value: &Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(value, &mut buf).unwrap();
+        buf
+    }
+
+    fn client_data(type_: &str, challenge: &str, origin: &str) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(
+            serde_json::json!({
+                "type": type_,
+                "challenge": challenge,
+                "origin": origin,
+            })
+            .to_string(),
+        )
+    }
+
+    fn authenticator_data(
+        rp: &RelyingParty,
+        flags: u8,
+        sign_count: u32,
+        attested: Option<(&[u8], &[u8])>,
+    ) -> Vec<u8> {
+        let mut data = Sha256::digest(rp.id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+
+        if let Some((credential_id, public_key)) = attested {
+            data.extend_from_slice(&[0; 16]);
+            data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+            data.extend_from_slice(credential_id);
+            data.extend_from_slice(public_key);
+        }
+
+        data
+    }
+
+    fn es256_cose_key(key: &p256::ecdsa::SigningKey) -> Vec<u8> {
+        let point = key.verifying_key().to_encoded_point(false);
+        cbor(&Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer(ES256.into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(point.x().unwrap().to_vec()),
+            ),
+            (
+                Value::Integer((-3).into()),
+                Value::Bytes(point.y().unwrap().to_vec()),
+            ),
+        ]))
+    }
+
+    fn registration(
+        rp: &RelyingParty,
+        challenge: &str,
+        credential_id: &[u8],
+        cose_key: &[u8],
+    ) -> RegistrationCredential {
+        let authenticator_data = authenticator_data(
+            rp,
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            0,
+            Some((credential_id, cose_key)),
+        );
+        let attestation_object = cbor(&Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("none".into())),
+            (Value::Text("attStmt".into()), Value::Map(vec![])),
+            (
+                Value::Text("authData".into()),
+                Value::Bytes(authenticator_data),
+            ),
+        ]));
+
+        serde_json::from_value(serde_json::json!({
+            "id": BASE64_URL_SAFE_NO_PAD.encode(credential_id),
+            "response": {
+                "clientDataJSON": client_data("webauthn.create", challenge, &rp.origin),
+                "attestationObject": BASE64_URL_SAFE_NO_PAD.encode(attestation_object),
+            }
+        }))
+        .unwrap()
+    }
+
+    fn assertion(
+        rp: &RelyingParty,
+        challenge: &str,
+        sign_count: u32,
+        sign: impl Fn(&[u8]) -> Vec<u8>,
+    ) -> AuthenticationCredential {
+        let authenticator_data =
+            authenticator_data(rp, FLAG_USER_PRESENT | FLAG_USER_VERIFIED, sign_count, None);
+        let client_data = client_data("webauthn.get", challenge, &rp.origin);
+
+        let mut signed = authenticator_data.clone();
+        signed.extend_from_slice(&Sha256::digest(decode(&client_data).unwrap()));
+
+        serde_json::from_value(serde_json::json!({
+            "id": "credential",
+            "response": {
+                "clientDataJSON": client_data,
+                "authenticatorData": BASE64_URL_SAFE_NO_PAD.encode(authenticator_data),
+                "signature": BASE64_URL_SAFE_NO_PAD.encode(sign(&signed)),
+                "userHandle": BASE64_URL_SAFE_NO_PAD.encode("user"),
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn es256_ceremonies() {
+        let rp = relying_party();
+        let key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+
+        let credential = registration(&rp, "register", b"credential", &es256_cose_key(&key));
+        assert_eq!(credential.challenge().unwrap(), "register");
+        let registered = credential.verify(&rp, "register").unwrap();
+
+        let sign = |message: &[u8]| {
+            let signature: p256::ecdsa::Signature = key.sign(message);
+            signature.to_der().as_bytes().to_vec()
+        };
+
+        let credential = assertion(&rp, "login", 5, sign);
+        assert_eq!(credential.user_handle().unwrap(), b"user");
+        let verified = credential
+            .verify(&rp, "login", &registered.public_key)
+            .unwrap();
+        assert_eq!(verified.sign_count, 5);
+        assert!(verified.user_verified);
+
+        // Signed by another key
+        let other = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let credential = assertion(&rp, "login", 5, |message| {
+            let signature: p256::ecdsa::Signature = other.sign(message);
+            signature.to_der().as_bytes().to_vec()
+        });
+        assert!(matches!(
+            credential.verify(&rp, "login", &registered.public_key),
+            Err(WebauthnError::Signature)
+        ));
+    }
+
+    #[test]
+    fn eddsa_assertion() {
+        let rp = relying_party();
+        let key_pair = ed25519_compact::KeyPair::generate();
+        let cose_key = cbor(&Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(1.into())),
+            (Value::Integer(3.into()), Value::Integer(EDDSA.into())),
+            (Value::Integer((-1).into()), Value::Integer(6.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(key_pair.pk.to_vec()),
+            ),
+        ]));
+
+        let credential = assertion(&rp, "login", 0, |message| {
+            key_pair.sk.sign(message, None).to_vec()
+        });
+        credential
+            .verify(&rp, "login", &BASE64_URL_SAFE_NO_PAD.encode(cose_key))
+            .unwrap();
+    }
+
+    #[test]
+    fn wrong_relying_party() {
+        let rp = relying_party();
+        let key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let credential = registration(&rp, "register", b"credential", &es256_cose_key(&key));
+
+        // Another origin
+        let other_origin = RelyingParty {
+            origin: "https://evil.example.com".to_string(),
+            ..relying_party()
+        };
+        assert!(matches!(
+            credential.verify(&other_origin, "register"),
+            Err(WebauthnError::Origin(_))
+        ));
+
+        // Another relying party ID
+        let other_id = RelyingParty {
+            id: "example.com".to_string(),
+            ..relying_party()
+        };
+        assert!(matches!(
+            credential.verify(&other_id, "register"),
+            Err(WebauthnError::RpId)
+        ));
+
+        // Another challenge
+        assert!(matches!(
+            credential.verify(&rp, "other"),
+            Err(WebauthnError::Challenge)
+        ));
+    }
+
+    #[test]
+    fn unsupported_key() {
+        let rp = relying_party();
+        // EC2 on P-384
+        let cose_key = cbor(&Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer((-35).into())),
+            (Value::Integer((-1).into()), Value::Integer(2.into())),
+        ]));
+
+        let credential = registration(&rp, "register", b"credential", &cose_key);
+        assert!(matches!(
+            credential.verify(&rp, "register"),
+            Err(WebauthnError::PublicKey)
+        ));
+    }
+}
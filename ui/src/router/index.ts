@@ -61,6 +61,16 @@ const routes = [
             name: "PasswordForgotten",
             component: () => import('@/views/PasswordForgotten.vue')
           },
+          {
+            path: '/reset-password',
+            name: 'ResetPassword',
+            component: () => import('@/views/ResetPassword.vue')
+          },
+          {
+            path: '/logout',
+            name: 'Logout',
+            component: () => import('@/views/Logout.vue')
+          },
           {
             path: '/verify-email',
             name: 'VerifyEmail',
@@ -2,6 +2,7 @@ import {ApiError, ApiErrorKind} from "@/scripts/core/error";
 import {Result} from "@/scripts/core/result";
 import {fetch1} from "@/scripts/core/fetch1";
 import {server} from "@/main";
+import {AuthenticationCredential} from "@/scripts/passkey";
 
 export enum LoginStatus {
   INVALID_CREDENTIALS,
@@ -20,6 +21,7 @@ export class Auth {
     password: string,
     authorizationCode: string,
     totpCode?: string,
+    passkey?: AuthenticationCredential,
   ): Promise<Result<LoginStatus, ApiError>> {
     const result = await (await fetch1(`${server}/api/v1/auth/login`, {
       method: 'POST',
@@ -30,6 +32,7 @@ export class Auth {
         'username': username,
         'password': password,
         'totp_code': totpCode,
+        'passkey': passkey,
         'authorization': authorizationCode,
       })
     }))
@@ -71,4 +74,13 @@ export class Auth {
     
     return result;
   }
+
+  /**
+   * Log the user out of the current login session of Wilford.
+   * The browser is redirected to the logout endpoint, which asks the user to confirm the logout.
+   */
+  static logout() {
+    window.localStorage.removeItem('access_token');
+    window.location.href = `${server}/api/oauth/logout`;
+  }
 }
\ No newline at end of file
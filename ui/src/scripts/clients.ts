@@ -3,36 +3,46 @@ import {server} from "@/main";
 interface _ClientInfo {
     name: string,
     client_id: string,
-    client_secret: string,
+    redirect_uris: string[],
+    allow_loopback_any_port: boolean,
+}
+
+interface _InternalClientInfo {
+    name: string,
+    client_id: string,
     redirect_uri: string,
 }
 
 export class ClientInfo {
     name: string;
     clientId: string;
-    clientSecret: string;
-    redirectUri: string;
+    redirectUris: string[];
+    allowLoopbackAnyPort: boolean;
 
-    constructor(name: string, client_id: string, client_secret: string, redirect_uri: string) {
+    constructor(name: string, client_id: string, redirect_uris: string[], allow_loopback_any_port: boolean) {
         this.name = name;
         this.clientId = client_id;
-        this.clientSecret = client_secret;
-        this.redirectUri = redirect_uri;
+        this.redirectUris = redirect_uris;
+        this.allowLoopbackAnyPort = allow_loopback_any_port;
     }
 
     static async getInternal() : Promise<ClientInfo> {
         const r = await fetch(`${server}/api/v1/clients/internal`);
-        const j: _ClientInfo = await r.json();
-        return new ClientInfo(j.name, j.client_id, j.client_secret, j.redirect_uri);
+        const j: _InternalClientInfo = await r.json();
+        return new ClientInfo(j.name, j.client_id, [j.redirect_uri], false);
     }
 
     getAuthorizationRedirect(manageScopes: boolean = false): string {
         const scopesParam = manageScopes ? `&scope=wilford.manage` : "";
-        return `${server}/api/oauth/authorize?client_id=${this.clientId}&response_type=token${scopesParam}&redirect_uri=${this.redirectUri}`
+        return `${server}/api/oauth/authorize?client_id=${this.clientId}&response_type=token${scopesParam}&redirect_uri=${this.redirectUris[0]}`
     }
 
-    static async new(name: string, redirectUri: string) {
-        await fetch(`${server}/api/v1/clients/add`, {
+    /**
+     * Create a new client.
+     * @return The secret of the client. It cannot be retrieved later.
+     */
+    static async new(name: string, redirectUris: string[], allowLoopbackAnyPort: boolean): Promise<string> {
+        const r = await fetch(`${server}/api/v1/clients/add`, {
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json',
@@ -40,9 +50,17 @@ export class ClientInfo {
             },
             body: JSON.stringify({
                 name: name,
-                redirect_uri: redirectUri,
+                redirect_uris: redirectUris,
+                allow_loopback_any_port: allowLoopbackAnyPort,
             })
         })
+
+        interface Response {
+            client_secret: string,
+        }
+
+        const j: Response = await r.json();
+        return j.client_secret;
     }
 
     static async list(): Promise<ClientInfo[]> {
@@ -57,7 +75,7 @@ export class ClientInfo {
         }
 
         const j: Response = await r.json();
-        return j.clients.map(c => new ClientInfo(c.name, c.client_id, c.client_secret, c.redirect_uri));
+        return j.clients.map(c => new ClientInfo(c.name, c.client_id, c.redirect_uris, c.allow_loopback_any_port));
     }
 
     async remove() {
@@ -72,4 +90,30 @@ export class ClientInfo {
             })
         })
     }
+
+    /**
+     * Replace the secret of the client.
+     * @param gracePeriod The number of seconds the current secret remains valid
+     * @return The new secret. It cannot be retrieved later.
+     */
+    async rotateSecret(gracePeriod: number): Promise<string> {
+        const r = await fetch(`${server}/api/v1/clients/rotate-secret`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+                'Authorization': `Bearer ${window.localStorage.getItem('access_token')}`
+            },
+            body: JSON.stringify({
+                client_id: this.clientId,
+                grace_period: gracePeriod,
+            })
+        })
+
+        interface Response {
+            client_secret: string,
+        }
+
+        const j: Response = await r.json();
+        return j.client_secret;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+import {ApiError} from "@/scripts/core/error";
+import {Result} from "@/scripts/core/result";
+import {fetch1} from "@/scripts/core/fetch1";
+import {server} from "@/main";
+import {LoginStatus} from "@/scripts/auth";
+
+/**
+ * A credential returned by `navigator.credentials.get()`, in the form the server expects.
+ * Binary values are base64url encoded.
+ */
+export interface AuthenticationCredential {
+  id: string,
+  response: {
+    clientDataJSON: string,
+    authenticatorData: string,
+    signature: string,
+    userHandle?: string,
+  }
+}
+
+export function base64UrlEncode(buffer: ArrayBuffer): string {
+  const binary = String.fromCharCode(...new Uint8Array(buffer));
+  return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+}
+
+export function base64UrlDecode(value: string): ArrayBuffer {
+  const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+  const binary = atob(base64.padEnd(base64.length + (4 - base64.length % 4) % 4, '='));
+  return Uint8Array.from(binary, c => c.charCodeAt(0)).buffer;
+}
+
+export class Passkey {
+
+  /**
+   * Whether the browser supports passkeys
+   */
+  static isSupported(): boolean {
+    return window.PublicKeyCredential !== undefined;
+  }
+
+  /**
+   * Ask the browser for a passkey, for the pending authorization.
+   * Returns `undefined` if the user cancelled.
+   */
+  static async assert(authorizationCode: string): Promise<Result<AuthenticationCredential | undefined, ApiError>> {
+    return await (await fetch1(`${server}/api/v1/auth/passkey/challenge`, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json'
+      },
+      body: JSON.stringify({
+        'authorization': authorizationCode,
+      })
+    }))
+      .map1(async (response) => {
+        interface Payload {
+          challenge: string,
+          timeout: number,
+          rpId: string,
+          userVerification: UserVerificationRequirement,
+        }
+
+        const payload: Payload = await response.json();
+
+        try {
+          const credential = <PublicKeyCredential | null> await navigator.credentials.get({
+            publicKey: {
+              challenge: base64UrlDecode(payload.challenge),
+              timeout: payload.timeout,
+              rpId: payload.rpId,
+              userVerification: payload.userVerification,
+            }
+          });
+
+          if(!credential) {
+            return undefined;
+          }
+
+          const assertion = <AuthenticatorAssertionResponse> credential.response;
+          return {
+            id: credential.id,
+            response: {
+              clientDataJSON: base64UrlEncode(assertion.clientDataJSON),
+              authenticatorData: base64UrlEncode(assertion.authenticatorData),
+              signature: base64UrlEncode(assertion.signature),
+              userHandle: assertion.userHandle ? base64UrlEncode(assertion.userHandle) : undefined,
+            }
+          };
+        } catch (e) {
+          // The user cancelled, or no passkey is available
+          console.warn(e);
+          return undefined;
+        }
+      });
+  }
+
+  /**
+   * Log in with a passkey, without password.
+   */
+  static async login(
+    authorizationCode: string,
+    credential: AuthenticationCredential,
+  ): Promise<Result<LoginStatus, ApiError>> {
+    return await (await fetch1(`${server}/api/v1/auth/passkey/login`, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json'
+      },
+      body: JSON.stringify({
+        'authorization': authorizationCode,
+        'credential': credential,
+      })
+    }))
+      .map1(async (response) => {
+        interface Payload {
+          status: boolean,
+        }
+
+        const payload: Payload = await response.json();
+        return payload.status ? LoginStatus.OK : LoginStatus.INVALID_CREDENTIALS;
+      });
+  }
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+import {server} from "@/main";
+import {ApiError} from "@/scripts/core/error";
+import {fetch1} from "@/scripts/core/fetch1";
+import {Result} from "@/scripts/core/result";
+
+interface _Session {
+    session_id: string,
+    auth_time: number,
+    amr: string[],
+    last_used_at: number,
+    expires_at: number,
+    user_agent?: string,
+    current: boolean,
+}
+
+export class Session {
+    sessionId: string;
+    authTime: Date;
+    amr: string[];
+    lastUsedAt: Date;
+    expiresAt: Date;
+    userAgent?: string;
+    current: boolean;
+
+    constructor(session: _Session) {
+        this.sessionId = session.session_id;
+        this.authTime = new Date(session.auth_time * 1000);
+        this.amr = session.amr;
+        this.lastUsedAt = new Date(session.last_used_at * 1000);
+        this.expiresAt = new Date(session.expires_at * 1000);
+        this.userAgent = session.user_agent;
+        this.current = session.current;
+    }
+
+    static async list(): Promise<Result<Session[], ApiError>> {
+        return await (await fetch1(`${server}/api/v1/user/sessions/list`))
+          .map1(async (response) => {
+              interface Payload {
+                  sessions: _Session[]
+              }
+
+              const payload: Payload = await response.json();
+              return payload.sessions.map(s => new Session(s));
+          });
+    }
+
+    async terminate(): Promise<Result<void, ApiError>> {
+        return (await fetch1(`${server}/api/v1/user/sessions/terminate`, {
+            method: 'DELETE',
+            headers: {
+                'Content-Type': 'application/json'
+            },
+            body: JSON.stringify({
+                session_id: this.sessionId,
+            })
+        })).mapVoid();
+    }
+}
@@ -161,6 +161,19 @@ export class User {
         })).mapVoid();
     }
     
+    static async resetPasswordWithToken(token: string, newPassword: string): Promise<Result<void, ApiError>> {
+        return (await fetch1(`${server}/api/v1/user/reset-password`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json'
+            },
+            body: JSON.stringify({
+                token: token,
+                new_password: newPassword,
+            })
+        })).mapVoid();
+    }
+    
     static async verifyEmail(userId: string, code: string): Promise<Result<void, ApiError>> {
         return (await fetch1(`${server}/api/v1/user/verify-email?user_id=${userId}&verification_code=${code}`, {
             method: 'POST',
@@ -6,16 +6,25 @@ pub struct IntrospectionResult {
     pub active: bool,
     pub scope: String,
     pub client_id: String,
+    /// Empty for tokens issued using the client credentials grant,
+    /// see [IntrospectionResult::is_client_credentials]
+    #[serde(default)]
     pub username: String,
     pub token_type: String,
     pub exp: i64,
     pub iat: i64,
     pub nbf: i64,
-    /// Espo user id
+    /// Espo user id, or the client ID for tokens issued using the client credentials grant
     pub sub: String,
 }
 
 impl IntrospectionResult {
+    /// Whether the token was issued to the client itself, using the client credentials grant,
+    /// rather than on behalf of a user.
+    pub fn is_client_credentials(&self) -> bool {
+        self.sub == self.client_id
+    }
+
     pub async fn new(wilford: &str, cat: &str, token: &str, scope: Option<&str>) -> Result<Self> {
         #[derive(Serialize)]
         struct Query<'a> {